@@ -0,0 +1,98 @@
+//! Benchmarks of the verification path, on proofs generated by the synthetic chain.
+//!
+//! Run with `cargo bench --features bench` (add `rayon` to also compare the
+//! parallel tree builder).
+
+use ancestry_prover::merkle::BlockRootsTree;
+use ancestry_prover::sim::SimChain;
+use ancestry_prover::verify_block_roots_proof;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ethereum_consensus::capella::presets::mainnet::SLOTS_PER_HISTORICAL_ROOT;
+use ethereum_consensus::ssz::prelude::Node;
+
+const WINDOW: u64 = SLOTS_PER_HISTORICAL_ROOT as u64;
+const ANCHOR_SLOT: u64 = 2 * WINDOW;
+
+fn target_slots(count: u64) -> Vec<u64> {
+    (0..count)
+        .map(|i| ANCHOR_SLOT - 1 - i * (WINDOW / count))
+        .collect()
+}
+
+fn single_vs_compact(c: &mut Criterion) {
+    let chain = SimChain::new(ANCHOR_SLOT);
+    let anchor_root = chain.state_root(ANCHOR_SLOT).0;
+    let target_slot = ANCHOR_SLOT - 1600;
+    let single = chain.prove_block_root(target_slot, ANCHOR_SLOT);
+    let compact = chain
+        .prove_block_roots(&[target_slot], ANCHOR_SLOT)
+        .unwrap();
+
+    let mut group = c.benchmark_group("verify");
+    group.bench_function("single", |b| {
+        b.iter(|| verify_block_roots_proof(anchor_root, target_slot, WINDOW, black_box(&single)))
+    });
+    group.bench_function("compact", |b| {
+        b.iter(|| verify_block_roots_proof(anchor_root, target_slot, WINDOW, black_box(&compact)))
+    });
+    group.finish();
+}
+
+fn batch_throughput(c: &mut Criterion) {
+    let chain = SimChain::new(ANCHOR_SLOT);
+    let anchor_root = chain.state_root(ANCHOR_SLOT).0;
+
+    let mut group = c.benchmark_group("batch");
+    for count in [16, 256] {
+        let slots = target_slots(count);
+        let singles = slots
+            .iter()
+            .map(|slot| chain.prove_block_root(*slot, ANCHOR_SLOT))
+            .collect::<Vec<_>>();
+        let compact = chain.prove_block_roots(&slots, ANCHOR_SLOT).unwrap();
+
+        group.throughput(Throughput::Elements(count));
+        group.bench_with_input(BenchmarkId::new("single", count), &slots, |b, slots| {
+            b.iter(|| {
+                for (slot, proof) in slots.iter().zip(&singles) {
+                    verify_block_roots_proof(anchor_root, *slot, WINDOW, black_box(proof)).unwrap();
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("compact", count), &slots, |b, slots| {
+            b.iter(|| {
+                for slot in slots {
+                    verify_block_roots_proof(anchor_root, *slot, WINDOW, black_box(&compact))
+                        .unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn merkleization(c: &mut Criterion) {
+    let periods = (0..8u8)
+        .map(|i| vec![Node::repeat_byte(i); SLOTS_PER_HISTORICAL_ROOT])
+        .collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("merkleization");
+    group.throughput(Throughput::Elements(periods.len() as u64));
+    group.bench_function("serial", |b| {
+        b.iter(|| {
+            periods
+                .iter()
+                .map(|block_roots| BlockRootsTree::new(black_box(block_roots)))
+                .collect::<Vec<_>>()
+        })
+    });
+    #[cfg(feature = "rayon")]
+    group.bench_function("parallel", |b| {
+        let builder = ancestry_prover::ParallelTreeBuilder::new();
+        b.iter(|| builder.build(black_box(&periods)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, single_vs_compact, batch_throughput, merkleization);
+criterion_main!(benches);
@@ -0,0 +1,104 @@
+//! Selection of the anchor block among the ones a destination verifier accepts,
+//! e.g. the roots in the EIP-4788 ring buffer or those stored by a light client contract.
+
+#[cfg(feature = "providers")]
+use crate::errors::AncestryProverError;
+use crate::prover::is_provable;
+use crate::provider::StateId;
+#[cfg(feature = "providers")]
+use async_trait::async_trait;
+use ethereum_consensus::ssz::prelude::Node;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "providers")]
+use std::future::Future;
+
+/// Block that proofs can be anchored to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Anchor {
+    pub slot: u64,
+    pub state_root: Node,
+}
+
+/// Checkpoint of the anchor chain that an anchor was resolved from, for destinations
+/// that accept justified checkpoints, fresher by about 2 epochs, and not only finalized ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Checkpoint {
+    Justified,
+    Finalized,
+}
+
+impl Checkpoint {
+    /// Returns the checkpoint designated by `state_id`, if any.
+    pub fn from_state_id(state_id: &StateId) -> Option<Self> {
+        match state_id {
+            StateId::Justified => Some(Checkpoint::Justified),
+            StateId::Finalized => Some(Checkpoint::Finalized),
+            _ => None,
+        }
+    }
+}
+
+/// Anchor tagged with the checkpoint it was resolved from, if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaggedAnchor {
+    pub anchor: Anchor,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<Checkpoint>,
+}
+
+/// Source of the anchors currently accepted by the destination of the proofs.
+///
+/// Implemented for async closures, e.g.
+///
+/// ```ignore
+/// let source = || async { Ok(read_stored_roots().await) };
+/// let (anchor, proof) = prover.prove_with_anchor_source(target_slot, &source).await?;
+/// ```
+#[cfg(feature = "providers")]
+#[async_trait]
+pub trait AnchorSource: Send + Sync {
+    async fn accepted_anchors(&self) -> Result<Vec<Anchor>, AncestryProverError>;
+}
+
+#[cfg(feature = "providers")]
+#[async_trait]
+impl<F, Fut> AnchorSource for F
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Vec<Anchor>, AncestryProverError>> + Send,
+{
+    async fn accepted_anchors(&self) -> Result<Vec<Anchor>, AncestryProverError> {
+        self().await
+    }
+}
+
+/// Returns the latest anchor that the block of `target_slot` can be proven against,
+/// as the most recent anchors stay accepted the longest.
+pub fn select_anchor(anchors: &[Anchor], target_slot: u64) -> Option<&Anchor> {
+    anchors
+        .iter()
+        .filter(|anchor| is_provable(target_slot, anchor.slot))
+        .max_by_key(|anchor| anchor.slot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anchor(slot: u64) -> Anchor {
+        Anchor {
+            slot,
+            state_root: Node::repeat_byte(slot as u8),
+        }
+    }
+
+    #[test]
+    fn it_should_select_the_latest_provable_anchor() {
+        let anchors = [anchor(100), anchor(9_000), anchor(300), anchor(50)];
+
+        assert_eq!(select_anchor(&anchors, 60), Some(&anchor(300)));
+        assert_eq!(select_anchor(&anchors, 8_999), Some(&anchor(9_000)));
+        assert_eq!(select_anchor(&anchors, 9_000), None);
+    }
+}
@@ -0,0 +1,170 @@
+//! Lock files (`anchors.lock`) pinning the anchor used for each range of targets,
+//! so that regenerating an archive of proofs later yields identical artifacts.
+
+use crate::anchor::Anchor;
+use crate::errors::AncestryProverError;
+use crate::prover::is_provable;
+use ethereum_consensus::ssz::prelude::Node;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Anchor pinned for the targets of `first_slot..=last_slot`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinnedAnchor {
+    pub first_slot: u64,
+    pub last_slot: u64,
+    pub slot: u64,
+    pub state_root: Node,
+    pub block_root: Node,
+}
+
+impl PinnedAnchor {
+    pub fn anchor(&self) -> Anchor {
+        Anchor {
+            slot: self.slot,
+            state_root: self.state_root,
+        }
+    }
+}
+
+/// Anchors pinned per range of targets, in ascending order of range.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnchorLock {
+    pub anchors: Vec<PinnedAnchor>,
+}
+
+impl AnchorLock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pins `anchor` (of block `block_root`) for the targets of `first_slot..=last_slot`,
+    /// failing with [`AncestryProverError::InvalidAnchor`] if the anchor can't prove
+    /// them all, or if the range overlaps a pinned one.
+    pub fn pin(
+        &mut self,
+        first_slot: u64,
+        last_slot: u64,
+        anchor: Anchor,
+        block_root: Node,
+    ) -> Result<(), AncestryProverError> {
+        let range = format!("{first_slot}..={last_slot}");
+        if first_slot > last_slot
+            || !is_provable(first_slot, anchor.slot)
+            || !is_provable(last_slot, anchor.slot)
+        {
+            return Err(AncestryProverError::InvalidAnchor(format!(
+                "anchor at slot {} can't prove the targets {range}",
+                anchor.slot
+            )));
+        }
+
+        let position = self
+            .anchors
+            .partition_point(|pinned| pinned.last_slot < first_slot);
+        if let Some(pinned) = self.anchors.get(position) {
+            if pinned.first_slot <= last_slot {
+                return Err(AncestryProverError::InvalidAnchor(format!(
+                    "targets {range} overlap the pinned targets {}..={}",
+                    pinned.first_slot, pinned.last_slot
+                )));
+            }
+        }
+
+        self.anchors.insert(
+            position,
+            PinnedAnchor {
+                first_slot,
+                last_slot,
+                slot: anchor.slot,
+                state_root: anchor.state_root,
+                block_root,
+            },
+        );
+        Ok(())
+    }
+
+    /// Returns the anchor pinned for `target_slot`.
+    pub fn anchor_for(&self, target_slot: u64) -> Option<&PinnedAnchor> {
+        let position = self
+            .anchors
+            .partition_point(|pinned| pinned.last_slot < target_slot);
+        self.anchors
+            .get(position)
+            .filter(|pinned| pinned.first_slot <= target_slot)
+    }
+
+    /// Reads a lock file written by [`AnchorLock::write`].
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let lock: Self = serde_json::from_slice(&fs::read(path)?)?;
+        let sorted = lock
+            .anchors
+            .windows(2)
+            .all(|pair| pair[0].last_slot < pair[1].first_slot);
+        if !sorted {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Overlapping or unsorted anchor pins",
+            ));
+        }
+        Ok(lock)
+    }
+
+    /// Writes the lock file as pretty-printed JSON, to be reviewed and versioned
+    /// along with the archive.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        fs::write(path, json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anchor(slot: u64) -> Anchor {
+        Anchor {
+            slot,
+            state_root: Node::repeat_byte(slot as u8),
+        }
+    }
+
+    #[test]
+    fn it_should_pin_anchors_per_range_of_targets() {
+        let mut lock = AnchorLock::new();
+        lock.pin(9_000, 9_999, anchor(10_000), Node::repeat_byte(1))
+            .unwrap();
+        lock.pin(1_000, 1_999, anchor(2_000), Node::repeat_byte(2))
+            .unwrap();
+
+        assert_eq!(lock.anchor_for(1_500).unwrap().anchor(), anchor(2_000));
+        assert_eq!(lock.anchor_for(9_999).unwrap().anchor(), anchor(10_000));
+        assert_eq!(lock.anchor_for(2_000), None);
+        assert!(matches!(
+            lock.pin(1_999, 2_500, anchor(3_000), Node::repeat_byte(3)),
+            Err(AncestryProverError::InvalidAnchor(_))
+        ));
+        // the block_roots window of the anchor doesn't reach the first target
+        assert!(matches!(
+            lock.pin(2_000, 8_000, anchor(20_000), Node::repeat_byte(3)),
+            Err(AncestryProverError::InvalidAnchor(_))
+        ));
+    }
+
+    #[test]
+    fn it_should_read_written_lock_files() {
+        let path = std::env::temp_dir().join(format!("anchors_{}.lock", std::process::id()));
+        let mut lock = AnchorLock::new();
+        lock.pin(1_000, 1_999, anchor(2_000), Node::repeat_byte(2))
+            .unwrap();
+
+        lock.write(&path).unwrap();
+        let read = AnchorLock::read(&path);
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(read.unwrap(), lock);
+    }
+}
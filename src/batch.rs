@@ -0,0 +1,130 @@
+//! Normalization of the targets of batch requests, so that sloppy inputs (repeated
+//! targets, targets at the anchor) don't multiply the requests sent to providers.
+
+use crate::errors::AncestryProverError;
+use crate::provider::BlockRootsProof;
+use crate::validation::validate_target_slot;
+
+/// Unique target slots of a batch, with the mapping back to the positions of the
+/// caller's input.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchTargets {
+    slots: Vec<u64>,
+    indices: Vec<usize>,
+}
+
+impl BatchTargets {
+    /// Dedupes `targets`, failing with [`AncestryProverError::TargetNotBeforeAnchor`]
+    /// for the first target that is not before the anchor, e.g. the anchor itself.
+    pub fn new(targets: &[u64], anchor_slot: u64) -> Result<Self, AncestryProverError> {
+        for target_slot in targets {
+            validate_target_slot(*target_slot, anchor_slot)?;
+        }
+
+        Ok(Self::dedup(targets))
+    }
+
+    /// Dedupes `targets` without validating them, for batches reporting invalid
+    /// targets one by one.
+    pub fn dedup(targets: &[u64]) -> Self {
+        let mut slots = targets.to_vec();
+        slots.sort_unstable();
+        slots.dedup();
+        let indices = targets
+            .iter()
+            .map(|target_slot| slots.binary_search(target_slot).unwrap())
+            .collect();
+
+        Self { slots, indices }
+    }
+
+    /// Returns the unique target slots, in ascending order.
+    pub fn slots(&self) -> &[u64] {
+        &self.slots
+    }
+
+    /// Returns, for each position of the input, the index of its target in [`BatchTargets::slots`].
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    /// Maps the results of the unique targets, in the order of [`BatchTargets::slots`],
+    /// back to the positions of the input.
+    pub fn expand<T: Clone>(&self, results: &[T]) -> Vec<T> {
+        self.indices
+            .iter()
+            .map(|index| results[*index].clone())
+            .collect()
+    }
+}
+
+/// Outcome of a batch with per-target errors, so that callers can submit the
+/// proven targets and retry only the failed ones.
+#[derive(Debug, Default)]
+pub struct BatchResult {
+    /// Proven targets, in ascending order of slot.
+    pub ok: Vec<(u64, BlockRootsProof)>,
+    /// Failed targets, in ascending order of slot.
+    pub failed: Vec<(u64, AncestryProverError)>,
+    /// Targets of the batch, mapping the caller's input to `ok` and `failed`.
+    pub targets: BatchTargets,
+}
+
+impl BatchResult {
+    /// Returns the outcome of the target of `slot`, if it is part of the batch.
+    pub fn outcome(&self, slot: u64) -> Option<Result<&BlockRootsProof, &AncestryProverError>> {
+        let proven = self.ok.iter().find(|(proven, _)| *proven == slot);
+        if let Some((_, proof)) = proven {
+            return Some(Ok(proof));
+        }
+        let failed = self.failed.iter().find(|(failed, _)| *failed == slot);
+        failed.map(|(_, err)| Err(err))
+    }
+
+    /// Returns the outcomes of the targets in the order of the caller's input,
+    /// repeated targets sharing the outcome of their single proof. Outcomes are
+    /// only missing from batches whose `ok` and `failed` don't cover `targets`.
+    pub fn outcomes(&self) -> Vec<Option<Result<&BlockRootsProof, &AncestryProverError>>> {
+        let outcomes = self
+            .targets
+            .slots()
+            .iter()
+            .map(|slot| self.outcome(*slot))
+            .collect::<Vec<_>>();
+        self.targets.expand(&outcomes)
+    }
+
+    /// Returns whether every target was proven.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Returns the slots of the failed targets, e.g. to retry them.
+    pub fn failed_slots(&self) -> Vec<u64> {
+        self.failed.iter().map(|(slot, _)| *slot).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_dedupe_targets_and_map_them_back() {
+        let targets = BatchTargets::new(&[300, 100, 300, 200, 100], 1_000).unwrap();
+
+        assert_eq!(targets.slots(), &[100, 200, 300]);
+        assert_eq!(targets.indices(), &[2, 0, 2, 1, 0]);
+        assert_eq!(
+            targets.expand(&["a", "b", "c"]),
+            vec!["c", "a", "c", "b", "a"]
+        );
+        assert!(matches!(
+            BatchTargets::new(&[100, 1_000], 1_000),
+            Err(AncestryProverError::TargetNotBeforeAnchor {
+                target: 1_000,
+                anchor: 1_000
+            })
+        ));
+    }
+}
@@ -0,0 +1,102 @@
+//! `ancestry-verify`: standalone verifier of block roots proofs, for environments
+//! where the async stack of the providers is unwanted. Build it without the default
+//! features, e.g. statically linked with
+//! `cargo build --release --no-default-features --bin ancestry-verify --target x86_64-unknown-linux-musl`.
+//!
+//! ```text
+//! ancestry-verify <anchor-state-root> <anchor-slot> <target-slot> [proof.json]
+//! ```
+//!
+//! Reads the JSON proof from the file, or stdin without one, and prints the proven
+//! block root of the target. Exits with 1 if the proof is invalid, and with 2 on
+//! invalid arguments or input.
+
+use alloy_primitives::FixedBytes;
+use ancestry_prover::verification::{
+    verify_block_roots_proof_for_anchor, verify_block_roots_proof_strict,
+};
+use ancestry_prover::BlockRootsProof;
+use ethereum_consensus::capella::presets::mainnet::SLOTS_PER_HISTORICAL_ROOT;
+use std::io::Read;
+use std::process::ExitCode;
+use std::str::FromStr;
+
+const USAGE: &str =
+    "usage: ancestry-verify <anchor-state-root> <anchor-slot> <target-slot> [proof.json]";
+
+struct Args {
+    anchor_root: [u8; 32],
+    anchor_slot: u64,
+    target_slot: u64,
+    proof: BlockRootsProof,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let (anchor_root, anchor_slot, target_slot, path) = match args.as_slice() {
+        [root, anchor, target] => (root, anchor, target, None),
+        [root, anchor, target, path] => (root, anchor, target, Some(path)),
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let anchor_root = FixedBytes::<32>::from_str(anchor_root)
+        .map_err(|err| format!("invalid anchor state root: {err}"))?;
+    let anchor_slot = anchor_slot
+        .parse()
+        .map_err(|err| format!("invalid anchor slot: {err}"))?;
+    let target_slot = target_slot
+        .parse()
+        .map_err(|err| format!("invalid target slot: {err}"))?;
+
+    let json = match path {
+        Some(path) => std::fs::read_to_string(path).map_err(|err| format!("{path}: {err}"))?,
+        None => {
+            let mut json = String::new();
+            std::io::stdin()
+                .read_to_string(&mut json)
+                .map_err(|err| format!("stdin: {err}"))?;
+            json
+        }
+    };
+    let proof = serde_json::from_str(&json).map_err(|err| format!("invalid proof: {err}"))?;
+
+    Ok(Args {
+        anchor_root: anchor_root.0,
+        anchor_slot,
+        target_slot,
+        proof,
+    })
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let window = SLOTS_PER_HISTORICAL_ROOT as u64;
+    let verified = verify_block_roots_proof_for_anchor(
+        args.anchor_root,
+        args.anchor_slot,
+        args.target_slot,
+        window,
+        &args.proof,
+    )
+    .and_then(|_| {
+        verify_block_roots_proof_strict(args.anchor_root, args.target_slot, window, &args.proof)
+    });
+
+    match verified {
+        Ok(block_root) => {
+            println!("0x{}", hex::encode(block_root));
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("invalid proof: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
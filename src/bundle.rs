@@ -0,0 +1,103 @@
+//! Proof bundles, proving the blocks of a batch of cross-chain messages against
+//! one anchor with a single shared multiproof.
+
+use crate::errors::VerifyError;
+use crate::provider::{BlockRootsProof, Verify};
+use crate::verification::{block_roots_gindex, proven_leaf};
+use crate::version::VersionInfo;
+use alloy_primitives::FixedBytes;
+use ethereum_consensus::capella::presets::mainnet::SLOTS_PER_HISTORICAL_ROOT;
+use ethereum_consensus::ssz::prelude::Node;
+use serde::{Deserialize, Serialize};
+
+/// Metadata of a message of a [`ProofBundle`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BundledMessage {
+    /// Slot of the block the message was included in.
+    pub target_slot: u64,
+    /// Hash of the message payload, opaque to the prover.
+    pub payload_hash: Node,
+    /// Root of the block of `target_slot`, proven by the bundle proof.
+    pub block_root: Node,
+}
+
+/// Single artifact proving the blocks of several messages against one anchor state.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProofBundle {
+    pub anchor_slot: u64,
+    pub anchor_state_root: Node,
+    /// Proof of the `block_roots` entries of all the target slots.
+    pub proof: BlockRootsProof,
+    pub messages: Vec<BundledMessage>,
+    /// Version of the prover that generated the bundle, absent in bundles of older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prover: Option<VersionInfo>,
+}
+
+impl ProofBundle {
+    /// Verifies the bundle proof against the anchor state root, and that it
+    /// proves the block root of every message.
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        self.proof.verify(FixedBytes(self.anchor_state_root.0))?;
+
+        for message in &self.messages {
+            let gindex = block_roots_gindex(message.target_slot, SLOTS_PER_HISTORICAL_ROOT as u64)?;
+            if proven_leaf(&self.proof, gindex)? != message.block_root {
+                return Err(VerifyError::UnexpectedLeaf(gindex));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "providers"))]
+mod tests {
+    use super::*;
+    use crate::sim::{SimChain, SimProvider};
+    use crate::AncestryProver;
+    use std::sync::Arc;
+
+    async fn bundle(chain: &Arc<SimChain>) -> ProofBundle {
+        let prover = AncestryProver::new(SimProvider::new(chain.clone()));
+        let messages = [
+            (9_000, Node::repeat_byte(1)),
+            (12_345, Node::repeat_byte(2)),
+            (9_000, Node::repeat_byte(3)),
+        ];
+        let anchor_root = format!("0x{}", hex::encode(chain.state_root(16_000)));
+
+        prover
+            .prove_bundle(&messages, 16_000, &anchor_root)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_should_prove_all_messages_with_one_proof() {
+        let chain = Arc::new(SimChain::new(16_000));
+        let bundle = bundle(&chain).await;
+
+        assert!(matches!(bundle.proof, BlockRootsProof::CompactProof { .. }));
+        assert_eq!(bundle.messages.len(), 3);
+        assert_eq!(bundle.messages[1].payload_hash, Node::repeat_byte(2));
+        assert_eq!(bundle.messages[1].block_root, chain.block_root(12_345));
+        assert_eq!(bundle.verify(), Ok(()));
+        assert_eq!(bundle.prover, Some(VersionInfo::current()));
+
+        let json = serde_json::to_string(&bundle).unwrap();
+        assert_eq!(serde_json::from_str::<ProofBundle>(&json).unwrap(), bundle);
+    }
+
+    #[tokio::test]
+    async fn it_should_reject_messages_with_another_block_root() {
+        let chain = Arc::new(SimChain::new(16_000));
+        let mut bundle = bundle(&chain).await;
+        bundle.messages[2].block_root = chain.block_root(9_001);
+
+        assert_eq!(
+            bundle.verify(),
+            Err(VerifyError::UnexpectedLeaf(37 * 8192 + 9_000 % 8192))
+        );
+    }
+}
@@ -0,0 +1,59 @@
+//! Canonical JSON encoding of the artifacts produced by the prover (proofs,
+//! bundles, ...), so that they are reproducible byte-for-byte for auditing.
+//!
+//! The canonical form has no whitespace, object keys sorted, and all the hex
+//! strings (`0x...`) lowercase. Arrays keep their order, which is deterministic
+//! for all the artifacts of the crate.
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Serializes `value` into canonical JSON.
+pub fn to_canonical_json<T: Serialize + ?Sized>(value: &T) -> Result<String, serde_json::Error> {
+    let value = canonicalize(serde_json::to_value(value)?);
+    serde_json::to_string(&value)
+}
+
+fn canonicalize(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries = map.into_iter().collect::<Vec<_>>();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, canonicalize(value)))
+                    .collect::<Map<_, _>>(),
+            )
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(canonicalize).collect()),
+        Value::String(s) if is_hex(&s) => Value::String(s.to_ascii_lowercase()),
+        value => value,
+    }
+}
+
+fn is_hex(s: &str) -> bool {
+    s.strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .is_some_and(|digits| digits.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn it_should_sort_keys_and_lowercase_hex() {
+        let value = json!({
+            "nodes": ["0xABcd", "Not hex"],
+            "descriptor": [1, 2],
+            "anchor": { "slot": 1, "root": "0XFF" }
+        });
+
+        assert_eq!(
+            to_canonical_json(&value).unwrap(),
+            r#"{"anchor":{"root":"0xff","slot":1},"descriptor":[1,2],"nodes":["0xabcd","Not hex"]}"#
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use crate::errors::ProofProviderError;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::Client;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// `User-Agent` of the requests, unless configured otherwise.
+pub const DEFAULT_USER_AGENT: &str =
+    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Header identifying the client, e.g. for rate-limit allowlisting by hosted beacon nodes.
+pub const CLIENT_ID_HEADER: HeaderName = HeaderName::from_static("x-client-id");
+
+/// Connection pool and identification settings of the HTTP client used by the providers.
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    pub pool_max_idle_per_host: usize,
+    pub pool_idle_timeout: Option<Duration>,
+    pub http2_keep_alive_interval: Option<Duration>,
+    pub http2_keep_alive_timeout: Duration,
+    pub connect_timeout: Option<Duration>,
+    /// Advertises gzip, deflate and brotli in `Accept-Encoding` and transparently
+    /// decompresses responses.
+    pub decompression: bool,
+    pub user_agent: String,
+    /// Sent in the [`CLIENT_ID_HEADER`] of every request when set.
+    pub client_id: Option<String>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            pool_max_idle_per_host: 32,
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            http2_keep_alive_interval: Some(Duration::from_secs(30)),
+            http2_keep_alive_timeout: Duration::from_secs(10),
+            connect_timeout: Some(Duration::from_secs(10)),
+            decompression: true,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            client_id: None,
+        }
+    }
+}
+
+impl ClientConfig {
+    /// Sets the client id, failing with [`ProofProviderError::InputError`] if it
+    /// isn't a valid header value, e.g. because it contains a newline.
+    pub fn with_client_id(mut self, client_id: &str) -> Result<Self, ProofProviderError> {
+        client_id_header(client_id)?;
+        self.client_id = Some(client_id.to_string());
+        Ok(self)
+    }
+
+    /// Builds the client, failing with [`ProofProviderError::InputError`] for an
+    /// invalid client id, or [`ProofProviderError::NetworkError`] if `reqwest`
+    /// rejects the configuration (e.g. an invalid user agent).
+    pub fn build(&self) -> Result<Client, ProofProviderError> {
+        let mut headers = HeaderMap::new();
+        if let Some(client_id) = &self.client_id {
+            headers.insert(CLIENT_ID_HEADER, client_id_header(client_id)?);
+        }
+
+        let mut builder = Client::builder()
+            .user_agent(&self.user_agent)
+            .default_headers(headers)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .http2_keep_alive_interval(self.http2_keep_alive_interval)
+            .http2_keep_alive_timeout(self.http2_keep_alive_timeout)
+            .http2_keep_alive_while_idle(true)
+            .gzip(self.decompression)
+            .deflate(self.decompression)
+            .brotli(self.decompression);
+
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+fn client_id_header(client_id: &str) -> Result<HeaderValue, ProofProviderError> {
+    HeaderValue::from_str(client_id)
+        .map_err(|_| ProofProviderError::InputError(format!("Invalid client id {:?}", client_id)))
+}
+
+static SHARED_CLIENT: OnceLock<Client> = OnceLock::new();
+
+/// Returns the crate-level HTTP client, shared by all providers that are not
+/// given a client explicitly, so that they share one connection pool.
+pub fn shared_client() -> Client {
+    SHARED_CLIENT
+        .get_or_init(|| {
+            ClientConfig::default()
+                .build()
+                .expect("the default client configuration is valid")
+        })
+        .clone()
+}
+
+/// Initializes the crate-level HTTP client with a custom configuration.
+/// Returns `false` if the shared client was already initialized, and fails if
+/// the configuration is invalid, see [`ClientConfig::build`].
+pub fn init_shared_client(config: &ClientConfig) -> Result<bool, ProofProviderError> {
+    Ok(SHARED_CLIENT.set(config.build()?).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+
+    #[tokio::test]
+    async fn it_should_identify_the_client() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::path("/default"),
+                request::headers(contains(("user-agent", DEFAULT_USER_AGENT))),
+                not(request::headers(contains(key("x-client-id")))),
+            ])
+            .respond_with(status_code(200)),
+        );
+        server.expect(
+            Expectation::matching(all_of![
+                request::path("/configured"),
+                request::headers(contains(("user-agent", "bridge-relayer/1.2"))),
+                request::headers(contains(("x-client-id", "relayer-7"))),
+            ])
+            .respond_with(status_code(200)),
+        );
+
+        ClientConfig::default()
+            .build()
+            .unwrap()
+            .get(server.url_str("/default"))
+            .send()
+            .await
+            .unwrap();
+        ClientConfig {
+            user_agent: "bridge-relayer/1.2".to_string(),
+            ..Default::default()
+        }
+        .with_client_id("relayer-7")
+        .unwrap()
+        .build()
+        .unwrap()
+        .get(server.url_str("/configured"))
+        .send()
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn it_should_reject_invalid_client_ids() {
+        assert!(matches!(
+            ClientConfig::default().with_client_id("relayer\n7"),
+            Err(ProofProviderError::InputError(_))
+        ));
+        let config = ClientConfig {
+            client_id: Some("relayer\n7".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.build(),
+            Err(ProofProviderError::InputError(_))
+        ));
+    }
+}
@@ -0,0 +1,87 @@
+//! Time source of the clock-dependent features of the prover (e.g. computing the
+//! current slot), so that tests and simulations can drive time deterministically.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Genesis time of mainnet, in seconds since the unix epoch.
+pub const MAINNET_GENESIS_TIME: u64 = 1_606_824_023;
+
+pub const SECONDS_PER_SLOT: u64 = 12;
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// Clock backed by the system time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Clock that only moves when told to. Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl ManualClock {
+    pub fn new(now: SystemTime) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> SystemTime {
+        (**self).now()
+    }
+}
+
+/// Returns the slot at the current time of `clock`, or `None` before genesis.
+pub fn current_slot<C: Clock + ?Sized>(clock: &C, genesis_time: u64) -> Option<u64> {
+    let now = clock.now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let elapsed = now.checked_sub(genesis_time)?;
+
+    Some(elapsed / SECONDS_PER_SLOT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_compute_the_current_slot() {
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(MAINNET_GENESIS_TIME));
+        assert_eq!(current_slot(&clock, MAINNET_GENESIS_TIME), Some(0));
+
+        clock.advance(Duration::from_secs(7_862_720 * SECONDS_PER_SLOT + 11));
+        assert_eq!(current_slot(&clock, MAINNET_GENESIS_TIME), Some(7_862_720));
+    }
+
+    #[test]
+    fn it_should_not_compute_slots_before_genesis() {
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(MAINNET_GENESIS_TIME - 1));
+
+        assert_eq!(current_slot(&clock, MAINNET_GENESIS_TIME), None);
+    }
+}
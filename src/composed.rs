@@ -0,0 +1,183 @@
+//! Chains of proofs through nested trees, e.g. an ancestry proof followed by proofs
+//! of fields of the target block, for chains the crate doesn't ship natively.
+//!
+//! [`chain_proofs`] stitches ancestry proofs through intermediate anchors, to prove
+//! targets older than the block_roots window of the recent block.
+
+use crate::errors::VerifyError;
+pub use crate::header::HEADER_STATE_ROOT_GINDEX;
+use crate::provider::{BlockRootsProof, Verify};
+use crate::verification::{proof_root, proven_leaf};
+use alloy_primitives::FixedBytes;
+use ethereum_consensus::ssz::prelude::Node;
+use serde::{Deserialize, Serialize};
+
+/// Link of a [`ComposedProof`], proving the node at `gindex` of the tree whose root
+/// is the node proven by the previous link, or the anchor root for the first link.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SubProof {
+    pub gindex: u64,
+    pub proof: BlockRootsProof,
+}
+
+/// Chain of sub-proofs from an anchor root down to a node, verified as a whole.
+///
+/// ```ignore
+/// let composed = ComposedProof::new()
+///     .with_sub_proof(block_roots_gindex(target_slot), ancestry_proof)
+///     .with_sub_proof(STATE_ROOT_GINDEX, header_proof);
+/// let target_state_root = composed.verify(anchor_state_root)?;
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ComposedProof {
+    pub sub_proofs: Vec<SubProof>,
+}
+
+impl ComposedProof {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a proof of the node at `gindex` of the tree rooted at the node proven so far.
+    pub fn with_sub_proof(mut self, gindex: u64, proof: BlockRootsProof) -> Self {
+        self.sub_proofs.push(SubProof { gindex, proof });
+        self
+    }
+
+    /// Verifies each sub-proof against the node proven by the previous one, starting
+    /// from `anchor_root`, and returns the node proven by the last one.
+    pub fn verify(&self, anchor_root: Node) -> Result<Node, VerifyError> {
+        if self.sub_proofs.is_empty() {
+            return Err(VerifyError::InvalidProof);
+        }
+
+        self.sub_proofs
+            .iter()
+            .try_fold(anchor_root, |root, sub_proof| {
+                sub_proof.proof.verify(FixedBytes(root.0))?;
+                proven_leaf(&sub_proof.proof, sub_proof.gindex)
+            })
+    }
+}
+
+/// Chains an ancestry proof of a target A against an intermediate anchor B, and one
+/// of B against a recent anchor C, into a proof of A against the state root of C.
+///
+/// `b_header` proves the state root of B under its block root (i.e. the node at
+/// [`HEADER_STATE_ROOT_GINDEX`] of its `BeaconBlockHeader`), since `b_to_c` proves
+/// the block root of B while `a_to_b` is verified against its state root. Chains of
+/// more anchors are built by chaining the result further with
+/// [`ComposedProof::with_sub_proof`].
+///
+/// Fails if a proof doesn't prove a single node, or if the links don't match.
+pub fn chain_proofs(
+    a_to_b: BlockRootsProof,
+    b_header: BlockRootsProof,
+    b_to_c: BlockRootsProof,
+) -> Result<ComposedProof, VerifyError> {
+    let gindex = |proof: &BlockRootsProof| match proof.gindices()?.as_slice() {
+        [gindex] => Ok(*gindex),
+        _ => Err(VerifyError::InvalidProof),
+    };
+    let (a_gindex, c_gindex) = (gindex(&a_to_b)?, gindex(&b_to_c)?);
+
+    // fail early on proofs of unrelated anchors, rather than on verification
+    if proven_leaf(&b_to_c, c_gindex)? != proof_root(&b_header)?
+        || proven_leaf(&b_header, HEADER_STATE_ROOT_GINDEX)? != proof_root(&a_to_b)?
+    {
+        return Err(VerifyError::InvalidProof);
+    }
+
+    Ok(ComposedProof::new()
+        .with_sub_proof(c_gindex, b_to_c)
+        .with_sub_proof(HEADER_STATE_ROOT_GINDEX, b_header)
+        .with_sub_proof(a_gindex, a_to_b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::BlockRootsTree;
+
+    fn tree(seed: u8) -> BlockRootsTree {
+        let leaves = (0..16u8)
+            .map(|i| Node::repeat_byte(seed.wrapping_add(i)))
+            .collect::<Vec<_>>();
+        BlockRootsTree::new(&leaves)
+    }
+
+    #[test]
+    fn it_should_verify_chains_through_nested_trees() {
+        // the root of the inner tree is leaf 5 of the outer one
+        let inner = tree(100);
+        let mut outer_leaves = tree(0).leaves().to_vec();
+        outer_leaves[5] = inner.root();
+        let outer = BlockRootsTree::new(&outer_leaves);
+
+        let composed = ComposedProof::new()
+            .with_sub_proof(21, outer.prove_leaf(5))
+            .with_sub_proof(18, inner.prove_leaf(2));
+
+        assert_eq!(composed.verify(outer.root()), Ok(Node::repeat_byte(102)));
+        assert!(composed.verify(inner.root()).is_err());
+        assert_eq!(
+            ComposedProof::new().verify(outer.root()),
+            Err(VerifyError::InvalidProof)
+        );
+    }
+
+    #[test]
+    fn it_should_chain_proofs_through_intermediate_anchors() {
+        // block A is leaf 3 of the block_roots of B, whose header is leaf 7 of the
+        // block_roots of C
+        let mut b_state_leaves = tree(0).leaves().to_vec();
+        b_state_leaves[3] = Node::repeat_byte(42);
+        let b_state = BlockRootsTree::new(&b_state_leaves);
+        let mut b_header_leaves = vec![Node::repeat_byte(7); 8];
+        b_header_leaves[3] = b_state.root();
+        let b_header = BlockRootsTree::new(&b_header_leaves);
+        let mut c_state_leaves = tree(100).leaves().to_vec();
+        c_state_leaves[7] = b_header.root();
+        let c_state = BlockRootsTree::new(&c_state_leaves);
+
+        let chained = chain_proofs(
+            b_state.prove_leaf(3),
+            b_header.prove_leaf(3),
+            c_state.prove_leaf(7),
+        )
+        .unwrap();
+        assert_eq!(chained.verify(c_state.root()), Ok(Node::repeat_byte(42)));
+        assert!(chained.verify(b_state.root()).is_err());
+
+        // the state root of B is not the one proven by its header
+        assert_eq!(
+            chain_proofs(
+                tree(0).prove_leaf(3),
+                b_header.prove_leaf(3),
+                c_state.prove_leaf(7),
+            ),
+            Err(VerifyError::InvalidProof)
+        );
+    }
+
+    #[test]
+    fn it_should_reject_broken_links() {
+        let (outer, inner) = (tree(0), tree(100));
+
+        // leaf 5 of the outer tree is not the root of the inner tree
+        let composed = ComposedProof::new()
+            .with_sub_proof(21, outer.prove_leaf(5))
+            .with_sub_proof(18, inner.prove_leaf(2));
+        assert!(composed.verify(outer.root()).is_err());
+
+        // the gindex of a link must be the one its proof proves
+        let composed = ComposedProof::new().with_sub_proof(22, outer.prove_leaf(5));
+        assert_eq!(
+            composed.verify(outer.root()),
+            Err(VerifyError::UnexpectedGindex {
+                expected: 22,
+                actual: 21
+            })
+        );
+    }
+}
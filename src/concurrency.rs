@@ -0,0 +1,133 @@
+//! Adaptive concurrency of the batch modes, so that the number of requests in flight
+//! follows what the backend can take instead of being hand-tuned per endpoint.
+
+use std::time::Duration;
+
+/// Additive increase, multiplicative decrease (AIMD) controller of the number of
+/// requests in flight, driven by the observed latencies and errors.
+///
+/// The limit grows by about one per round of `limit` successful requests, and is
+/// multiplied by the backoff factor after a transient error, or a latency above the
+/// tolerated multiple of the lowest latency observed, at most once per round.
+#[derive(Debug, Clone)]
+pub struct AimdController {
+    min_limit: usize,
+    max_limit: usize,
+    limit: f64,
+    backoff: f64,
+    latency_tolerance: f64,
+    min_latency: Option<Duration>,
+    since_decrease: usize,
+}
+
+impl AimdController {
+    /// Starts at `min_limit` requests in flight, growing up to `max_limit`.
+    pub fn new(min_limit: usize, max_limit: usize) -> Self {
+        let min_limit = min_limit.max(1);
+        Self {
+            min_limit,
+            max_limit: max_limit.max(min_limit),
+            limit: min_limit as f64,
+            backoff: 0.5,
+            latency_tolerance: 2.0,
+            min_latency: None,
+            since_decrease: 0,
+        }
+    }
+
+    /// Factor applied to the limit on congestion, 0.5 by default.
+    pub fn with_backoff(mut self, backoff: f64) -> Self {
+        self.backoff = backoff.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Multiple of the lowest observed latency above which a response is considered
+    /// a sign of congestion, 2 by default.
+    pub fn with_latency_tolerance(mut self, tolerance: f64) -> Self {
+        self.latency_tolerance = tolerance.max(1.0);
+        self
+    }
+
+    /// Returns the number of requests allowed in flight.
+    pub fn limit(&self) -> usize {
+        self.limit as usize
+    }
+
+    /// Records a response received after `latency`.
+    pub fn on_success(&mut self, latency: Duration) {
+        let min_latency = self.min_latency.map_or(latency, |min| min.min(latency));
+        self.min_latency = Some(min_latency);
+
+        if latency.as_secs_f64() > min_latency.as_secs_f64() * self.latency_tolerance {
+            self.decrease();
+        } else {
+            self.since_decrease += 1;
+            self.limit = (self.limit + 1.0 / self.limit).min(self.max_limit as f64);
+        }
+    }
+
+    /// Records a transient error, e.g. a timeout or a rate limited request.
+    pub fn on_error(&mut self) {
+        self.decrease();
+    }
+
+    fn decrease(&mut self) {
+        // the responses to the requests sent before the last decrease don't count
+        if self.since_decrease < self.limit() {
+            self.since_decrease += 1;
+            return;
+        }
+        self.since_decrease = 0;
+        self.limit = (self.limit * self.backoff).max(self.min_limit as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAST: Duration = Duration::from_millis(100);
+    const SLOW: Duration = Duration::from_millis(500);
+
+    #[test]
+    fn it_should_increase_additively_and_decrease_multiplicatively() {
+        let mut controller = AimdController::new(1, 16);
+        for _ in 0..1_000 {
+            controller.on_success(FAST);
+        }
+        assert_eq!(controller.limit(), 16);
+
+        controller.on_error();
+        assert_eq!(controller.limit(), 8);
+        // the errors of the 8 requests in flight at the decrease are ignored
+        for _ in 0..8 {
+            controller.on_error();
+        }
+        assert_eq!(controller.limit(), 8);
+        controller.on_success(SLOW);
+        assert_eq!(controller.limit(), 4);
+
+        for _ in 0..100 {
+            controller.on_error();
+        }
+        assert_eq!(controller.limit(), 1);
+
+        // about one more request per round, here of 1, 2, 3 and 4 requests
+        for _ in 0..1 + 2 + 3 + 4 {
+            controller.on_success(FAST);
+        }
+        assert_eq!(controller.limit(), 4);
+    }
+
+    #[test]
+    fn it_should_keep_fixed_limits() {
+        let mut controller = AimdController::new(8, 8);
+        controller.on_success(FAST);
+        for _ in 0..100 {
+            controller.on_error();
+            controller.on_success(SLOW);
+        }
+
+        assert_eq!(controller.limit(), 8);
+    }
+}
@@ -0,0 +1,163 @@
+//! Proofs of a target block against two anchors, proving the same block root, as
+//! a defense against a single compromised beacon endpoint.
+
+use crate::anchor::Anchor;
+use crate::errors::VerifyError;
+#[cfg(feature = "providers")]
+use crate::errors::{AncestryProverError, ProofProviderError};
+#[cfg(feature = "providers")]
+use crate::prover::AncestryProver;
+use crate::provider::BlockRootsProof;
+#[cfg(feature = "providers")]
+use crate::provider::{ProofProvider, StateId};
+use crate::verification::{block_roots_gindex, verify_block_roots_proof_for_anchor};
+use ethereum_consensus::capella::presets::mainnet::SLOTS_PER_HISTORICAL_ROOT;
+use ethereum_consensus::ssz::prelude::Node;
+use serde::{Deserialize, Serialize};
+
+/// Proof of a target block against one anchor.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnchoredProof {
+    pub anchor: Anchor,
+    pub proof: BlockRootsProof,
+}
+
+impl AnchoredProof {
+    fn verify(&self, target_slot: u64) -> Result<Node, VerifyError> {
+        verify_block_roots_proof_for_anchor(
+            self.anchor.state_root.0,
+            self.anchor.slot,
+            target_slot,
+            SLOTS_PER_HISTORICAL_ROOT as u64,
+            &self.proof,
+        )
+        .map(Node::from)
+    }
+}
+
+/// Proofs of the block of `target_slot` against two anchors, which prove the same block root.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DualAnchorProof {
+    pub target_slot: u64,
+    pub block_root: Node,
+    pub first: AnchoredProof,
+    pub second: AnchoredProof,
+}
+
+impl DualAnchorProof {
+    /// Verifies both proofs against their anchors, and that both prove `block_root`.
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        for anchored in [&self.first, &self.second] {
+            if anchored.verify(self.target_slot)? != self.block_root {
+                return Err(VerifyError::UnexpectedLeaf(block_roots_gindex(
+                    self.target_slot,
+                    SLOTS_PER_HISTORICAL_ROOT as u64,
+                )?));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "providers")]
+impl<P: ProofProvider> AncestryProver<P> {
+    /// Proves the block of `target_slot` against the `first` anchor with this prover,
+    /// and against the `second` anchor with `second_prover` (e.g. backed by another
+    /// beacon endpoint, or this prover again), failing with
+    /// [`AncestryProverError::InconsistentBlockRoots`] if they prove different blocks.
+    pub async fn prove_consistency<Q: ProofProvider>(
+        &self,
+        second_prover: &AncestryProver<Q>,
+        target_slot: u64,
+        first: Anchor,
+        second: Anchor,
+    ) -> Result<DualAnchorProof, AncestryProverError> {
+        let (first_root, second_root) = (
+            StateId::Root(first.state_root).to_string(),
+            StateId::Root(second.state_root).to_string(),
+        );
+        let (first_proof, second_proof) = futures::join!(
+            self.prove(target_slot, first.slot, &first_root),
+            second_prover.prove(target_slot, second.slot, &second_root),
+        );
+        let first = AnchoredProof {
+            anchor: first,
+            proof: first_proof?,
+        };
+        let second = AnchoredProof {
+            anchor: second,
+            proof: second_proof?,
+        };
+
+        let invalid = |_| ProofProviderError::InvalidProofError();
+        let block_root = first.verify(target_slot).map_err(invalid)?;
+        if second.verify(target_slot).map_err(invalid)? != block_root {
+            return Err(AncestryProverError::InconsistentBlockRoots(target_slot));
+        }
+
+        Ok(DualAnchorProof {
+            target_slot,
+            block_root,
+            first,
+            second,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "providers"))]
+mod tests {
+    use super::*;
+    use crate::sim::{SimChain, SimProvider};
+    use std::sync::Arc;
+
+    fn anchor(chain: &SimChain, slot: u64) -> Anchor {
+        Anchor {
+            slot,
+            state_root: chain.state_root(slot),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_should_prove_targets_against_two_anchors() {
+        let chain = Arc::new(SimChain::new(200));
+        let prover = AncestryProver::new(SimProvider::new(chain.clone()));
+
+        let mut proof = prover
+            .prove_consistency(&prover, 90, anchor(&chain, 100), anchor(&chain, 200))
+            .await
+            .unwrap();
+
+        assert_eq!(proof.block_root, chain.block_root(90));
+        assert_eq!(proof.verify(), Ok(()));
+
+        proof.block_root = chain.block_root(91);
+        assert!(matches!(
+            proof.verify(),
+            Err(VerifyError::UnexpectedLeaf(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_reject_anchors_proving_different_blocks() {
+        let chain = Arc::new(SimChain::new(200));
+        // another endpoint, serving a chain of the same shape
+        let forged = Arc::new(SimChain::new(200).with_seed(1));
+        let prover = AncestryProver::new(SimProvider::new(chain.clone()));
+        let forged_prover = AncestryProver::new(SimProvider::new(forged.clone()));
+
+        let result = prover
+            .prove_consistency(
+                &forged_prover,
+                90,
+                anchor(&chain, 100),
+                anchor(&forged, 200),
+            )
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(AncestryProverError::InconsistentBlockRoots(90))
+        ));
+    }
+}
@@ -0,0 +1,213 @@
+//! Encoding of proofs in the JSON shape of CosmWasm (IBC-style) light clients:
+//! binary fields as base64 and `u64`s as decimal strings, like the `Binary` and
+//! `Uint64` types of `cosmwasm_std`, and enums externally tagged in snake_case.
+
+use crate::errors::VerifyError;
+use crate::provider::{deserialize_bounded, BlockRootsProof, MAX_PROOF_NODES, MAX_WITNESSES};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ethereum_consensus::ssz::prelude::Node;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Bytes serialized as base64, like `cosmwasm_std::Binary`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binary(pub Vec<u8>);
+
+impl Serialize for Binary {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Binary {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD
+            .decode(encoded)
+            .map(Binary)
+            .map_err(D::Error::custom)
+    }
+}
+
+/// `u64` serialized as a decimal string, like `cosmwasm_std::Uint64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Uint64(pub u64);
+
+impl Serialize for Uint64 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Uint64 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let decimal = String::deserialize(deserializer)?;
+        decimal.parse().map(Uint64).map_err(D::Error::custom)
+    }
+}
+
+/// Proof of `block_roots` entries, in the shape expected by CosmWasm light clients.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CosmWasmProof {
+    Single {
+        gindex: Uint64,
+        leaf: Binary,
+        #[serde(deserialize_with = "deserialize_bounded::<_, _, MAX_WITNESSES>")]
+        witnesses: Vec<Binary>,
+    },
+    Compact {
+        descriptor: Binary,
+        #[serde(deserialize_with = "deserialize_bounded::<_, _, MAX_PROOF_NODES>")]
+        nodes: Vec<Binary>,
+    },
+}
+
+/// Execute message of the light client verifying that the block of `target_slot`
+/// is an ancestor of the one of `anchor_slot`, i.e. `{"verify_ancestry": {...}}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    VerifyAncestry {
+        target_slot: Uint64,
+        anchor_slot: Uint64,
+        proof: CosmWasmProof,
+    },
+}
+
+impl ExecuteMsg {
+    pub fn verify_ancestry(target_slot: u64, anchor_slot: u64, proof: &BlockRootsProof) -> Self {
+        ExecuteMsg::VerifyAncestry {
+            target_slot: Uint64(target_slot),
+            anchor_slot: Uint64(anchor_slot),
+            proof: proof.into(),
+        }
+    }
+}
+
+impl From<&BlockRootsProof> for CosmWasmProof {
+    fn from(proof: &BlockRootsProof) -> Self {
+        let binaries = |nodes: &[Node]| nodes.iter().map(|node| Binary(node.0.to_vec())).collect();
+
+        match proof {
+            BlockRootsProof::SingleProof {
+                gindex,
+                witnesses,
+                leaf,
+            } => CosmWasmProof::Single {
+                gindex: Uint64(*gindex),
+                leaf: Binary(leaf.0.to_vec()),
+                witnesses: binaries(witnesses),
+            },
+            BlockRootsProof::CompactProof { descriptor, nodes } => CosmWasmProof::Compact {
+                descriptor: Binary(descriptor.clone()),
+                nodes: binaries(nodes),
+            },
+        }
+    }
+}
+
+impl TryFrom<&CosmWasmProof> for BlockRootsProof {
+    type Error = VerifyError;
+
+    /// Fails with [`VerifyError::InvalidNodeLength`] for nodes that aren't 32 bytes long.
+    fn try_from(proof: &CosmWasmProof) -> Result<Self, Self::Error> {
+        let node = |binary: &Binary| match binary.0.len() {
+            32 => Ok(Node::from_slice(&binary.0)),
+            len => Err(VerifyError::InvalidNodeLength(len)),
+        };
+
+        Ok(match proof {
+            CosmWasmProof::Single {
+                gindex,
+                leaf,
+                witnesses,
+            } => BlockRootsProof::SingleProof {
+                gindex: gindex.0,
+                witnesses: witnesses.iter().map(node).collect::<Result<_, _>>()?,
+                leaf: node(leaf)?,
+            },
+            CosmWasmProof::Compact { descriptor, nodes } => BlockRootsProof::CompactProof {
+                descriptor: descriptor.0.clone(),
+                nodes: nodes.iter().map(node).collect::<Result<_, _>>()?,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::BlockRootsTree;
+    use serde_json::json;
+
+    fn tree() -> BlockRootsTree {
+        let leaves = (0..16u8).map(Node::repeat_byte).collect::<Vec<_>>();
+        BlockRootsTree::new(&leaves)
+    }
+
+    #[test]
+    fn it_should_encode_proofs_in_the_cosmwasm_shape() {
+        let proof = BlockRootsProof::SingleProof {
+            gindex: 309_908,
+            witnesses: vec![Node::repeat_byte(1)],
+            leaf: Node::repeat_byte(2),
+        };
+
+        let msg = serde_json::to_value(ExecuteMsg::verify_ancestry(7_879_316, 7_879_323, &proof));
+
+        assert_eq!(
+            msg.unwrap(),
+            json!({
+                "verify_ancestry": {
+                    "target_slot": "7879316",
+                    "anchor_slot": "7879323",
+                    "proof": {
+                        "single": {
+                            "gindex": "309908",
+                            "leaf": STANDARD.encode([2; 32]),
+                            "witnesses": [STANDARD.encode([1; 32])]
+                        }
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn it_should_round_trip_single_and_compact_proofs() {
+        let tree = tree();
+        let compact = BlockRootsProof::CompactProof {
+            descriptor: vec![0b1011_0000],
+            nodes: vec![
+                tree.node(2).unwrap(),
+                tree.node(6).unwrap(),
+                tree.node(7).unwrap(),
+            ],
+        };
+
+        for proof in [tree.prove_leaf(5), compact] {
+            let msg = ExecuteMsg::verify_ancestry(150, 200, &proof);
+            let json = serde_json::to_string(&msg).unwrap();
+            let ExecuteMsg::VerifyAncestry { proof: decoded, .. } =
+                serde_json::from_str(&json).unwrap();
+
+            assert_eq!(BlockRootsProof::try_from(&decoded), Ok(proof));
+        }
+    }
+
+    #[test]
+    fn it_should_reject_nodes_of_other_lengths() {
+        let proof = CosmWasmProof::Single {
+            gindex: Uint64(21),
+            leaf: Binary(vec![0; 31]),
+            witnesses: vec![],
+        };
+
+        assert_eq!(
+            BlockRootsProof::try_from(&proof),
+            Err(VerifyError::InvalidNodeLength(31))
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! Structured comparison of two proofs, to debug discrepancies between backends or
+//! between archived and freshly generated proofs.
+
+use crate::errors::VerifyError;
+use crate::provider::BlockRootsProof;
+use ethereum_consensus::ssz::prelude::Node;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Differences between two proofs, see [`diff_proofs`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ProofDiff {
+    /// Proven gindex of each single proof, when they differ.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gindex: Option<(u64, u64)>,
+    /// Whether single proofs of the same gindex prove different leaves.
+    pub leaf_mismatch: bool,
+    /// Level of the first differing witness of single proofs of the same gindex,
+    /// from the leaf up (0 is the sibling of the leaf).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_differing_level: Option<usize>,
+    /// Gindices of the nodes revealed by only one of the proofs, or with different
+    /// values, in ascending order.
+    pub differing_gindices: Vec<u64>,
+}
+
+impl ProofDiff {
+    /// Returns whether both proofs reveal the same nodes.
+    pub fn is_empty(&self) -> bool {
+        self.differing_gindices.is_empty()
+    }
+}
+
+/// Returns the nodes revealed by a proof, by gindex.
+pub(crate) fn nodes_by_gindex(proof: &BlockRootsProof) -> Result<BTreeMap<u64, Node>, VerifyError> {
+    match proof {
+        BlockRootsProof::SingleProof {
+            gindex,
+            witnesses,
+            leaf,
+        } => {
+            let mut nodes = BTreeMap::from([(*gindex, *leaf)]);
+            for (level, witness) in witnesses.iter().enumerate() {
+                let sibling = gindex.checked_shr(level as u32).unwrap_or(0) ^ 1;
+                nodes.insert(sibling, *witness);
+            }
+            Ok(nodes)
+        }
+        BlockRootsProof::CompactProof { nodes, .. } => {
+            let gindices = proof.gindices()?;
+            if gindices.len() != nodes.len() {
+                return Err(VerifyError::InvalidNodeCount {
+                    expected: gindices.len(),
+                    actual: nodes.len(),
+                });
+            }
+            Ok(gindices.into_iter().zip(nodes.iter().copied()).collect())
+        }
+    }
+}
+
+/// Compares two proofs node by node. Proofs of different kinds (single and compact)
+/// are compared by the nodes they reveal.
+pub fn diff_proofs(a: &BlockRootsProof, b: &BlockRootsProof) -> Result<ProofDiff, VerifyError> {
+    let (a_nodes, b_nodes) = (nodes_by_gindex(a)?, nodes_by_gindex(b)?);
+    let mut diff = ProofDiff {
+        differing_gindices: a_nodes
+            .keys()
+            .chain(b_nodes.keys())
+            .filter(|gindex| a_nodes.get(gindex) != b_nodes.get(gindex))
+            .copied()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect(),
+        ..Default::default()
+    };
+
+    if let (
+        BlockRootsProof::SingleProof {
+            gindex: a_gindex,
+            witnesses: a_witnesses,
+            leaf: a_leaf,
+        },
+        BlockRootsProof::SingleProof {
+            gindex: b_gindex,
+            witnesses: b_witnesses,
+            leaf: b_leaf,
+        },
+    ) = (a, b)
+    {
+        if a_gindex != b_gindex {
+            diff.gindex = Some((*a_gindex, *b_gindex));
+        } else {
+            diff.leaf_mismatch = a_leaf != b_leaf;
+            diff.first_differing_level = (0..a_witnesses.len().max(b_witnesses.len()))
+                .find(|level| a_witnesses.get(*level) != b_witnesses.get(*level));
+        }
+    }
+
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::BlockRootsTree;
+
+    fn tree() -> BlockRootsTree {
+        let leaves = (0..16u8).map(Node::repeat_byte).collect::<Vec<_>>();
+        BlockRootsTree::new(&leaves)
+    }
+
+    #[test]
+    fn it_should_report_the_first_differing_witness() {
+        let proof = tree().prove_leaf(5);
+        let mut tampered = proof.clone();
+        if let BlockRootsProof::SingleProof { witnesses, .. } = &mut tampered {
+            witnesses[2] = Node::repeat_byte(0xff);
+        }
+
+        let diff = diff_proofs(&proof, &tampered).unwrap();
+
+        assert_eq!(diff.gindex, None);
+        assert!(!diff.leaf_mismatch);
+        assert_eq!(diff.first_differing_level, Some(2));
+        // the sibling of the ancestor of gindex 21 two levels up
+        assert_eq!(diff.differing_gindices, vec![4]);
+        assert!(diff_proofs(&proof, &proof).unwrap().is_empty());
+    }
+
+    #[test]
+    fn it_should_report_proofs_of_other_gindices() {
+        let tree = tree();
+
+        let diff = diff_proofs(&tree.prove_leaf(5), &tree.prove_leaf(6)).unwrap();
+
+        assert_eq!(diff.gindex, Some((21, 22)));
+        assert_eq!(diff.first_differing_level, None);
+        assert_eq!(diff.differing_gindices, vec![10, 11, 20, 21, 22, 23]);
+    }
+}
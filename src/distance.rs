@@ -0,0 +1,87 @@
+//! Slot distance arithmetic shared by the prover, the verifiers and the historical
+//! proofs, so that all of them agree on which targets an anchor can prove.
+
+use ethereum_consensus::capella::presets::mainnet::SLOTS_PER_HISTORICAL_ROOT;
+
+const WINDOW: u64 = SLOTS_PER_HISTORICAL_ROOT as u64;
+
+/// Largest distance of a target in the `block_roots` window of its anchor.
+pub const MAX_DISTANCE_IN_WINDOW: u64 = WINDOW - 1;
+
+/// Number of slots from a target block back to an anchor block, which is at least 1
+/// as the anchor state only holds the roots of earlier blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SlotDistance(u64);
+
+impl SlotDistance {
+    pub fn slots(self) -> u64 {
+        self.0
+    }
+
+    /// Returns whether the target is still in the `block_roots` window of the anchor,
+    /// i.e. less than `SLOTS_PER_HISTORICAL_ROOT` slots before it.
+    pub fn fits_in_window(self) -> bool {
+        self.fits_in(WINDOW)
+    }
+
+    /// Like [`SlotDistance::fits_in_window`], for a `block_roots` vector of `window`
+    /// entries instead of the mainnet preset's.
+    pub fn fits_in(self, window: u64) -> bool {
+        self.0 < window
+    }
+}
+
+/// Returns the distance from `target_slot` to `anchor_slot`, or `None` if the
+/// target is not before the anchor.
+pub fn distance(anchor_slot: u64, target_slot: u64) -> Option<SlotDistance> {
+    anchor_slot
+        .checked_sub(target_slot)
+        .filter(|slots| *slots > 0)
+        .map(SlotDistance)
+}
+
+/// Returns whether `target_slot` is before `anchor_slot` and in its `block_roots` window.
+pub fn fits_in_window(anchor_slot: u64, target_slot: u64) -> bool {
+    distance(anchor_slot, target_slot).is_some_and(SlotDistance::fits_in_window)
+}
+
+/// Returns whether the `block_roots` entry of `target_slot` has been overwritten
+/// with the root of a later block in the state of `anchor_slot`, as the vector is a
+/// ring buffer of `SLOTS_PER_HISTORICAL_ROOT` entries. Such targets need a
+/// historical proof.
+pub fn is_overwritten(anchor_slot: u64, target_slot: u64) -> bool {
+    distance(anchor_slot, target_slot).is_some_and(|distance| !distance.fits_in_window())
+}
+
+/// Returns the number of periods (of `SLOTS_PER_HISTORICAL_ROOT` slots) completed
+/// between `target_slot` and `anchor_slot`, or `None` if the target is not before the anchor.
+pub fn periods_between(anchor_slot: u64, target_slot: u64) -> Option<u64> {
+    distance(anchor_slot, target_slot)?;
+    Some(anchor_slot / WINDOW - target_slot / WINDOW)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_compute_distances_and_windows() {
+        assert_eq!(distance(100, 40).map(SlotDistance::slots), Some(60));
+        assert_eq!(distance(100, 100), None);
+        assert_eq!(distance(100, 101), None);
+
+        assert!(fits_in_window(WINDOW, 1));
+        assert!(!fits_in_window(WINDOW, 0));
+        assert!(fits_in_window(1, 0));
+        assert!(!fits_in_window(0, 0));
+
+        assert!(!is_overwritten(2 * WINDOW + 10, WINDOW + 11));
+        assert!(is_overwritten(2 * WINDOW + 10, WINDOW + 10));
+        assert!(!is_overwritten(10, 10));
+
+        assert_eq!(periods_between(WINDOW, WINDOW - 1), Some(1));
+        assert_eq!(periods_between(WINDOW - 1, 0), Some(0));
+        assert_eq!(periods_between(3 * WINDOW + 5, WINDOW), Some(2));
+        assert_eq!(periods_between(WINDOW, WINDOW), None);
+    }
+}
@@ -0,0 +1,155 @@
+//! Calldata of the verifier contracts that read the anchor block root from the
+//! [EIP-4788](https://eips.ethereum.org/EIPS/eip-4788) beacon roots contract, for
+//! proofs anchored at a block root (see [`crate::header`]).
+//!
+//! The beacon roots contract stores the root of each beacon block under the
+//! timestamp of the execution block of its child, i.e. of the next non-skipped
+//! slot, for the last 8191 timestamps (about 27 hours).
+
+use crate::clock::SECONDS_PER_SLOT;
+use crate::errors::VerifyError;
+use crate::header::header_gindex;
+use crate::provider::BlockRootsProof;
+use crate::verification::block_roots_gindex;
+use alloy_primitives::{address, Address, FixedBytes};
+use alloy_sol_types::{sol, SolCall};
+use ethereum_consensus::capella::presets::mainnet::SLOTS_PER_HISTORICAL_ROOT;
+
+/// Address of the beacon roots contract, on every chain that activated EIP-4788.
+pub const BEACON_ROOTS_ADDRESS: Address = address!("000F3df6D732807Ef1319fB7B8bB8522d0Beac02");
+
+/// Default number of candidate timestamps sent with the proofs.
+pub const DEFAULT_TIMESTAMP_HINTS: u64 = 4;
+
+sol! {
+    /// Interface of the verifier contracts reading the anchor block root from the
+    /// beacon roots contract, at the first of `timestamps` holding one.
+    interface IBeaconRootsAncestryVerifier {
+        function verifyAncestry(
+            uint64 targetSlot,
+            uint64[] timestamps,
+            uint64 gindex,
+            bytes32[] branch,
+            bytes32 leaf
+        );
+    }
+}
+
+/// Returns the timestamp of the execution block of `slot`, i.e. the key under which
+/// the beacon roots contract stores the root of the parent beacon block.
+pub fn beacon_roots_timestamp(slot: u64, genesis_time: u64) -> u64 {
+    genesis_time + slot * SECONDS_PER_SLOT
+}
+
+/// Builder of the calldata of [`IBeaconRootsAncestryVerifier`].
+#[derive(Debug, Clone)]
+pub struct Eip4788Calldata {
+    genesis_time: u64,
+    timestamp_hints: u64,
+}
+
+impl Eip4788Calldata {
+    /// Builds the calldata for the chain with genesis at `genesis_time`, e.g.
+    /// [`crate::clock::MAINNET_GENESIS_TIME`].
+    pub fn new(genesis_time: u64) -> Self {
+        Self {
+            genesis_time,
+            timestamp_hints: DEFAULT_TIMESTAMP_HINTS,
+        }
+    }
+
+    /// Sets the number of candidate timestamps, those of the slots following the
+    /// anchor, so that the root of the anchor is found after skipped slots.
+    pub fn with_timestamp_hints(mut self, hints: u64) -> Self {
+        self.timestamp_hints = hints.max(1);
+        self
+    }
+
+    /// ABI-encodes the call verifying the proof of the block of `target_slot`,
+    /// anchored at the root of the block of `anchor_slot`.
+    ///
+    /// Fails with [`VerifyError::UnexpectedGindex`] if `proof` is anchored at a state
+    /// root, and with [`VerifyError::InvalidProof`] for compact proofs.
+    pub fn encode(
+        &self,
+        target_slot: u64,
+        anchor_slot: u64,
+        proof: &BlockRootsProof,
+    ) -> Result<Vec<u8>, VerifyError> {
+        let BlockRootsProof::SingleProof {
+            gindex,
+            witnesses,
+            leaf,
+        } = proof
+        else {
+            return Err(VerifyError::InvalidProof);
+        };
+        let expected = header_gindex(block_roots_gindex(
+            target_slot,
+            SLOTS_PER_HISTORICAL_ROOT as u64,
+        )?);
+        if *gindex != expected {
+            return Err(VerifyError::UnexpectedGindex {
+                expected,
+                actual: *gindex,
+            });
+        }
+
+        let timestamps = (1..=self.timestamp_hints)
+            .map(|offset| beacon_roots_timestamp(anchor_slot + offset, self.genesis_time))
+            .collect();
+        Ok(IBeaconRootsAncestryVerifier::verifyAncestryCall {
+            targetSlot: target_slot,
+            timestamps,
+            gindex: *gindex,
+            branch: witnesses.iter().map(|node| FixedBytes(node.0)).collect(),
+            leaf: FixedBytes(leaf.0),
+        }
+        .abi_encode())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MAINNET_GENESIS_TIME;
+    use crate::header::anchor_at_block_root;
+    use crate::sim::SimChain;
+    use ethereum_consensus::capella::BeaconBlockHeader;
+    use ethereum_consensus::ssz::prelude::Node;
+
+    #[test]
+    fn it_should_encode_proofs_anchored_at_block_roots() {
+        let chain = SimChain::new(9_000);
+        let header = BeaconBlockHeader {
+            slot: 9_000,
+            proposer_index: 42,
+            parent_root: chain.block_root(8_999),
+            state_root: chain.state_root(9_000),
+            body_root: Node::repeat_byte(7),
+        };
+        let state_proof = chain.prove_block_root(8_990, 9_000);
+        let proof = anchor_at_block_root(state_proof.clone(), &header).unwrap();
+        let calldata = Eip4788Calldata::new(MAINNET_GENESIS_TIME).with_timestamp_hints(2);
+
+        let encoded = calldata.encode(8_990, 9_000, &proof).unwrap();
+        let call =
+            IBeaconRootsAncestryVerifier::verifyAncestryCall::abi_decode(&encoded, true).unwrap();
+        assert_eq!(call.targetSlot, 8_990);
+        assert_eq!(
+            call.timestamps,
+            [
+                MAINNET_GENESIS_TIME + 9_001 * 12,
+                MAINNET_GENESIS_TIME + 9_002 * 12
+            ]
+        );
+        assert_eq!(call.leaf, FixedBytes(chain.block_root(8_990).0));
+        assert_eq!(call.branch.len(), 21);
+
+        // anchored at the state root
+        assert!(matches!(
+            calldata.encode(8_990, 9_000, &state_proof),
+            Err(VerifyError::UnexpectedGindex { .. })
+        ));
+    }
+}
@@ -1,10 +1,38 @@
-use reqwest;
+use crate::provider::NodeOrdering;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum AncestryProverError {
     #[error("ProofProvider error: {0}")]
     ProofProviderError(#[from] ProofProviderError),
+    #[error("No prover registered for network: {0}")]
+    UnknownNetwork(String),
+    #[error("Gindex {0} is outside of the block_roots subtree")]
+    GindexOutOfBounds(u64),
+    #[error("Unexpected gindex: expected {expected}, got {actual}")]
+    UnexpectedGindex { expected: u64, actual: u64 },
+    #[error("Proof does not include gindex {0}")]
+    MissingGindex(u64),
+    #[error("Invalid state root: {0}")]
+    InvalidStateRoot(String),
+    #[error("Target slot {target} is not before the anchor slot {anchor}")]
+    TargetNotBeforeAnchor { target: u64, anchor: u64 },
+    #[error("No state found for anchor state root {state_root} at {url}")]
+    AnchorStateNotFound { state_root: String, url: String },
+    #[error("Anchor state root {0} is the root of a block, not of a state")]
+    BlockRootAsStateRoot(String),
+    #[error("None of the accepted anchors can prove slot {0}")]
+    NoAcceptedAnchor(u64),
+    #[error("The anchors prove different block roots for slot {0}")]
+    InconsistentBlockRoots(u64),
+    #[error("Deadline exceeded while proving slot {0}")]
+    DeadlineExceeded(u64),
+    #[error("Invalid anchor: {0}")]
+    InvalidAnchor(String),
+    #[error("Target is {distance} slots before the anchor, at most {max_supported} are supported")]
+    TargetTooOld { distance: u64, max_supported: u64 },
 }
 
 #[derive(Error, Debug)]
@@ -13,10 +41,548 @@ pub enum ProofProviderError {
     InputError(String),
     #[error("State or block not found: {0}")]
     NotFoundError(String),
+    #[cfg(feature = "providers")]
     #[error("Network error: {0}")]
     NetworkError(#[from] reqwest::Error),
+    #[error("Request to {url} timed out after {elapsed:?}")]
+    Timeout { url: String, elapsed: Duration },
+    #[error("Transport error for {url}: {message}")]
+    TransportError { url: String, message: String },
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
     #[error("Invalid proof error")]
     InvalidProofError(),
+    #[error("Not supported by this provider: {0}")]
+    NotSupportedError(String),
+    #[error("Unexpected content type {content_type} from {url}")]
+    UnexpectedContentType { url: String, content_type: String },
+    #[error("Response from {url} exceeds {limit} bytes")]
+    ResponseTooLarge { url: String, limit: usize },
+    #[error("Only {agreeing} backends agree on the proof, {required} required")]
+    NoQuorum { required: usize, agreeing: usize },
+    #[error("Rate limited by {url} (status {status}), retry after {retry_after:?}")]
+    RateLimited {
+        url: String,
+        status: u16,
+        retry_after: Option<Duration>,
+    },
+    #[error("Request to {url} failed with status {status}: {message}")]
+    BackendError {
+        url: String,
+        status: u16,
+        message: String,
+    },
+}
+
+/// Errors of the construction of `block_roots` trees, see [`crate::merkle`].
+#[derive(Error, Debug)]
+pub enum TreeError {
+    #[error("Invalid leaf count {0}: expected a power of two")]
+    InvalidLeafCount(usize),
+    #[cfg(feature = "rayon")]
+    #[error("Failed to build the thread pool: {0}")]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
+}
+
+/// Errors of the submission of proofs to a verifier contract, see [`crate::submitter`].
+#[cfg(feature = "evm")]
+#[derive(Error, Debug)]
+pub enum SubmitError {
+    #[error("Network error: {0}")]
+    NetworkError(#[from] reqwest::Error),
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+    #[error("JSON-RPC error {code} from {method}: {message}")]
+    RpcError {
+        method: String,
+        code: i64,
+        message: String,
+        /// Hex-encoded data of the error, e.g. the output of reverted calls.
+        data: Option<String>,
+    },
+    #[error("Verifier contract reverted: {reason}")]
+    Reverted { reason: String },
+    #[error("Unexpected result of {method}: {result}")]
+    UnexpectedResult { method: String, result: String },
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum VerifyError {
+    #[error("Invalid gindex: {0}")]
+    InvalidGindex(u64),
+    #[error(
+        "Invalid branch length for gindex {gindex}: expected {expected} witnesses, got {actual}"
+    )]
+    InvalidBranchLength {
+        gindex: u64,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("Invalid compact proof descriptor")]
+    InvalidDescriptor,
+    #[error("Invalid node count: descriptor expects {expected} nodes, got {actual}")]
+    InvalidNodeCount { expected: usize, actual: usize },
+    #[error("Compact proof nodes are in {0:?} order instead of the canonical depth-first one")]
+    NonCanonicalOrder(NodeOrdering),
+    #[error("Unexpected gindex: expected {expected}, got {actual}")]
+    UnexpectedGindex { expected: u64, actual: u64 },
+    #[error("Proof does not include gindex {0}")]
+    MissingGindex(u64),
+    #[error("Invalid block_roots window: {0}")]
+    InvalidWindow(u64),
+    #[error("Invalid proof")]
+    InvalidProof,
+    #[error("Proven leaf of gindex {0} does not match the expected one")]
+    UnexpectedLeaf(u64),
+    #[error("Proven leaf of gindex {0} is zero")]
+    ZeroLeaf(u64),
+    #[error("Branch of gindex {0} is all zeros")]
+    ZeroBranch(u64),
+    #[error("The block_roots entry of slot {target} doesn't hold its root in the state of slot {anchor}")]
+    AmbiguousTarget { target: u64, anchor: u64 },
+    #[error("Invalid node length: expected 32 bytes, got {0}")]
+    InvalidNodeLength(usize),
+    #[error("Invalid signature by key {0}")]
+    InvalidSignature(String),
+}
+
+/// Stable JSON representation of an error, so that clients can branch on
+/// `code` instead of matching on error messages.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorPayload {
+    pub code: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "ErrorDetails::is_empty")]
+    pub details: ErrorDetails,
+    /// How to fix the request, for errors caused by common integration mistakes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ErrorDetails {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elapsed_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gindex: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_gindex: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actual: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distance: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_supported: Option<u64>,
+}
+
+impl ErrorDetails {
+    pub fn is_empty(&self) -> bool {
+        self == &ErrorDetails::default()
+    }
+}
+
+impl AncestryProverError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AncestryProverError::ProofProviderError(err) => err.code(),
+            AncestryProverError::UnknownNetwork(_) => "unknown_network",
+            AncestryProverError::GindexOutOfBounds(_) => "gindex_out_of_bounds",
+            AncestryProverError::UnexpectedGindex { .. } => "unexpected_gindex",
+            AncestryProverError::MissingGindex(_) => "missing_gindex",
+            AncestryProverError::InvalidStateRoot(_) => "invalid_state_root",
+            AncestryProverError::TargetNotBeforeAnchor { .. } => "target_not_before_anchor",
+            AncestryProverError::AnchorStateNotFound { .. } => "anchor_state_not_found",
+            AncestryProverError::BlockRootAsStateRoot(_) => "block_root_as_state_root",
+            AncestryProverError::NoAcceptedAnchor(_) => "no_accepted_anchor",
+            AncestryProverError::InconsistentBlockRoots(_) => "inconsistent_block_roots",
+            AncestryProverError::DeadlineExceeded(_) => "deadline_exceeded",
+            AncestryProverError::InvalidAnchor(_) => "invalid_anchor",
+            AncestryProverError::TargetTooOld { .. } => "target_too_old",
+        }
+    }
+
+    /// Returns how to fix the request, for errors caused by common integration mistakes.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            AncestryProverError::UnknownNetwork(_) => {
+                Some("Check the network name, or register a prover for the network first.")
+            }
+            AncestryProverError::InvalidStateRoot(_) => {
+                Some("State roots are 0x-prefixed hex strings of 32 bytes.")
+            }
+            AncestryProverError::InvalidAnchor(_) => Some(
+                "Anchors are `head`, `finalized`, `justified`, a slot or a 0x-prefixed state root.",
+            ),
+            AncestryProverError::TargetNotBeforeAnchor { .. } => Some(
+                "The anchor must be a block after the target; check that the slots are not swapped.",
+            ),
+            AncestryProverError::AnchorStateNotFound { .. } => Some(
+                "Check that the anchor root is the state root of the anchor block (the `state_root` of its header) and not its block root, and that the provider serves the same network.",
+            ),
+            AncestryProverError::BlockRootAsStateRoot(_) => Some(
+                "Pass the state root of the anchor block (the `state_root` of its header) instead of its block root.",
+            ),
+            AncestryProverError::NoAcceptedAnchor(_) => Some(
+                "Wait for the destination to accept a newer anchor, or relay one that is less than SLOTS_PER_HISTORICAL_ROOT slots after the target.",
+            ),
+            AncestryProverError::TargetTooOld { .. } => Some(
+                "Use an anchor less than SLOTS_PER_HISTORICAL_ROOT slots after the target, or check support with `AncestryProver::can_prove` first.",
+            ),
+            _ => None,
+        }
+    }
+
+    pub fn to_payload(&self) -> ErrorPayload {
+        match self {
+            AncestryProverError::ProofProviderError(err) => err.to_payload(),
+            _ => {
+                let details = match self {
+                    AncestryProverError::GindexOutOfBounds(gindex)
+                    | AncestryProverError::MissingGindex(gindex) => ErrorDetails {
+                        gindex: Some(*gindex),
+                        ..Default::default()
+                    },
+                    AncestryProverError::UnexpectedGindex { expected, actual } => ErrorDetails {
+                        gindex: Some(*actual),
+                        expected_gindex: Some(*expected),
+                        ..Default::default()
+                    },
+                    AncestryProverError::TargetNotBeforeAnchor { target, .. } => ErrorDetails {
+                        slot: Some(*target),
+                        ..Default::default()
+                    },
+                    AncestryProverError::NoAcceptedAnchor(slot)
+                    | AncestryProverError::InconsistentBlockRoots(slot)
+                    | AncestryProverError::DeadlineExceeded(slot) => ErrorDetails {
+                        slot: Some(*slot),
+                        ..Default::default()
+                    },
+                    AncestryProverError::AnchorStateNotFound { url, .. } => ErrorDetails {
+                        url: Some(url.clone()),
+                        ..Default::default()
+                    },
+                    AncestryProverError::TargetTooOld {
+                        distance,
+                        max_supported,
+                    } => ErrorDetails {
+                        distance: Some(*distance),
+                        max_supported: Some(*max_supported),
+                        ..Default::default()
+                    },
+                    _ => ErrorDetails::default(),
+                };
+
+                ErrorPayload {
+                    code: self.code().to_string(),
+                    message: self.to_string(),
+                    details,
+                    hint: self.hint().map(str::to_string),
+                }
+            }
+        }
+    }
+}
+
+impl ProofProviderError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ProofProviderError::InputError(_) => "input_error",
+            ProofProviderError::NotFoundError(_) => "not_found",
+            #[cfg(feature = "providers")]
+            ProofProviderError::NetworkError(_) => "network_error",
+            ProofProviderError::Timeout { .. } => "timeout",
+            ProofProviderError::TransportError { .. } => "transport_error",
+            ProofProviderError::SerializationError(_) => "serialization_error",
+            ProofProviderError::InvalidProofError() => "invalid_proof",
+            ProofProviderError::NotSupportedError(_) => "not_supported",
+            ProofProviderError::UnexpectedContentType { .. } => "unexpected_content_type",
+            ProofProviderError::ResponseTooLarge { .. } => "response_too_large",
+            ProofProviderError::NoQuorum { .. } => "no_quorum",
+            ProofProviderError::RateLimited { .. } => "rate_limited",
+            ProofProviderError::BackendError { .. } => "backend_error",
+        }
+    }
+
+    /// Returns whether the request may succeed when retried, i.e. it failed in transit.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            #[cfg(feature = "providers")]
+            ProofProviderError::NetworkError(_) => true,
+            ProofProviderError::Timeout { .. }
+            | ProofProviderError::TransportError { .. }
+            | ProofProviderError::RateLimited { .. } => true,
+            ProofProviderError::BackendError { status, .. } => *status >= 500,
+            _ => false,
+        }
+    }
+
+    /// Returns the wait advised by the backend before retrying, e.g. from the
+    /// `Retry-After` header of rate limited requests.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ProofProviderError::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    pub fn to_payload(&self) -> ErrorPayload {
+        let details = match self {
+            ProofProviderError::NotFoundError(url)
+            | ProofProviderError::TransportError { url, .. }
+            | ProofProviderError::BackendError { url, .. }
+            | ProofProviderError::UnexpectedContentType { url, .. }
+            | ProofProviderError::ResponseTooLarge { url, .. } => ErrorDetails {
+                url: Some(url.clone()),
+                ..Default::default()
+            },
+            #[cfg(feature = "providers")]
+            ProofProviderError::NetworkError(err) => ErrorDetails {
+                url: err.url().map(|url| url.to_string()),
+                ..Default::default()
+            },
+            ProofProviderError::Timeout { url, elapsed } => ErrorDetails {
+                url: Some(url.clone()),
+                elapsed_ms: Some(elapsed.as_millis() as u64),
+                ..Default::default()
+            },
+            ProofProviderError::RateLimited {
+                url, retry_after, ..
+            } => ErrorDetails {
+                url: Some(url.clone()),
+                retry_after_ms: retry_after.map(|wait| wait.as_millis() as u64),
+                ..Default::default()
+            },
+            ProofProviderError::NoQuorum { required, agreeing } => ErrorDetails {
+                expected: Some(*required),
+                actual: Some(*agreeing),
+                ..Default::default()
+            },
+            _ => ErrorDetails::default(),
+        };
+
+        ErrorPayload {
+            code: self.code().to_string(),
+            message: self.to_string(),
+            details,
+            hint: None,
+        }
+    }
+}
+
+impl VerifyError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            VerifyError::InvalidGindex(_) => "invalid_gindex",
+            VerifyError::InvalidBranchLength { .. } => "invalid_branch_length",
+            VerifyError::InvalidDescriptor => "invalid_descriptor",
+            VerifyError::InvalidNodeCount { .. } => "invalid_node_count",
+            VerifyError::NonCanonicalOrder(_) => "non_canonical_order",
+            VerifyError::UnexpectedGindex { .. } => "unexpected_gindex",
+            VerifyError::MissingGindex(_) => "missing_gindex",
+            VerifyError::InvalidWindow(_) => "invalid_window",
+            VerifyError::InvalidProof => "invalid_proof",
+            VerifyError::UnexpectedLeaf(_) => "unexpected_leaf",
+            VerifyError::ZeroLeaf(_) => "zero_leaf",
+            VerifyError::ZeroBranch(_) => "zero_branch",
+            VerifyError::AmbiguousTarget { .. } => "ambiguous_target",
+            VerifyError::InvalidNodeLength(_) => "invalid_node_length",
+            VerifyError::InvalidSignature(_) => "invalid_signature",
+        }
+    }
+
+    pub fn to_payload(&self) -> ErrorPayload {
+        let details = match self {
+            VerifyError::InvalidGindex(gindex)
+            | VerifyError::MissingGindex(gindex)
+            | VerifyError::UnexpectedLeaf(gindex)
+            | VerifyError::ZeroLeaf(gindex)
+            | VerifyError::ZeroBranch(gindex) => ErrorDetails {
+                gindex: Some(*gindex),
+                ..Default::default()
+            },
+            VerifyError::UnexpectedGindex { expected, actual } => ErrorDetails {
+                gindex: Some(*actual),
+                expected_gindex: Some(*expected),
+                ..Default::default()
+            },
+            VerifyError::InvalidBranchLength {
+                gindex,
+                expected,
+                actual,
+            } => ErrorDetails {
+                gindex: Some(*gindex),
+                expected: Some(*expected),
+                actual: Some(*actual),
+                ..Default::default()
+            },
+            VerifyError::InvalidNodeCount { expected, actual } => ErrorDetails {
+                expected: Some(*expected),
+                actual: Some(*actual),
+                ..Default::default()
+            },
+            VerifyError::AmbiguousTarget { target, .. } => ErrorDetails {
+                slot: Some(*target),
+                ..Default::default()
+            },
+            VerifyError::InvalidNodeLength(actual) => ErrorDetails {
+                expected: Some(32),
+                actual: Some(*actual),
+                ..Default::default()
+            },
+            _ => ErrorDetails::default(),
+        };
+
+        ErrorPayload {
+            code: self.code().to_string(),
+            message: self.to_string(),
+            details,
+            hint: None,
+        }
+    }
+}
+
+#[cfg(feature = "evm")]
+impl SubmitError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            SubmitError::NetworkError(_) => "network_error",
+            SubmitError::SerializationError(_) => "serialization_error",
+            SubmitError::RpcError { .. } => "rpc_error",
+            SubmitError::Reverted { .. } => "reverted",
+            SubmitError::UnexpectedResult { .. } => "unexpected_result",
+        }
+    }
+
+    pub fn to_payload(&self) -> ErrorPayload {
+        let details = match self {
+            SubmitError::NetworkError(err) => ErrorDetails {
+                url: err.url().map(|url| url.to_string()),
+                ..Default::default()
+            },
+            _ => ErrorDetails::default(),
+        };
+
+        ErrorPayload {
+            code: self.code().to_string(),
+            message: self.to_string(),
+            details,
+            hint: None,
+        }
+    }
+}
+
+impl From<&AncestryProverError> for ErrorPayload {
+    fn from(err: &AncestryProverError) -> Self {
+        err.to_payload()
+    }
+}
+
+impl From<&ProofProviderError> for ErrorPayload {
+    fn from(err: &ProofProviderError) -> Self {
+        err.to_payload()
+    }
+}
+
+#[cfg(feature = "evm")]
+impl From<&SubmitError> for ErrorPayload {
+    fn from(err: &SubmitError) -> Self {
+        err.to_payload()
+    }
+}
+
+impl From<&VerifyError> for ErrorPayload {
+    fn from(err: &VerifyError) -> Self {
+        err.to_payload()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn it_should_serialize_provider_errors_with_code_and_details() {
+        let err = AncestryProverError::from(ProofProviderError::Timeout {
+            url: "http://localhost/state_proof".to_string(),
+            elapsed: Duration::from_millis(1500),
+        });
+
+        assert_eq!(
+            serde_json::to_value(ErrorPayload::from(&err)).unwrap(),
+            json!({
+                "code": "timeout",
+                "message": "Request to http://localhost/state_proof timed out after 1.5s",
+                "details": {
+                    "url": "http://localhost/state_proof",
+                    "elapsed_ms": 1500
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn it_should_omit_empty_details() {
+        let err = ProofProviderError::InputError("bad input".to_string());
+
+        assert_eq!(
+            serde_json::to_value(ErrorPayload::from(&err)).unwrap(),
+            json!({
+                "code": "input_error",
+                "message": "Input error: bad input"
+            })
+        );
+    }
+
+    #[test]
+    fn it_should_include_hints_for_integration_mistakes() {
+        let err = AncestryProverError::AnchorStateNotFound {
+            state_root: "0xa168".to_string(),
+            url: "http://localhost/eth/v0/beacon/proof/state/0xa168".to_string(),
+        };
+        let payload = ErrorPayload::from(&err);
+
+        assert_eq!(payload.code, "anchor_state_not_found");
+        assert_eq!(payload.hint.as_deref(), err.hint());
+        assert!(payload.hint.unwrap().contains("not its block root"));
+    }
+
+    #[test]
+    fn it_should_include_the_distance_of_targets_too_old() {
+        let err = AncestryProverError::TargetTooOld {
+            distance: 8193,
+            max_supported: 8192,
+        };
+
+        assert_eq!(
+            serde_json::to_value(ErrorPayload::from(&err)).unwrap()["details"],
+            json!({
+                "distance": 8193,
+                "max_supported": 8192
+            })
+        );
+    }
+
+    #[test]
+    fn it_should_include_gindex_for_verify_errors() {
+        let err = VerifyError::InvalidBranchLength {
+            gindex: 309_908,
+            expected: 18,
+            actual: 17,
+        };
+        let payload = ErrorPayload::from(&err);
+
+        assert_eq!(payload.code, "invalid_branch_length");
+        assert_eq!(payload.details.gindex, Some(309_908));
+        assert_eq!(payload.details.expected, Some(18));
+        assert_eq!(payload.details.actual, Some(17));
+    }
 }
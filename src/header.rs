@@ -0,0 +1,192 @@
+//! Proofs anchored at a beacon block root instead of its state root, for verifiers
+//! that only know block roots (e.g. the consumers of the EIP-4788 beacon roots
+//! contract).
+//!
+//! The proof of a `block_roots` entry in the state is extended with the branch of
+//! `state_root` in the `BeaconBlockHeader`, so that it verifies against the root of
+//! the header, i.e. the block root.
+
+use crate::errors::VerifyError;
+use crate::merkle::BlockRootsTree;
+use crate::provider::{descriptor_gindices, BlockRootsProof, Verify};
+use crate::verification::{block_roots_gindex, proof_root, proven_leaf};
+use alloy_primitives::FixedBytes;
+use ethereum_consensus::capella::BeaconBlockHeader;
+use ethereum_consensus::ssz::prelude::Node;
+
+/// Generalized index of `state_root` in a `BeaconBlockHeader`.
+pub const HEADER_STATE_ROOT_GINDEX: u64 = 11;
+
+/// Depth of the fields in the (8 leaf) `BeaconBlockHeader` container.
+const HEADER_DEPTH: u32 = HEADER_STATE_ROOT_GINDEX.ilog2();
+
+/// Returns the gindex, in the block header, of the node at `state_gindex` in its state.
+pub const fn header_gindex(state_gindex: u64) -> u64 {
+    let depth = state_gindex.ilog2();
+    (HEADER_STATE_ROOT_GINDEX << depth) | (state_gindex ^ (1 << depth))
+}
+
+fn header_tree(header: &BeaconBlockHeader) -> BlockRootsTree {
+    let mut leaves = vec![Node::default(); 1 << HEADER_DEPTH];
+    leaves[0] = Node::right_padding_from(&header.slot.to_le_bytes());
+    leaves[1] = Node::right_padding_from(&(header.proposer_index as u64).to_le_bytes());
+    leaves[2] = header.parent_root;
+    leaves[3] = header.state_root;
+    leaves[4] = header.body_root;
+    BlockRootsTree::new(&leaves)
+}
+
+/// Returns the root of `header`, i.e. the root of its block.
+pub fn header_root(header: &BeaconBlockHeader) -> Node {
+    header_tree(header).root()
+}
+
+/// Returns the proof of the state root of `header` under its root.
+pub fn prove_header_state_root(header: &BeaconBlockHeader) -> BlockRootsProof {
+    header_tree(header).prove_leaf((HEADER_STATE_ROOT_GINDEX - (1 << HEADER_DEPTH)) as usize)
+}
+
+/// Extends `proof`, of nodes of the state of `header`, into a proof of the same
+/// nodes under the root of `header`.
+///
+/// Fails with [`VerifyError::InvalidProof`] if `proof` is not a proof against the
+/// state root of `header`.
+pub fn anchor_at_block_root(
+    proof: BlockRootsProof,
+    header: &BeaconBlockHeader,
+) -> Result<BlockRootsProof, VerifyError> {
+    if proof_root(&proof)? != header.state_root {
+        return Err(VerifyError::InvalidProof);
+    }
+    let BlockRootsProof::SingleProof {
+        witnesses: header_witnesses,
+        ..
+    } = prove_header_state_root(header)
+    else {
+        unreachable!("trees prove single leaves with single proofs");
+    };
+
+    match proof {
+        BlockRootsProof::SingleProof {
+            gindex,
+            mut witnesses,
+            leaf,
+        } => {
+            witnesses.extend(header_witnesses);
+            Ok(BlockRootsProof::SingleProof {
+                gindex: header_gindex(gindex),
+                witnesses,
+                leaf,
+            })
+        }
+        BlockRootsProof::CompactProof { descriptor, nodes } => {
+            // the path to the state root in depth-first order: inner nodes 1 and 2,
+            // witness 4, inner node 5 and witness 10, then the subtree of the state
+            // root (node 11) and finally witness 3
+            let state_bits = 2 * descriptor_gindices(&descriptor)?.len() - 1;
+            let bits = [false, false, true, false, true]
+                .into_iter()
+                .chain(
+                    descriptor
+                        .iter()
+                        .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1))
+                        .take(state_bits),
+                )
+                .chain([true])
+                .collect::<Vec<_>>();
+            let descriptor = bits
+                .chunks(8)
+                .map(|chunk| {
+                    chunk
+                        .iter()
+                        .enumerate()
+                        .fold(0u8, |byte, (i, bit)| byte | (u8::from(*bit) << (7 - i)))
+                })
+                .collect();
+
+            let [witness_10, witness_4, witness_3] = header_witnesses[..] else {
+                unreachable!("the header tree has a depth of 3");
+            };
+            let nodes = [witness_4, witness_10]
+                .into_iter()
+                .chain(nodes)
+                .chain([witness_3])
+                .collect();
+            Ok(BlockRootsProof::CompactProof { descriptor, nodes })
+        }
+    }
+}
+
+/// Like [`crate::verification::verify_block_roots_proof`], but for proofs anchored
+/// at the root of the anchor block, see [`anchor_at_block_root`]. Also rejects the
+/// proofs of a zero leaf, like [`crate::verification::verify_block_roots_proof_strict`].
+pub fn verify_block_roots_proof_at_block_root(
+    block_root: [u8; 32],
+    target_slot: u64,
+    window: u64,
+    proof: &BlockRootsProof,
+) -> Result<[u8; 32], VerifyError> {
+    let expected = header_gindex(block_roots_gindex(target_slot, window)?);
+    let leaf = proven_leaf(proof, expected)?;
+    if leaf == Node::default() {
+        return Err(VerifyError::ZeroLeaf(expected));
+    }
+
+    proof.verify(FixedBytes(block_root))?;
+
+    Ok(leaf.0)
+}
+
+#[cfg(all(test, feature = "providers"))]
+mod tests {
+    use super::*;
+    use crate::provider::merge_proofs;
+    use crate::sim::SimChain;
+    use crate::verification::mainnet_block_roots_gindex;
+
+    fn header(chain: &SimChain, slot: u64) -> BeaconBlockHeader {
+        BeaconBlockHeader {
+            slot,
+            proposer_index: 42,
+            parent_root: chain.block_root(slot - 1),
+            state_root: chain.state_root(slot),
+            body_root: Node::repeat_byte(7),
+        }
+    }
+
+    #[test]
+    fn it_should_anchor_proofs_at_the_block_root() {
+        let chain = SimChain::new(9_000);
+        let header = header(&chain, 9_000);
+        let block_root = header_root(&header).0;
+
+        let single = chain.prove_block_root(8_990, 9_000);
+        let gindex = mainnet_block_roots_gindex(8_990);
+        let compact = merge_proofs(std::slice::from_ref(&single), &[gindex]).unwrap();
+        for proof in [single, compact] {
+            let anchored = anchor_at_block_root(proof, &header).unwrap();
+            assert_eq!(
+                verify_block_roots_proof_at_block_root(block_root, 8_990, 8_192, &anchored),
+                Ok(chain.block_root(8_990).0)
+            );
+            assert_eq!(
+                proven_leaf(&anchored, header_gindex(gindex)),
+                Ok(chain.block_root(8_990))
+            );
+            assert!(anchored
+                .verify(FixedBytes(chain.state_root(9_000).0))
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn it_should_reject_proofs_of_another_state() {
+        let chain = SimChain::new(9_000);
+        let proof = chain.prove_block_root(8_990, 8_999);
+
+        assert_eq!(
+            anchor_at_block_root(proof, &header(&chain, 9_000)),
+            Err(VerifyError::InvalidProof)
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Index math for proving blocks older than `SLOTS_PER_HISTORICAL_ROOT` through the
+//! `historical_summaries` field of the beacon state.
+//!
+//! Each historical summary commits (through its `block_summary_root`) to the
+//! `block_roots` vector of a completed period of `SLOTS_PER_HISTORICAL_ROOT`
+//! slots. Summaries are accumulated since the Capella fork, so the summary of a
+//! slot is found by its period relative to the Capella fork period.
+
+use crate::distance::periods_between;
+#[cfg(feature = "providers")]
+use crate::errors::ProofProviderError;
+#[cfg(feature = "providers")]
+use crate::provider::{ProofProvider, StateId};
+use ethereum_consensus::capella::presets::mainnet::{BeaconState, SLOTS_PER_HISTORICAL_ROOT};
+use ethereum_consensus::ssz::prelude::*;
+
+/// First slot of the Capella fork on mainnet (epoch 194048).
+pub const MAINNET_CAPELLA_FORK_SLOT: u64 = 6_209_536;
+
+/// Position of a slot's block root inside the historical summaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoricalSummaryPosition {
+    /// Index of the summary in the `historical_summaries` list.
+    pub summary_index: u64,
+    /// Index of the block root in the `block_roots` vector summarized by `block_summary_root`.
+    pub block_roots_index: u64,
+}
+
+/// Returns the period (`slot / SLOTS_PER_HISTORICAL_ROOT`) of a slot.
+pub fn period(slot: u64) -> u64 {
+    slot / SLOTS_PER_HISTORICAL_ROOT as u64
+}
+
+/// Returns where the block root of `slot` is found in the historical summaries,
+/// or `None` if the slot precedes the Capella fork.
+pub fn historical_summary_position(
+    slot: u64,
+    capella_fork_slot: u64,
+) -> Option<HistoricalSummaryPosition> {
+    let summary_index = period(slot).checked_sub(period(capella_fork_slot))?;
+
+    Some(HistoricalSummaryPosition {
+        summary_index,
+        block_roots_index: slot % SLOTS_PER_HISTORICAL_ROOT as u64,
+    })
+}
+
+/// Checks whether the block root of `slot` has been summarized in the state of `anchor_slot`,
+/// i.e. whether its period has been completed before the anchor's.
+pub fn is_summarized(slot: u64, anchor_slot: u64, capella_fork_slot: u64) -> bool {
+    slot >= capella_fork_slot
+        && periods_between(anchor_slot, slot).is_some_and(|periods| periods > 0)
+}
+
+/// Returns the gindex of `historical_summaries[summary_index].block_summary_root` in the beacon state,
+/// or `None` if `summary_index` is over the limit of the `historical_summaries` list.
+pub fn block_summary_root_gindex(summary_index: u64) -> Option<u64> {
+    let path = &[
+        "historical_summaries".into(),
+        PathElement::Index(usize::try_from(summary_index).ok()?),
+        "block_summary_root".into(),
+    ];
+    BeaconState::generalized_index(path)
+        .ok()
+        .map(|gindex| gindex as u64)
+}
+
+/// Returns the gindex of a block root in the `block_roots` vector of a period,
+/// relative to its `block_summary_root`.
+pub fn period_block_root_gindex(block_roots_index: u64) -> u64 {
+    SLOTS_PER_HISTORICAL_ROOT as u64 + block_roots_index
+}
+
+/// Returns the gindex of a summarized block root relative to the beacon state root,
+/// i.e. the concatenation of the summary and the intra-period gindexes, or `None`
+/// if the summary is out of the `historical_summaries` list.
+pub fn historical_block_root_gindex(position: &HistoricalSummaryPosition) -> Option<u64> {
+    block_summary_root_gindex(position.summary_index)?
+        .checked_mul(SLOTS_PER_HISTORICAL_ROOT as u64)?
+        .checked_add(position.block_roots_index)
+}
+
+/// Returns the slot whose beacon state holds the complete `block_roots` vector
+/// of `period`, i.e. the first slot of the next period.
+pub fn period_state_slot(period: u64) -> u64 {
+    (period + 1) * SLOTS_PER_HISTORICAL_ROOT as u64
+}
+
+/// Fetches the block roots of all the slots of a completed `period`, from
+/// which its `block_summary_root` subtree can be rebuilt.
+///
+/// The state is requested by slot, so the block roots are unverified: callers must
+/// check the root of the rebuilt subtree against a proven `block_summary_root`, as
+/// [`PeriodTreeCache`](crate::period_tree_cache::PeriodTreeCache) does.
+#[cfg(feature = "providers")]
+pub async fn fetch_period_block_roots<P: ProofProvider>(
+    provider: &P,
+    period: u64,
+) -> Result<Vec<Node>, ProofProviderError> {
+    let state_id = StateId::Slot(period_state_slot(period));
+    let block_roots = provider.get_block_roots(&state_id).await?;
+
+    if block_roots.len() != SLOTS_PER_HISTORICAL_ROOT {
+        return Err(ProofProviderError::InvalidProofError());
+    }
+
+    Ok(block_roots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "providers")]
+    use crate::provider::MockProofProvider;
+
+    #[test]
+    fn it_should_compute_summary_position() {
+        // slot 7862720 is in period 959, the 201st period since capella (758)
+        assert_eq!(
+            historical_summary_position(7_862_720, MAINNET_CAPELLA_FORK_SLOT),
+            Some(HistoricalSummaryPosition {
+                summary_index: 201,
+                block_roots_index: 6592,
+            })
+        );
+        assert_eq!(
+            historical_summary_position(MAINNET_CAPELLA_FORK_SLOT - 1, MAINNET_CAPELLA_FORK_SLOT),
+            None
+        );
+    }
+
+    #[test]
+    fn it_should_compute_block_summary_root_gindex() {
+        // matches the state prover testdata for historical_summaries,201,block_summary_root
+        assert_eq!(block_summary_root_gindex(201), Some(3_959_423_378));
+        // historical_summaries is limited to HISTORICAL_ROOTS_LIMIT = 2**24 entries
+        assert!(block_summary_root_gindex((1 << 24) - 1).is_some());
+        assert_eq!(block_summary_root_gindex(1 << 24), None);
+        assert_eq!(block_summary_root_gindex(u64::MAX), None);
+    }
+
+    #[test]
+    fn it_should_concatenate_gindexes() {
+        let position = HistoricalSummaryPosition {
+            summary_index: 201,
+            block_roots_index: 6592,
+        };
+
+        assert_eq!(period_block_root_gindex(6592), 14_784);
+        assert_eq!(
+            historical_block_root_gindex(&position),
+            Some(3_959_423_378 * 8192 + 6592)
+        );
+    }
+
+    #[test]
+    fn it_should_only_consider_completed_periods_summarized() {
+        assert!(is_summarized(
+            7_862_720,
+            7_864_320,
+            MAINNET_CAPELLA_FORK_SLOT
+        ));
+        assert!(!is_summarized(
+            7_862_720,
+            7_864_319,
+            MAINNET_CAPELLA_FORK_SLOT
+        ));
+        assert!(!is_summarized(
+            6_000_000,
+            7_864_320,
+            MAINNET_CAPELLA_FORK_SLOT
+        ));
+    }
+
+    #[cfg(feature = "providers")]
+    #[tokio::test]
+    async fn it_should_fetch_block_roots_from_the_next_period_state() {
+        let mut provider = MockProofProvider::new();
+        provider
+            .expect_get_block_roots()
+            .withf(|state_id| *state_id == StateId::Slot(7_864_320))
+            .returning(|_| Ok(vec![Node::default(); SLOTS_PER_HISTORICAL_ROOT]));
+
+        let block_roots = fetch_period_block_roots(&provider, 959).await.unwrap();
+
+        assert_eq!(block_roots.len(), SLOTS_PER_HISTORICAL_ROOT);
+    }
+
+    #[cfg(feature = "providers")]
+    #[tokio::test]
+    async fn it_should_reject_incomplete_block_roots() {
+        let mut provider = MockProofProvider::new();
+        provider
+            .expect_get_block_roots()
+            .returning(|_| Ok(vec![Node::default(); 10]));
+
+        let result = fetch_period_block_roots(&provider, 959).await;
+
+        assert!(matches!(
+            result,
+            Err(ProofProviderError::InvalidProofError())
+        ));
+    }
+}
@@ -51,15 +51,78 @@
 //!
 //! This crate allows you to easily verify the ancestry of Ethereum beacon blocks using the provided providers or any custom provider that implements the `ProverProvider` trait.
 
+pub mod anchor;
+pub mod anchor_lock;
+pub mod batch;
+pub mod bundle;
+pub mod canonical;
+#[cfg(feature = "providers")]
+pub mod client;
+pub mod clock;
+pub mod composed;
+#[cfg(feature = "providers")]
+pub mod concurrency;
+pub mod consistency;
+#[cfg(feature = "cosmwasm")]
+pub mod cosmwasm;
+pub mod diff;
+pub mod distance;
+#[cfg(feature = "evm")]
+pub mod eip4788;
 pub mod errors;
+pub mod header;
+pub mod historical;
+#[cfg(feature = "providers")]
 pub mod lodestar_provider;
+pub mod merkle;
+#[cfg(feature = "providers")]
+pub mod middleware;
+pub mod period_tree_cache;
+#[cfg(feature = "providers")]
+pub mod pipeline;
 pub mod prover;
 pub mod provider;
+#[cfg(feature = "providers")]
+pub mod quorum;
+#[cfg(feature = "providers")]
+pub mod registry;
+#[cfg(feature = "schemars")]
+pub mod schema;
+#[cfg(feature = "signing")]
+pub mod signing;
+#[cfg(feature = "providers")]
+pub mod sim;
+#[cfg(feature = "providers")]
 pub mod state_prover_provider;
+pub mod stats;
+#[cfg(feature = "evm")]
+pub mod submitter;
+#[cfg(feature = "providers")]
+pub mod testing;
+pub mod transform;
+#[cfg(all(feature = "providers", unix))]
+pub mod transport;
+pub mod validation;
+pub mod verification;
+pub mod version;
 
-pub use prover::verify;
-pub use prover::AncestryProver;
-pub use provider::{BlockRootsProof, ProofProvider};
+pub use composed::{chain_proofs, ComposedProof};
+pub use prover::{verify, verify_at_block_root, verify_expected, verify_strict};
+#[cfg(feature = "providers")]
+pub use prover::{AncestryProver, ProveOptions};
+#[cfg(feature = "providers")]
+pub use provider::ProofProvider;
+pub use provider::{BlockRootsProof, NodeOrdering, StateId};
+#[cfg(feature = "providers")]
+pub use registry::ProverRegistry;
+pub use verification::{verify_block_roots_proof, verify_block_roots_proof_for_anchor};
 
+#[cfg(feature = "rayon")]
+pub use merkle::ParallelTreeBuilder;
+
+#[cfg(feature = "providers")]
 pub use lodestar_provider::LodestarProvider;
+#[cfg(feature = "providers")]
+pub use quorum::QuorumProvider;
+#[cfg(feature = "providers")]
 pub use state_prover_provider::StateProverProvider;
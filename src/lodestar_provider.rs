@@ -1,16 +1,97 @@
 use crate::errors::ProofProviderError;
-use crate::provider::{BlockRootsProof, ProofProvider};
+use crate::middleware::{
+    self, ContentType, HttpService, ReqwestService, DEFAULT_MAX_RESPONSE_SIZE,
+};
+use crate::provider::{
+    descriptor_gindices, deserialize_bounded, merge_proofs, BlockRootsProof, ProofProvider,
+    ProviderInfo, StateId, Verify, MAX_PROOF_NODES,
+};
+use crate::stats::{Stats, StatsRecorder};
 use ::ssz_rs::compact_multiproofs::compute_proof_descriptor;
+use alloy_primitives::FixedBytes;
 use async_trait::async_trait;
+use ethereum_consensus::capella::presets::mainnet::{BeaconState, SLOTS_PER_HISTORICAL_ROOT};
+use ethereum_consensus::capella::BeaconBlockHeader;
 use ethereum_consensus::ssz::prelude::*;
+use futures::future::try_join_all;
 use hex;
-use mockall::automock;
+use reqwest::{Client, Method, Request, Response, Url};
 use serde::{Deserialize, Serialize};
+use std::sync::{Arc, OnceLock};
+use tower::{Layer, Service};
+
+/// Default limit of the size of the descriptors of the multiproofs requested at once,
+/// whose hex encoding is a query parameter, to stay under the usual URL length limits.
+pub const DEFAULT_MAX_DESCRIPTOR_SIZE: usize = 2048;
 
 /// Provider that uses the [Lodestar](http://lodestar.chainsafe.io/) API directly.
 #[derive(Clone)]
 pub struct LodestarProvider {
     rpc: String,
+    service: HttpService,
+    stats: StatsRecorder,
+    max_response_size: usize,
+    max_descriptor_size: usize,
+    query_params: Vec<(String, String)>,
+    // negotiated on the first proof request answered by a known route, shared by clones
+    api_version: Arc<OnceLock<ProofApiVersion>>,
+}
+
+/// Version of the Lodestar proof endpoints (`/eth/{version}/beacon/proof/...`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofApiVersion {
+    V0,
+    V1,
+}
+
+impl ProofApiVersion {
+    fn path(&self) -> &'static str {
+        match self {
+            ProofApiVersion::V0 => "eth/v0",
+            ProofApiVersion::V1 => "eth/v1",
+        }
+    }
+
+    fn parse_leaves(&self, bytes: &[u8]) -> Result<Vec<Node>, ProofProviderError> {
+        let data = match self {
+            ProofApiVersion::V0 => serde_json::from_slice::<ProofResponse>(bytes).map(|r| r.data),
+            ProofApiVersion::V1 => serde_json::from_slice::<ApiResponse>(bytes).map(|r| r.data),
+        };
+
+        data.map(|data| data.leaves)
+            .map_err(|_| ProofProviderError::InvalidProofError())
+    }
+}
+
+/// Most bytes of the body of a 404 response read to tell unknown routes from
+/// missing states, see [`is_unknown_route`].
+const MAX_NOT_FOUND_BODY_SIZE: usize = 4 * 1024;
+
+/// Error body of Lodestar (i.e. fastify) 404 responses.
+#[derive(Deserialize, Debug)]
+struct NotFoundResponse {
+    message: String,
+}
+
+/// Tells the 404 responses to unknown routes, e.g. `Route GET:/eth/v1/... not found`,
+/// from those of known routes to missing states or blocks.
+fn is_unknown_route(body: &[u8]) -> bool {
+    serde_json::from_slice::<NotFoundResponse>(body).is_ok_and(|response| {
+        response.message.starts_with("Route ") && response.message.ends_with(" not found")
+    })
+}
+
+/// Most bytes of the SSZ encoding of a compact multiproof, read before failing
+/// with [`ProofProviderError::ResponseTooLarge`].
+const MAX_SSZ_PROOF_SIZE: usize = MAX_PROOF_NODES * 32;
+
+/// Parses the SSZ encoding of the leaves of a compact multiproof, i.e. their concatenation.
+fn parse_ssz_leaves(bytes: &[u8]) -> Result<Vec<Node>, ProofProviderError> {
+    if !bytes.len().is_multiple_of(32) || bytes.len() / 32 > MAX_PROOF_NODES {
+        return Err(ProofProviderError::InvalidProofError());
+    }
+
+    Ok(bytes.chunks(32).map(Node::from_slice).collect())
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -19,67 +100,817 @@ struct ProofResponse {
     version: String,
 }
 
+/// Standard beacon API envelope, whose metadata (`version`, `finalized`, ...) is optional.
+#[derive(Deserialize, Debug)]
+struct ApiResponse {
+    data: ProofData,
+}
+
+#[derive(Deserialize, Debug)]
+struct BlockRootResponse {
+    data: BlockRootData,
+}
+
+#[derive(Deserialize, Debug)]
+struct BlockRootData {
+    root: Node,
+}
+
+#[derive(Deserialize, Debug)]
+struct HeaderResponse {
+    data: HeaderData,
+}
+
+#[derive(Deserialize, Debug)]
+struct HeaderData {
+    header: SignedHeader,
+}
+
+#[derive(Deserialize, Debug)]
+struct SignedHeader {
+    message: BeaconBlockHeader,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct ProofData {
+    #[serde(deserialize_with = "deserialize_bounded::<_, _, MAX_PROOF_NODES>")]
     leaves: Vec<Node>,
     descriptor: String,
 }
 
 impl LodestarProvider {
     pub fn new(rpc: String) -> Self {
-        Self { rpc }
+        Self::with_service(rpc, middleware::boxed(ReqwestService::default()))
     }
 
-    async fn get(&self, req: &str) -> Result<Vec<u8>, ProofProviderError> {
-        let response = reqwest::get(req)
-            .await
-            .map_err(ProofProviderError::NetworkError)?;
+    /// Creates a provider that uses the given client (and its connection pool).
+    pub fn with_client(rpc: String, client: Client) -> Self {
+        Self::with_service(rpc, middleware::boxed(ReqwestService::new(client)))
+    }
+
+    /// Creates a provider that sends its requests through a custom `tower::Service` stack.
+    pub fn with_service(rpc: String, service: HttpService) -> Self {
+        Self {
+            rpc,
+            service,
+            stats: StatsRecorder::default(),
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            max_descriptor_size: DEFAULT_MAX_DESCRIPTOR_SIZE,
+            query_params: Vec::new(),
+            api_version: Arc::new(OnceLock::new()),
+        }
+    }
+
+    /// Pins the version of the proof endpoints instead of negotiating it, i.e.
+    /// trying the newest endpoints first and falling back to older ones if the
+    /// node doesn't know their route.
+    pub fn with_api_version(mut self, version: ProofApiVersion) -> Self {
+        self.api_version = Arc::new(OnceLock::from(version));
+        self
+    }
+
+    /// Returns the version of the proof endpoints, once negotiated.
+    pub fn api_version(&self) -> Option<ProofApiVersion> {
+        self.api_version.get().copied()
+    }
+
+    /// Wraps the request stack of the provider with an additional layer,
+    /// e.g. for authentication, tracing, retries or rate limiting.
+    pub fn layer<L>(mut self, layer: L) -> Self
+    where
+        L: Layer<HttpService>,
+        L::Service: Service<Request, Response = Response, Error = ProofProviderError>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        <L::Service as Service<Request>>::Future: Send + 'static,
+    {
+        self.service = middleware::wrap(self.service, layer);
+        self
+    }
+
+    /// Appends a query parameter to every request, e.g. to select a node behind a
+    /// gateway multiplexing several of them. The path of `rpc` is kept as a prefix.
+    pub fn with_query_param(mut self, name: &str, value: &str) -> Self {
+        self.query_params
+            .push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Limits the (decompressed) size of the responses, defaults to [`DEFAULT_MAX_RESPONSE_SIZE`].
+    pub fn with_max_response_size(mut self, bytes: usize) -> Self {
+        self.max_response_size = bytes;
+        self
+    }
+
+    /// Limits the size of the descriptors of multiproof requests, defaults to
+    /// [`DEFAULT_MAX_DESCRIPTOR_SIZE`]. Multiproofs of more gindices are requested in
+    /// chunks under the limit, and merged.
+    pub fn with_max_descriptor_size(mut self, bytes: usize) -> Self {
+        self.max_descriptor_size = bytes;
+        self
+    }
+
+    /// Returns the statistics of the requests made by the provider (and its clones).
+    pub fn stats(&self) -> Stats {
+        self.stats.snapshot()
+    }
+
+    /// Returns the recorder of the provider statistics, e.g. for caches wrapping
+    /// the provider to record their hits.
+    pub fn stats_recorder(&self) -> StatsRecorder {
+        self.stats.clone()
+    }
+
+    /// Splits sorted gindices into chunks whose proof descriptors are under the limit,
+    /// keeping the gindices of each chunk close to share their witnesses.
+    fn descriptor_chunks(&self, gindices: &[usize]) -> Result<Vec<Vec<usize>>, ProofProviderError> {
+        let descriptor_size = |gindices: &[usize]| {
+            compute_proof_descriptor(gindices)
+                .map(|descriptor| descriptor.len())
+                .map_err(|err| {
+                    ProofProviderError::InputError(format!(
+                        "Failed to compute proof descriptor: {}",
+                        err
+                    ))
+                })
+        };
+        if descriptor_size(gindices)? <= self.max_descriptor_size {
+            return Ok(vec![gindices.to_vec()]);
+        }
+
+        let mut chunks = vec![];
+        let mut rest = gindices;
+        while !rest.is_empty() {
+            // descriptors only grow with the gindices of a chunk, so search for the
+            // longest prefix under the limit (of at least one gindex)
+            let (mut fits, mut exceeds) = (1, rest.len() + 1);
+            while exceeds - fits > 1 {
+                let middle = (fits + exceeds) / 2;
+                if descriptor_size(&rest[..middle])? <= self.max_descriptor_size {
+                    fits = middle;
+                } else {
+                    exceeds = middle;
+                }
+            }
+            let (chunk, tail) = rest.split_at(fits);
+            chunks.push(chunk.to_vec());
+            rest = tail;
+        }
+        Ok(chunks)
+    }
+
+    /// Fetches a compact multiproof of the given gindices, returning its descriptor and nodes.
+    async fn get_compact_proof(
+        &self,
+        state_id: &StateId,
+        gindices: &[usize],
+    ) -> Result<(Vec<u8>, Vec<Node>), ProofProviderError> {
+        let descriptor = compute_proof_descriptor(gindices).map_err(|err| {
+            ProofProviderError::InputError(format!("Failed to compute proof descriptor: {}", err))
+        })?;
+        let format = hex::encode(&descriptor);
+
+        let versions = match self.api_version() {
+            Some(version) => vec![version],
+            None => vec![ProofApiVersion::V1, ProofApiVersion::V0],
+        };
+
+        for (i, version) in versions.iter().enumerate() {
+            // Example URL: https://lodestar-sepolia.chainsafe.io/eth/v0/beacon/proof/state/latest?format=...
+            let url = middleware::endpoint_url(
+                &self.rpc,
+                &format!("{}/beacon/proof/state/{}", version.path(), state_id),
+                &[("format", &format)],
+                &self.query_params,
+            )?;
+            let req_url = url.to_string();
+
+            let (content_type, compact_proof) = match self.get(url).await {
+                // the endpoint doesn't exist in this version, try the previous one
+                Err(ProofProviderError::NotSupportedError(_)) if i + 1 < versions.len() => continue,
+                // the endpoint exists, but not the state
+                Err(err @ ProofProviderError::NotFoundError(_)) => {
+                    let _ = self.api_version.set(*version);
+                    return Err(err);
+                }
+                result => result?,
+            };
+            let leaves = match content_type {
+                ContentType::Json => version.parse_leaves(&compact_proof)?,
+                ContentType::Ssz => parse_ssz_leaves(&compact_proof)?,
+                other => return Err(other.unexpected(&req_url)),
+            };
+            let _ = self.api_version.set(*version);
+
+            return Ok((descriptor, leaves));
+        }
+
+        unreachable!("at least one api version is tried")
+    }
+
+    async fn get(&self, url: Url) -> Result<(ContentType, Vec<u8>), ProofProviderError> {
+        let result = self.try_get(url).await;
+        self.stats.record(&result);
+        result
+    }
+
+    async fn try_get(&self, url: Url) -> Result<(ContentType, Vec<u8>), ProofProviderError> {
+        let req = url.to_string();
+        let response = middleware::send(&self.service, Request::new(Method::GET, url)).await?;
+        if let Some(err) = middleware::rate_limited(&response) {
+            return Err(err);
+        }
 
         if response.status() == reqwest::StatusCode::NOT_FOUND {
-            return Err(ProofProviderError::NotFoundError(req.into()));
+            let body = middleware::read_body(response, MAX_NOT_FOUND_BODY_SIZE)
+                .await
+                .unwrap_or_default();
+            if is_unknown_route(&body) {
+                return Err(ProofProviderError::NotSupportedError(req));
+            }
+            return Err(ProofProviderError::NotFoundError(req));
         }
 
-        let bytes = response
-            .bytes()
-            .await
-            .map_err(ProofProviderError::NetworkError)?;
+        let content_type = ContentType::of(&response);
+        // SSZ bodies are only proofs, whose size is known ahead of reading them
+        let limit = match content_type {
+            ContentType::Ssz => self.max_response_size.min(MAX_SSZ_PROOF_SIZE),
+            _ => self.max_response_size,
+        };
+        let bytes = middleware::read_body(response, limit).await?;
+
+        self.stats.record_bytes(bytes.len());
 
-        Ok(bytes.to_vec())
+        Ok((content_type, bytes))
     }
 }
 
-#[automock]
+#[cfg_attr(any(test, feature = "test-utils"), mockall::automock)]
 #[async_trait]
 impl ProofProvider for LodestarProvider {
     async fn get_state_proof(
         &self,
-        state_id: &str,
+        state_id: &StateId,
         gindex: u64,
     ) -> Result<BlockRootsProof, ProofProviderError> {
-        let descriptor = compute_proof_descriptor(&[gindex as usize]).map_err(|err| {
-            ProofProviderError::InputError(format!("Failed to compute proof descriptor: {}", err))
-        })?;
-        let format = hex::encode(&descriptor);
+        let (descriptor, nodes) = self.get_compact_proof(state_id, &[gindex as usize]).await?;
 
-        // Example URL: https://lodestar-sepolia.chainsafe.io/eth/v0/beacon/proof/state/latest?format=...
-        let req_url = format!(
-            "{}/eth/v0/beacon/proof/state/{}?format={}",
-            self.rpc, state_id, format,
-        );
+        Ok(BlockRootsProof::CompactProof { descriptor, nodes })
+    }
 
-        let response = self.get(&req_url).await;
+    async fn get_state_multiproof(
+        &self,
+        state_id: &StateId,
+        gindices: &[u64],
+    ) -> Result<BlockRootsProof, ProofProviderError> {
+        let mut sorted = gindices.iter().map(|g| *g as usize).collect::<Vec<_>>();
+        sorted.sort_unstable();
+        sorted.dedup();
+        let chunks = self.descriptor_chunks(&sorted)?;
+        if let [chunk] = chunks.as_slice() {
+            let (descriptor, nodes) = self.get_compact_proof(state_id, chunk).await?;
+            return Ok(BlockRootsProof::CompactProof { descriptor, nodes });
+        }
 
-        match response {
-            Ok(compact_proof) => {
-                let proof_response: ProofResponse = serde_json::from_slice(&compact_proof)
-                    .map_err(|_| ProofProviderError::InvalidProofError())?;
+        let proofs = try_join_all(chunks.iter().map(|chunk| async move {
+            let (descriptor, nodes) = self.get_compact_proof(state_id, chunk).await?;
+            Ok::<_, ProofProviderError>(BlockRootsProof::CompactProof { descriptor, nodes })
+        }))
+        .await?;
+        merge_proofs(&proofs, gindices).map_err(|_| ProofProviderError::InvalidProofError())
+    }
 
-                Ok(BlockRootsProof::CompactProof {
-                    descriptor,
-                    nodes: proof_response.data.leaves,
-                })
+    async fn get_block_roots(&self, state_id: &StateId) -> Result<Vec<Node>, ProofProviderError> {
+        // request a multiproof of all the block_roots leaves, split under the
+        // descriptor size limit, and keep only the leaves
+        let gindices = (0..SLOTS_PER_HISTORICAL_ROOT)
+            .map(|index| {
+                let path = &["block_roots".into(), PathElement::Index(index)];
+                BeaconState::generalized_index(path).unwrap() as u64
+            })
+            .collect::<Vec<_>>();
+        let proof = self.get_state_multiproof(state_id, &gindices).await?;
+        // only a state root can be checked, other state ids are resolved by the node
+        if let StateId::Root(root) = state_id {
+            proof
+                .verify(FixedBytes(root.0))
+                .map_err(|_| ProofProviderError::InvalidProofError())?;
+        }
+        let BlockRootsProof::CompactProof { descriptor, nodes } = proof else {
+            return Err(ProofProviderError::InvalidProofError());
+        };
+
+        let proof_gindices = descriptor_gindices(&descriptor)
+            .map_err(|_| ProofProviderError::InvalidProofError())?;
+        if proof_gindices.len() != nodes.len() {
+            return Err(ProofProviderError::InvalidProofError());
+        }
+
+        let first = gindices[0];
+        let last = gindices[gindices.len() - 1];
+        let block_roots = proof_gindices
+            .iter()
+            .zip(nodes)
+            .filter(|(gindex, _)| (first..=last).contains(*gindex))
+            .map(|(_, node)| node)
+            .collect::<Vec<_>>();
+        if block_roots.len() != SLOTS_PER_HISTORICAL_ROOT {
+            return Err(ProofProviderError::InvalidProofError());
+        }
+
+        Ok(block_roots)
+    }
+
+    async fn get_block_root(&self, slot: u64) -> Result<Node, ProofProviderError> {
+        let url = middleware::endpoint_url(
+            &self.rpc,
+            &format!("eth/v1/beacon/blocks/{}/root", slot),
+            &[],
+            &self.query_params,
+        )?;
+        let req = url.to_string();
+        let (content_type, bytes) = self.get(url).await?;
+        if content_type != ContentType::Json {
+            return Err(content_type.unexpected(&req));
+        }
+
+        let response: BlockRootResponse = serde_json::from_slice(&bytes)?;
+        Ok(response.data.root)
+    }
+
+    fn info(&self) -> ProviderInfo {
+        ProviderInfo {
+            backend: "lodestar".to_string(),
+            url: Some(self.rpc.clone()),
+        }
+    }
+
+    async fn is_block_root(&self, root: &Node) -> Result<bool, ProofProviderError> {
+        let url = middleware::endpoint_url(
+            &self.rpc,
+            &format!("eth/v1/beacon/headers/{}", StateId::Root(*root)),
+            &[],
+            &self.query_params,
+        )?;
+
+        let req = url.to_string();
+
+        // only the status matters, but errors of the node must not pass for a known block
+        let result = async {
+            let response = middleware::send(&self.service, Request::new(Method::GET, url)).await?;
+            if let Some(err) = middleware::rate_limited(&response) {
+                return Err(err);
+            }
+            match response.status() {
+                reqwest::StatusCode::NOT_FOUND => Ok(false),
+                status if status.is_success() => Ok(true),
+                status => Err(ProofProviderError::BackendError {
+                    url: req,
+                    status: status.as_u16(),
+                    message: status.to_string(),
+                }),
             }
-            Err(e) => Err(e),
         }
+        .await;
+        self.stats.record(&result);
+        result
+    }
+
+    async fn get_block_header(
+        &self,
+        block_root: &Node,
+    ) -> Result<BeaconBlockHeader, ProofProviderError> {
+        let url = middleware::endpoint_url(
+            &self.rpc,
+            &format!("eth/v1/beacon/headers/{}", StateId::Root(*block_root)),
+            &[],
+            &self.query_params,
+        )?;
+        let req = url.to_string();
+        let (content_type, bytes) = self.get(url).await?;
+        if content_type != ContentType::Json {
+            return Err(content_type.unexpected(&req));
+        }
+
+        let response: HeaderResponse = serde_json::from_slice(&bytes)?;
+        Ok(response.data.header.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::U256;
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+    use serde_json::json;
+
+    /// Responds like Lodestar to requests for routes it doesn't know.
+    fn unknown_route() -> impl Responder {
+        status_code(404).body(
+            json!({
+                "message": "Route GET:/eth/v1/beacon/proof/state/head not found",
+                "error": "Not Found",
+                "statusCode": 404
+            })
+            .to_string(),
+        )
+    }
+
+    fn node(value: u64) -> Node {
+        Node::from(U256::from(value))
+    }
+
+    /// Tree of the `block_roots` vector served by [`serve_block_roots`], whose
+    /// leaves are labelled with their own gindex.
+    fn block_roots_tree() -> crate::merkle::BlockRootsTree {
+        crate::merkle::BlockRootsTree::new(
+            &(0..SLOTS_PER_HISTORICAL_ROOT as u64)
+                .map(|index| node(303_104 + index))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Root of the state served by [`serve_block_roots`], where every node outside
+    /// of the `block_roots` vector is labelled with its own gindex.
+    fn block_roots_state_root() -> Node {
+        use crate::merkle::hash_pair;
+        use crate::verification::BLOCK_ROOTS_FIELD_GINDEX;
+
+        let mut gindex = BLOCK_ROOTS_FIELD_GINDEX;
+        let mut root = block_roots_tree().root();
+        while gindex > 1 {
+            root = if gindex % 2 == 0 {
+                hash_pair(&root, &node(gindex + 1))
+            } else {
+                hash_pair(&node(gindex - 1), &root)
+            };
+            gindex /= 2;
+        }
+        root
+    }
+
+    /// Serves the multiproofs of all the `block_roots` leaves of the state at `state_path`.
+    fn serve_block_roots(server: &Server, provider: &LodestarProvider, state_path: &str) {
+        use crate::verification::BLOCK_ROOTS_FIELD_GINDEX;
+
+        let tree = block_roots_tree();
+        let state_node =
+            |gindex: u64| match gindex.ilog2().checked_sub(BLOCK_ROOTS_FIELD_GINDEX.ilog2()) {
+                Some(depth) if gindex >> depth == BLOCK_ROOTS_FIELD_GINDEX => tree
+                    .node(gindex - (BLOCK_ROOTS_FIELD_GINDEX << depth) + (1 << depth))
+                    .unwrap(),
+                _ => node(gindex),
+            };
+
+        // the descriptor of all the leaves is over the default size limit
+        let gindices = (0..SLOTS_PER_HISTORICAL_ROOT)
+            .map(|index| 303_104 + index)
+            .collect::<Vec<_>>();
+        let chunks = provider.descriptor_chunks(&gindices).unwrap();
+        assert!(chunks.len() > 1);
+        for chunk in chunks {
+            let descriptor = compute_proof_descriptor(&chunk).unwrap();
+            assert!(descriptor.len() <= DEFAULT_MAX_DESCRIPTOR_SIZE);
+            let leaves = descriptor_gindices(&descriptor)
+                .unwrap()
+                .into_iter()
+                .map(state_node)
+                .collect::<Vec<_>>();
+            server.expect(
+                Expectation::matching(all_of![
+                    request::path(format!("/eth/v0/beacon/proof/state/{}", state_path)),
+                    request::query(url_decoded(contains(("format", hex::encode(&descriptor))))),
+                ])
+                .times(1)
+                .respond_with(json_encoded(json!({
+                    "data": { "leaves": leaves, "descriptor": hex::encode(&descriptor) },
+                    "version": "capella"
+                }))),
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn it_should_return_the_block_roots_vector() {
+        let server = Server::run();
+        let provider = LodestarProvider::new(server.url_str("").trim_end_matches('/').to_string())
+            .with_api_version(ProofApiVersion::V0);
+        serve_block_roots(&server, &provider, "7864320");
+
+        let block_roots = provider
+            .get_block_roots(&StateId::Slot(7_864_320))
+            .await
+            .unwrap();
+
+        assert_eq!(block_roots.len(), SLOTS_PER_HISTORICAL_ROOT);
+        assert_eq!(block_roots[0], node(303_104));
+        assert_eq!(block_roots[8191], node(311_295));
+    }
+
+    #[tokio::test]
+    async fn it_should_verify_the_block_roots_of_a_state_root() {
+        let server = Server::run();
+        let provider = LodestarProvider::new(server.url_str("").trim_end_matches('/').to_string())
+            .with_api_version(ProofApiVersion::V0);
+        let (state_root, other_root) = (block_roots_state_root(), Node::repeat_byte(1));
+        serve_block_roots(&server, &provider, &StateId::Root(state_root).to_string());
+        serve_block_roots(&server, &provider, &StateId::Root(other_root).to_string());
+
+        let block_roots = provider
+            .get_block_roots(&StateId::Root(state_root))
+            .await
+            .unwrap();
+        assert_eq!(block_roots.len(), SLOTS_PER_HISTORICAL_ROOT);
+
+        // the same leaves are served for another state
+        let result = provider.get_block_roots(&StateId::Root(other_root)).await;
+        assert!(matches!(
+            result,
+            Err(ProofProviderError::InvalidProofError())
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_fall_back_to_older_proof_endpoints() {
+        let server = Server::run();
+        let provider = LodestarProvider::new(server.url_str("").trim_end_matches('/').to_string());
+        let response = json!({
+            "data": { "leaves": [node(1)], "descriptor": "80" },
+            "version": "capella"
+        });
+
+        server.expect(
+            Expectation::matching(request::path(matches("^/eth/v1/")))
+                .times(1)
+                .respond_with(unknown_route()),
+        );
+        server.expect(
+            Expectation::matching(request::path(matches("^/eth/v0/")))
+                .times(2)
+                .respond_with(json_encoded(response)),
+        );
+
+        for _ in 0..2 {
+            let proof = provider.get_state_proof(&StateId::Head, 1).await.unwrap();
+            assert!(
+                matches!(proof, BlockRootsProof::CompactProof { nodes, .. } if nodes == vec![node(1)])
+            );
+        }
+        assert_eq!(provider.api_version(), Some(ProofApiVersion::V0));
+    }
+
+    #[tokio::test]
+    async fn it_should_not_fall_back_for_missing_states() {
+        let server = Server::run();
+        let provider = LodestarProvider::new(server.url_str("").trim_end_matches('/').to_string());
+
+        server.expect(
+            Expectation::matching(request::path(matches("^/eth/v1/")))
+                .times(1)
+                .respond_with(
+                    status_code(404).body(
+                        json!({
+                            "statusCode": 404,
+                            "error": "Not Found",
+                            "message": "No state found for id '7864320'"
+                        })
+                        .to_string(),
+                    ),
+                ),
+        );
+        server.expect(Expectation::matching(request::path(matches("^/eth/v0/"))).times(0));
+
+        assert!(matches!(
+            provider.get_state_proof(&StateId::Slot(7_864_320), 1).await,
+            Err(ProofProviderError::NotFoundError(_))
+        ));
+        assert_eq!(provider.api_version(), Some(ProofApiVersion::V1));
+    }
+
+    #[tokio::test]
+    async fn it_should_split_multiproofs_with_large_descriptors() {
+        use crate::merkle::BlockRootsTree;
+        use crate::provider::Verify;
+
+        let server = Server::run();
+        let provider = LodestarProvider::new(server.url_str("").trim_end_matches('/').to_string())
+            .with_api_version(ProofApiVersion::V1)
+            .with_max_descriptor_size(2);
+        let tree = BlockRootsTree::new(&(0..16).map(node).collect::<Vec<_>>());
+
+        // the proofs of 16 and 18 share a 2 bytes descriptor, the one of 29 needs its own
+        for chunk in [&[16usize, 18][..], &[29]] {
+            let descriptor = compute_proof_descriptor(chunk).unwrap();
+            let leaves = descriptor_gindices(&descriptor)
+                .unwrap()
+                .into_iter()
+                .map(|gindex| tree.node(gindex).unwrap())
+                .collect::<Vec<_>>();
+            server.expect(
+                Expectation::matching(request::query(url_decoded(contains((
+                    "format",
+                    hex::encode(&descriptor),
+                )))))
+                .times(1)
+                .respond_with(json_encoded(json!({
+                    "data": { "leaves": leaves, "descriptor": hex::encode(&descriptor) }
+                }))),
+            );
+        }
+
+        let proof = provider
+            .get_state_multiproof(&StateId::Head, &[29, 16, 18])
+            .await
+            .unwrap();
+
+        // merged into the proof that a single request would have returned
+        let descriptor = compute_proof_descriptor(&[16, 18, 29]).unwrap();
+        assert!(
+            matches!(&proof, BlockRootsProof::CompactProof { descriptor: merged, .. } if *merged == descriptor)
+        );
+        assert_eq!(proof.verify(FixedBytes(tree.root().0)), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn it_should_use_newer_proof_endpoints_when_available() {
+        let server = Server::run();
+        let provider = LodestarProvider::new(server.url_str("").trim_end_matches('/').to_string());
+
+        // v1 responses may omit the version and include other metadata
+        server.expect(
+            Expectation::matching(request::path("/eth/v1/beacon/proof/state/head")).respond_with(
+                json_encoded(json!({
+                    "execution_optimistic": false,
+                    "data": { "leaves": [node(1)], "descriptor": "80" }
+                })),
+            ),
+        );
+
+        provider.get_state_proof(&StateId::Head, 1).await.unwrap();
+        assert_eq!(provider.api_version(), Some(ProofApiVersion::V1));
+    }
+
+    #[tokio::test]
+    async fn it_should_parse_proofs_by_content_type() {
+        let server = Server::run();
+        let provider = LodestarProvider::new(server.url_str("").trim_end_matches('/').to_string())
+            .with_api_version(ProofApiVersion::V0);
+
+        server.expect(
+            Expectation::matching(request::path("/eth/v0/beacon/proof/state/head")).respond_with(
+                status_code(200)
+                    .insert_header("Content-Type", "application/octet-stream")
+                    .body([node(1).0, node(2).0].concat()),
+            ),
+        );
+        server.expect(
+            Expectation::matching(request::path("/eth/v0/beacon/proof/state/finalized"))
+                .respond_with(
+                    status_code(502)
+                        .insert_header("Content-Type", "text/html; charset=utf-8")
+                        .body("<html>Bad Gateway</html>"),
+                ),
+        );
+
+        let proof = provider.get_state_proof(&StateId::Head, 1).await.unwrap();
+        assert!(
+            matches!(proof, BlockRootsProof::CompactProof { nodes, .. } if nodes == vec![node(1), node(2)])
+        );
+
+        let result = provider.get_state_proof(&StateId::Finalized, 1).await;
+        assert!(matches!(
+            result,
+            Err(ProofProviderError::UnexpectedContentType { content_type, .. })
+                if content_type == "text/html; charset=utf-8"
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_reject_ssz_proofs_over_the_node_limit() {
+        let server = Server::run();
+        let provider = LodestarProvider::new(server.url_str("").trim_end_matches('/').to_string())
+            .with_api_version(ProofApiVersion::V0);
+
+        server.expect(
+            Expectation::matching(request::path("/eth/v0/beacon/proof/state/head")).respond_with(
+                status_code(200)
+                    .insert_header("Content-Type", "application/octet-stream")
+                    .body(vec![0u8; MAX_SSZ_PROOF_SIZE + 32]),
+            ),
+        );
+
+        let result = provider.get_state_proof(&StateId::Head, 1).await;
+        assert!(matches!(
+            result,
+            Err(ProofProviderError::ResponseTooLarge { limit, .. }) if limit == MAX_SSZ_PROOF_SIZE
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_fetch_canonical_block_roots() {
+        let server = Server::run();
+        let provider = LodestarProvider::new(server.url_str("").trim_end_matches('/').to_string());
+
+        server.expect(
+            Expectation::matching(request::path("/eth/v1/beacon/blocks/7879316/root"))
+                .respond_with(json_encoded(json!({
+                    "execution_optimistic": false,
+                    "finalized": true,
+                    "data": { "root": node(42) }
+                }))),
+        );
+
+        assert_eq!(provider.get_block_root(7_879_316).await.unwrap(), node(42));
+    }
+
+    #[tokio::test]
+    async fn it_should_keep_the_base_path_and_query_params_of_gateways() {
+        let server = Server::run();
+        let provider = LodestarProvider::new(server.url_str("/lodestar/"))
+            .with_query_param("node", "mainnet-2");
+
+        server.expect(
+            Expectation::matching(all_of![
+                request::path("/lodestar/eth/v1/beacon/blocks/7879316/root"),
+                request::query(url_decoded(contains(("node", "mainnet-2")))),
+            ])
+            .respond_with(json_encoded(json!({ "data": { "root": node(42) } }))),
+        );
+
+        assert_eq!(provider.get_block_root(7_879_316).await.unwrap(), node(42));
+    }
+
+    #[tokio::test]
+    async fn it_should_look_up_block_headers_by_root() {
+        let server = Server::run();
+        let provider = LodestarProvider::new(server.url_str("").trim_end_matches('/').to_string());
+        let (block_root, state_root) = (Node::repeat_byte(1), Node::repeat_byte(2));
+
+        server.expect(
+            Expectation::matching(request::path(format!(
+                "/eth/v1/beacon/headers/{}",
+                StateId::Root(block_root)
+            )))
+            .respond_with(json_encoded(json!({ "data": { "root": block_root } }))),
+        );
+        server.expect(
+            Expectation::matching(request::path(format!(
+                "/eth/v1/beacon/headers/{}",
+                StateId::Root(state_root)
+            )))
+            .respond_with(status_code(404)),
+        );
+        server.expect(
+            Expectation::matching(request::path(format!(
+                "/eth/v1/beacon/headers/{}",
+                StateId::Root(Node::repeat_byte(3))
+            )))
+            .respond_with(
+                status_code(500)
+                    .insert_header("Content-Type", "text/html")
+                    .body("<html>Internal Server Error</html>"),
+            ),
+        );
+
+        assert!(provider.is_block_root(&block_root).await.unwrap());
+        assert!(!provider.is_block_root(&state_root).await.unwrap());
+        assert!(matches!(
+            provider.is_block_root(&Node::repeat_byte(3)).await,
+            Err(ProofProviderError::BackendError { status: 500, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_return_block_headers() {
+        let server = Server::run();
+        let provider = LodestarProvider::new(server.url_str("").trim_end_matches('/').to_string());
+        let block_root = Node::repeat_byte(1);
+        let header = json!({
+            "slot": "7879323",
+            "proposer_index": "70173",
+            "parent_root": Node::repeat_byte(2),
+            "state_root": Node::repeat_byte(3),
+            "body_root": Node::repeat_byte(4),
+        });
+
+        server.expect(
+            Expectation::matching(request::path(format!(
+                "/eth/v1/beacon/headers/{}",
+                StateId::Root(block_root)
+            )))
+            .respond_with(json_encoded(json!({
+                "data": {
+                    "root": block_root,
+                    "canonical": true,
+                    "header": { "message": header, "signature": format!("0x{}", "00".repeat(96)) }
+                }
+            }))),
+        );
+
+        let header = provider.get_block_header(&block_root).await.unwrap();
+        assert_eq!(header.slot, 7_879_323);
+        assert_eq!(header.state_root, Node::repeat_byte(3));
     }
 }
@@ -0,0 +1,212 @@
+//! Low-level merkleization of `block_roots` vectors, for building custom composed proofs.
+//!
+//! A [`BlockRootsTree`] is the subtree of a `block_roots` vector (or of the vector
+//! summarized by a `block_summary_root`), and its leaf proofs are relative to its root.
+//! They can be extended to the beacon state root by concatenating them with a proof
+//! of the vector itself, e.g.
+//!
+//! ```ignore
+//! let tree = build_block_roots_tree(&block_roots);
+//! let proof = tree.prove_leaf(6592);
+//! assert!(proof.verify(tree.root()).is_ok());
+//! ```
+
+use crate::errors::TreeError;
+use crate::provider::BlockRootsProof;
+use ethereum_consensus::capella::presets::mainnet::SLOTS_PER_HISTORICAL_ROOT;
+use ethereum_consensus::ssz::prelude::Node;
+use sha2::{Digest, Sha256};
+
+pub(crate) fn hash_pair(left: &Node, right: &Node) -> Node {
+    let mut hasher = Sha256::new();
+    hasher.update(left.as_slice());
+    hasher.update(right.as_slice());
+    Node::from_slice(&hasher.finalize())
+}
+
+/// Fully merkleized tree of a `block_roots` vector.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockRootsTree {
+    // levels[0] holds the leaves, the last level holds the root
+    levels: Vec<Vec<Node>>,
+}
+
+impl BlockRootsTree {
+    /// Builds the tree of a vector of block roots, whose length must be a power of two.
+    ///
+    /// Panics otherwise, see [`BlockRootsTree::try_new`] for untrusted inputs.
+    pub fn new(leaves: &[Node]) -> Self {
+        Self::try_new(leaves).expect("block roots length must be a power of two")
+    }
+
+    /// Like [`BlockRootsTree::new`], failing with [`TreeError::InvalidLeafCount`]
+    /// if the length of `leaves` is not a power of two.
+    pub fn try_new(leaves: &[Node]) -> Result<Self, TreeError> {
+        if !leaves.len().is_power_of_two() {
+            return Err(TreeError::InvalidLeafCount(leaves.len()));
+        }
+
+        let mut levels = vec![leaves.to_vec()];
+        while levels[levels.len() - 1].len() > 1 {
+            let level = levels[levels.len() - 1]
+                .chunks(2)
+                .map(|pair| hash_pair(&pair[0], &pair[1]))
+                .collect();
+            levels.push(level);
+        }
+
+        Ok(Self { levels })
+    }
+
+    /// Returns the root of the tree.
+    pub fn root(&self) -> Node {
+        self.levels[self.levels.len() - 1][0]
+    }
+
+    pub fn leaves(&self) -> &[Node] {
+        &self.levels[0]
+    }
+
+    /// Returns the node at `gindex`, relative to the root of the tree.
+    pub fn node(&self, gindex: u64) -> Option<Node> {
+        if gindex == 0 {
+            return None;
+        }
+        let depth = (63 - gindex.leading_zeros()) as usize;
+        let level = self.levels.len().checked_sub(depth + 1)?;
+
+        self.levels[level]
+            .get((gindex - (1 << depth)) as usize)
+            .copied()
+    }
+
+    /// Returns the proof of the leaf at `index`, relative to the root of the tree,
+    /// i.e. with a gindex of `leaves.len() + index`.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn prove_leaf(&self, index: usize) -> BlockRootsProof {
+        let leaves = self.leaves();
+        let mut witnesses = Vec::with_capacity(self.levels.len() - 1);
+        let mut position = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            witnesses.push(level[position ^ 1]);
+            position /= 2;
+        }
+
+        BlockRootsProof::SingleProof {
+            gindex: (leaves.len() + index) as u64,
+            witnesses,
+            leaf: leaves[index],
+        }
+    }
+}
+
+/// Builds the tree of a complete `block_roots` vector, whose root is the
+/// `hash_tree_root` of the vector (i.e. a `block_summary_root`).
+pub fn build_block_roots_tree(block_roots: &[Node; SLOTS_PER_HISTORICAL_ROOT]) -> BlockRootsTree {
+    BlockRootsTree::new(block_roots)
+}
+
+/// Builds many period trees at once in a dedicated rayon thread pool, e.g. when
+/// backfilling deep history.
+#[cfg(feature = "rayon")]
+#[derive(Debug, Clone, Default)]
+pub struct ParallelTreeBuilder {
+    threads: Option<usize>,
+}
+
+#[cfg(feature = "rayon")]
+impl ParallelTreeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the number of threads used, defaults to the number of CPUs.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Builds the tree of each vector of block roots, preserving their order.
+    ///
+    /// Fails with [`TreeError::InvalidLeafCount`] if the length of a vector is not
+    /// a power of two.
+    pub fn build(&self, periods: &[Vec<Node>]) -> Result<Vec<BlockRootsTree>, TreeError> {
+        use rayon::prelude::*;
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads.unwrap_or(0))
+            .build()?;
+
+        pool.install(|| {
+            periods
+                .par_iter()
+                .map(|block_roots| BlockRootsTree::try_new(block_roots))
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::Verify;
+
+    #[test]
+    fn it_should_produce_verifiable_leaf_proofs() {
+        let leaves = (0..8192u16)
+            .map(|i| Node::left_padding_from(&i.to_be_bytes()))
+            .collect::<Vec<_>>();
+        let tree = build_block_roots_tree(leaves.as_slice().try_into().unwrap());
+
+        let proof = tree.prove_leaf(6592);
+        match &proof {
+            BlockRootsProof::SingleProof {
+                gindex,
+                witnesses,
+                leaf,
+            } => {
+                assert_eq!(*gindex, 8192 + 6592);
+                assert_eq!(witnesses.len(), 13);
+                assert_eq!(*leaf, leaves[6592]);
+            }
+            _ => panic!("Invalid proof type"),
+        }
+        assert!(proof.verify(tree.root()).is_ok());
+    }
+
+    #[test]
+    fn it_should_reject_leaf_counts_not_powers_of_two() {
+        for count in [0, 3, 8191] {
+            assert!(matches!(
+                BlockRootsTree::try_new(&vec![Node::default(); count]),
+                Err(TreeError::InvalidLeafCount(actual)) if actual == count
+            ));
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn it_should_build_trees_in_parallel() {
+        let periods = (0..4u8)
+            .map(|i| vec![Node::repeat_byte(i); 8192])
+            .collect::<Vec<_>>();
+
+        let trees = ParallelTreeBuilder::new()
+            .threads(2)
+            .build(&periods)
+            .unwrap();
+
+        assert_eq!(trees.len(), 4);
+        for (tree, block_roots) in trees.iter().zip(&periods) {
+            assert_eq!(tree, &BlockRootsTree::new(block_roots));
+        }
+
+        let mut periods = periods;
+        periods[2].pop();
+        assert!(matches!(
+            ParallelTreeBuilder::new().threads(2).build(&periods),
+            Err(TreeError::InvalidLeafCount(8191))
+        ));
+    }
+}
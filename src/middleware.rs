@@ -0,0 +1,622 @@
+use crate::client::shared_client;
+use crate::errors::ProofProviderError;
+use futures::future::BoxFuture;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::{Client, Request, Response, StatusCode, Url};
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
+use tower::util::BoxCloneSyncService;
+use tower::{Layer, Service, ServiceExt};
+
+/// The `tower::Service` stack that HTTP providers send their requests through.
+pub type HttpService = BoxCloneSyncService<Request, Response, ProofProviderError>;
+
+/// Innermost service of the stack, executing requests with a `reqwest` client.
+/// The default service uses the crate-level shared client.
+#[derive(Clone)]
+pub struct ReqwestService {
+    client: Client,
+}
+
+impl ReqwestService {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+impl Default for ReqwestService {
+    fn default() -> Self {
+        Self::new(shared_client())
+    }
+}
+
+impl Service<Request> for ReqwestService {
+    type Response = Response;
+    type Error = ProofProviderError;
+    type Future = BoxFuture<'static, Result<Response, ProofProviderError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let client = self.client.clone();
+        let _ = REQUEST_TALLY.try_with(|tally| tally.attempts.fetch_add(1, Ordering::Relaxed));
+        Box::pin(async move {
+            let url = req.url().to_string();
+            let start = Instant::now();
+            client.execute(req).await.map_err(|err| {
+                if err.is_timeout() {
+                    ProofProviderError::Timeout {
+                        url,
+                        elapsed: start.elapsed(),
+                    }
+                } else {
+                    ProofProviderError::NetworkError(err)
+                }
+            })
+        })
+    }
+}
+
+/// Layer that aborts requests taking longer than the configured duration with
+/// a `ProofProviderError::Timeout`.
+#[derive(Clone, Copy, Debug)]
+pub struct TimeoutLayer {
+    timeout: Duration,
+}
+
+impl TimeoutLayer {
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+}
+
+impl<S> Layer<S> for TimeoutLayer {
+    type Service = Timeout<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Timeout {
+            inner,
+            timeout: self.timeout,
+        }
+    }
+}
+
+/// Service produced by [`TimeoutLayer`].
+#[derive(Clone, Debug)]
+pub struct Timeout<S> {
+    inner: S,
+    timeout: Duration,
+}
+
+impl<S> Service<Request> for Timeout<S>
+where
+    S: Service<Request, Response = Response, Error = ProofProviderError>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = ProofProviderError;
+    type Future = BoxFuture<'static, Result<Response, ProofProviderError>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let url = req.url().to_string();
+        let timeout = self.timeout;
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let start = Instant::now();
+            match tokio::time::timeout(timeout, response).await {
+                Ok(result) => result,
+                Err(_) => Err(ProofProviderError::Timeout {
+                    url,
+                    elapsed: start.elapsed(),
+                }),
+            }
+        })
+    }
+}
+
+tokio::task_local! {
+    static TRACE_CONTEXT: TraceContext;
+    static REQUEST_TALLY: Arc<RequestTally>;
+}
+
+/// Counts the requests sent by the providers during an operation, and the attempts
+/// that reached the backend, which exceed the requests when a layer retries them.
+#[derive(Debug, Default)]
+pub(crate) struct RequestTally {
+    requests: AtomicU32,
+    attempts: AtomicU32,
+}
+
+impl RequestTally {
+    /// Runs `future` while counting the requests it sends.
+    pub(crate) async fn scope<F: Future>(self: &Arc<Self>, future: F) -> F::Output {
+        REQUEST_TALLY.scope(self.clone(), future).await
+    }
+
+    pub(crate) fn requests(&self) -> u32 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn retries(&self) -> u32 {
+        self.attempts
+            .load(Ordering::Relaxed)
+            .saturating_sub(self.requests())
+    }
+}
+
+/// Trace headers of the operation in progress (e.g. the incoming request of a
+/// service), propagated to the backends by [`TracePropagationLayer`].
+///
+/// The context is local to the task running [`TraceContext::scope`]. Futures
+/// spawned as other tasks keep it when wrapped with [`TraceContext::propagate`].
+#[derive(Clone, Debug, Default)]
+pub struct TraceContext {
+    headers: HeaderMap,
+}
+
+impl TraceContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Runs `future` with this trace context active.
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        TRACE_CONTEXT.scope(self, future).await
+    }
+
+    /// Returns the trace context active in the current task, if any.
+    pub fn current() -> Option<TraceContext> {
+        TRACE_CONTEXT.try_with(TraceContext::clone).ok()
+    }
+
+    /// Wraps `future` with the trace context active where it's created, if any,
+    /// e.g. before handing it to `tokio::spawn`.
+    pub fn propagate<F: Future>(future: F) -> impl Future<Output = F::Output> {
+        let context = TraceContext::current();
+        async move {
+            match context {
+                Some(context) => context.scope(future).await,
+                None => future.await,
+            }
+        }
+    }
+}
+
+/// Layer that copies the configured headers of the active [`TraceContext`], if
+/// any, to the requests, so that proof fetches can be correlated with the
+/// operation that triggered them.
+#[derive(Clone, Debug)]
+pub struct TracePropagationLayer {
+    headers: Vec<HeaderName>,
+}
+
+impl TracePropagationLayer {
+    pub fn new(headers: impl IntoIterator<Item = HeaderName>) -> Self {
+        Self {
+            headers: headers.into_iter().collect(),
+        }
+    }
+}
+
+impl Default for TracePropagationLayer {
+    /// Propagates the W3C trace context and `X-Request-Id` headers.
+    fn default() -> Self {
+        Self::new([
+            HeaderName::from_static("traceparent"),
+            HeaderName::from_static("tracestate"),
+            HeaderName::from_static("x-request-id"),
+        ])
+    }
+}
+
+impl<S> Layer<S> for TracePropagationLayer {
+    type Service = TracePropagation<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TracePropagation {
+            inner,
+            headers: self.headers.clone(),
+        }
+    }
+}
+
+/// Service produced by [`TracePropagationLayer`].
+#[derive(Clone, Debug)]
+pub struct TracePropagation<S> {
+    inner: S,
+    headers: Vec<HeaderName>,
+}
+
+impl<S> Service<Request> for TracePropagation<S>
+where
+    S: Service<Request, Response = Response, Error = ProofProviderError>,
+{
+    type Response = Response;
+    type Error = ProofProviderError;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request) -> Self::Future {
+        if let Some(context) = TraceContext::current() {
+            for name in &self.headers {
+                if let Some(value) = context.headers.get(name) {
+                    req.headers_mut().insert(name.clone(), value.clone());
+                }
+            }
+        }
+
+        self.inner.call(req)
+    }
+}
+
+/// Boxes a service so that it can be used as the stack of an HTTP provider.
+pub fn boxed<S>(service: S) -> HttpService
+where
+    S: Service<Request, Response = Response, Error = ProofProviderError>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    S::Future: Send + 'static,
+{
+    BoxCloneSyncService::new(service)
+}
+
+/// Wraps an existing stack with an additional layer.
+pub(crate) fn wrap<L>(service: HttpService, layer: L) -> HttpService
+where
+    L: Layer<HttpService>,
+    L::Service: Service<Request, Response = Response, Error = ProofProviderError>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    <L::Service as Service<Request>>::Future: Send + 'static,
+{
+    boxed(layer.layer(service))
+}
+
+/// Sends a single request through the stack.
+pub(crate) async fn send(
+    service: &HttpService,
+    request: Request,
+) -> Result<Response, ProofProviderError> {
+    let _ = REQUEST_TALLY.try_with(|tally| tally.requests.fetch_add(1, Ordering::Relaxed));
+    service.clone().oneshot(request).await
+}
+
+/// Returns a [`ProofProviderError::RateLimited`] error for `429 Too Many Requests`
+/// and `503 Service Unavailable` responses, with the wait advised by their headers.
+pub(crate) fn rate_limited(response: &Response) -> Option<ProofProviderError> {
+    let status = response.status();
+    if status != StatusCode::TOO_MANY_REQUESTS && status != StatusCode::SERVICE_UNAVAILABLE {
+        return None;
+    }
+
+    Some(ProofProviderError::RateLimited {
+        url: response.url().to_string(),
+        status: status.as_u16(),
+        retry_after: advised_wait(response.headers()),
+    })
+}
+
+/// Parses the wait advised by a `Retry-After` header (delay in seconds or HTTP
+/// date), or else by a `RateLimit-Reset` header (delay in seconds).
+fn advised_wait(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name| Some(headers.get(name)?.to_str().ok()?.trim());
+
+    if let Some(value) = header("retry-after") {
+        if let Ok(seconds) = value.parse() {
+            return Some(Duration::from_secs(seconds));
+        }
+        let date = httpdate::parse_http_date(value).ok()?;
+        return Some(date.duration_since(SystemTime::now()).unwrap_or_default());
+    }
+    header("ratelimit-reset")?
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Builds the url of the endpoint at `path` of the API at `base`, keeping the path
+/// of `base` (e.g. of a reverse proxy) regardless of trailing slashes, and
+/// percent-encoding the segments of `path` and the `query` parameters, followed
+/// by the `extra_query` parameters configured on the provider.
+pub(crate) fn endpoint_url(
+    base: &str,
+    path: &str,
+    query: &[(&str, &str)],
+    extra_query: &[(String, String)],
+) -> Result<Url, ProofProviderError> {
+    let invalid = |reason: &dyn std::fmt::Display| {
+        ProofProviderError::InputError(format!("Invalid url {}: {}", base, reason))
+    };
+    let mut url = Url::parse(base).map_err(|err| invalid(&err))?;
+
+    url.path_segments_mut()
+        .map_err(|_| invalid(&"cannot be a base"))?
+        .pop_if_empty()
+        .extend(path.split('/').filter(|segment| !segment.is_empty()));
+    if !query.is_empty() || !extra_query.is_empty() {
+        url.query_pairs_mut()
+            .extend_pairs(query)
+            .extend_pairs(extra_query);
+    }
+
+    Ok(url)
+}
+
+/// Default limit of the (decompressed) size of the response bodies read by the providers.
+pub const DEFAULT_MAX_RESPONSE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Reads the body of a response, failing as soon as it exceeds `limit` bytes,
+/// so that compressed responses can't expand without bounds.
+pub(crate) async fn read_body(
+    mut response: Response,
+    limit: usize,
+) -> Result<Vec<u8>, ProofProviderError> {
+    let too_large = |response: &Response| ProofProviderError::ResponseTooLarge {
+        url: response.url().to_string(),
+        limit,
+    };
+    if response
+        .content_length()
+        .is_some_and(|length| length > limit as u64)
+    {
+        return Err(too_large(&response));
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(ProofProviderError::NetworkError)?
+    {
+        if body.len() + chunk.len() > limit {
+            return Err(too_large(&response));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(body)
+}
+
+/// Media type of a response body, from its `Content-Type` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ContentType {
+    Json,
+    Ssz,
+    /// Any other type, e.g. the HTML error pages of reverse proxies.
+    Other(String),
+}
+
+impl ContentType {
+    /// Responses without a `Content-Type` are assumed to be JSON.
+    pub(crate) fn of(response: &Response) -> Self {
+        let Some(value) = response.headers().get(CONTENT_TYPE) else {
+            return ContentType::Json;
+        };
+        let value = value.to_str().unwrap_or_default();
+        let media_type = value.split(';').next().unwrap_or_default().trim();
+
+        match media_type.to_ascii_lowercase().as_str() {
+            "application/json" => ContentType::Json,
+            "application/octet-stream" => ContentType::Ssz,
+            _ => ContentType::Other(value.to_string()),
+        }
+    }
+
+    /// Returns an error for bodies of a type the caller can't parse.
+    pub(crate) fn unexpected(self, url: &str) -> ProofProviderError {
+        let content_type = match self {
+            ContentType::Json => "application/json".to_string(),
+            ContentType::Ssz => "application/octet-stream".to_string(),
+            ContentType::Other(content_type) => content_type,
+        };
+
+        ProofProviderError::UnexpectedContentType {
+            url: url.to_string(),
+            content_type,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StateProverProvider;
+    use crate::{BlockRootsProof, ProofProvider, StateId};
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+    use tower::util::MapRequestLayer;
+
+    #[test]
+    fn it_should_parse_the_advised_wait() {
+        let headers = |name, value: &str| {
+            HeaderMap::from_iter([(HeaderName::from_static(name), value.parse().unwrap())])
+        };
+
+        assert_eq!(
+            advised_wait(&headers("retry-after", "120")),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            advised_wait(&headers("ratelimit-reset", "7")),
+            Some(Duration::from_secs(7))
+        );
+        let past = httpdate::fmt_http_date(SystemTime::UNIX_EPOCH);
+        assert_eq!(
+            advised_wait(&headers("retry-after", &past)),
+            Some(Duration::ZERO)
+        );
+        let later = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(60));
+        assert!(advised_wait(&headers("retry-after", &later)).unwrap() > Duration::from_secs(58));
+        assert_eq!(advised_wait(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn it_should_build_endpoint_urls() {
+        let url = |base| endpoint_url(base, "eth/v1/beacon/blocks/1/root", &[], &[]).unwrap();
+
+        for base in ["http://localhost:9596", "http://localhost:9596/"] {
+            assert_eq!(
+                url(base).as_str(),
+                "http://localhost:9596/eth/v1/beacon/blocks/1/root"
+            );
+        }
+        for base in [
+            "https://gateway.example/lodestar",
+            "https://gateway.example/lodestar/",
+        ] {
+            assert_eq!(
+                url(base).as_str(),
+                "https://gateway.example/lodestar/eth/v1/beacon/blocks/1/root"
+            );
+        }
+        assert_eq!(
+            url("http://[::1]:5052").as_str(),
+            "http://[::1]:5052/eth/v1/beacon/blocks/1/root"
+        );
+        assert_eq!(
+            url("http://10.0.0.1:5052").as_str(),
+            "http://10.0.0.1:5052/eth/v1/beacon/blocks/1/root"
+        );
+
+        let url = endpoint_url(
+            "http://localhost/?key=secret",
+            "state_proof",
+            &[("state_id", "head"), ("network", "a b&c")],
+            &[("node".to_string(), "2".to_string())],
+        )
+        .unwrap();
+        assert_eq!(
+            url.as_str(),
+            "http://localhost/state_proof?key=secret&state_id=head&network=a+b%26c&node=2"
+        );
+        assert!(matches!(
+            endpoint_url("localhost:5052", "eth", &[], &[]),
+            Err(ProofProviderError::InputError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_run_requests_through_custom_layers() {
+        let server = Server::run();
+        let url = server.url("");
+        let json_response = serde_json::to_string(&BlockRootsProof::default()).unwrap();
+
+        server.expect(
+            Expectation::matching(all_of![
+                request::headers(contains(("authorization", "Bearer token"))),
+                request::query(url_decoded(contains(("gindex", "1")))),
+            ])
+            .respond_with(status_code(200).body(json_response)),
+        );
+
+        let prover = StateProverProvider::new("mainnet".to_string(), url.to_string()).layer(
+            MapRequestLayer::new(|mut req: Request| {
+                req.headers_mut()
+                    .insert("authorization", HeaderValue::from_static("Bearer token"));
+                req
+            }),
+        );
+
+        let result = prover.get_state_proof(&StateId::Head, 1).await.unwrap();
+        assert_eq!(result, BlockRootsProof::default());
+    }
+
+    #[tokio::test]
+    async fn it_should_return_timeout_error_for_slow_backends() {
+        let server = Server::run();
+        let url = server.url("");
+        let json_response = serde_json::to_string(&BlockRootsProof::default()).unwrap();
+
+        server.expect(
+            Expectation::matching(request::query(url_decoded(contains(("gindex", "1")))))
+                .respond_with(delay_and_then(
+                    Duration::from_millis(500),
+                    status_code(200).body(json_response),
+                )),
+        );
+
+        let prover = StateProverProvider::new("mainnet".to_string(), url.to_string())
+            .layer(TimeoutLayer::new(Duration::from_millis(50)));
+
+        match prover.get_state_proof(&StateId::Head, 1).await {
+            Err(ProofProviderError::Timeout { url, elapsed }) => {
+                assert!(url.contains("gindex=1"));
+                assert!(elapsed >= Duration::from_millis(50));
+            }
+            other => panic!("Expected timeout error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_should_propagate_trace_headers() {
+        let server = Server::run();
+        let url = server.url("");
+        let json_response = serde_json::to_string(&BlockRootsProof::default()).unwrap();
+        let traceparent = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+
+        server.expect(
+            Expectation::matching(all_of![
+                request::headers(contains(("traceparent", traceparent))),
+                request::headers(contains(("x-request-id", "42"))),
+                request::headers(not(contains(key("authorization")))),
+            ])
+            .times(1)
+            .respond_with(status_code(200).body(json_response.clone())),
+        );
+        server.expect(
+            Expectation::matching(request::headers(not(contains(key("traceparent")))))
+                .times(1)
+                .respond_with(status_code(200).body(json_response)),
+        );
+
+        let prover = StateProverProvider::new("mainnet".to_string(), url.to_string())
+            .layer(TracePropagationLayer::default());
+        let context = TraceContext::new()
+            .with_header(
+                HeaderName::from_static("traceparent"),
+                HeaderValue::from_static(traceparent),
+            )
+            .with_header(
+                HeaderName::from_static("x-request-id"),
+                HeaderValue::from_static("42"),
+            )
+            .with_header(
+                HeaderName::from_static("authorization"),
+                HeaderValue::from_static("Bearer token"),
+            );
+
+        // the context follows the requests of spawned tasks
+        let spawned = prover.clone();
+        context
+            .scope(async move {
+                tokio::spawn(TraceContext::propagate(async move {
+                    spawned.get_state_proof(&StateId::Head, 1).await
+                }))
+                .await
+                .unwrap()
+            })
+            .await
+            .unwrap();
+        prover.get_state_proof(&StateId::Head, 1).await.unwrap();
+    }
+}
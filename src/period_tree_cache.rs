@@ -0,0 +1,327 @@
+#[cfg(feature = "providers")]
+use crate::errors::ProofProviderError;
+#[cfg(feature = "providers")]
+use crate::historical::fetch_period_block_roots;
+use crate::merkle::BlockRootsTree;
+#[cfg(feature = "providers")]
+use crate::provider::ProofProvider;
+use ethereum_consensus::ssz::prelude::Node;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+#[derive(Default)]
+struct Entries {
+    trees: HashMap<Node, Arc<BlockRootsTree>>,
+    // least recently used first
+    order: VecDeque<Node>,
+}
+
+/// Cache of merkleized historical period trees, keyed by their `block_summary_root`,
+/// so that proving many targets of the same period rebuilds its tree only once.
+///
+/// Trees are kept in memory up to `capacity` entries (evicting the least recently
+/// used), and optionally persisted as raw block roots in a directory.
+pub struct PeriodTreeCache {
+    capacity: usize,
+    dir: Option<PathBuf>,
+    entries: Mutex<Entries>,
+}
+
+impl PeriodTreeCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            dir: None,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// Also persists the trees in `dir`, so that they survive restarts.
+    pub fn with_dir(mut self, dir: PathBuf) -> Self {
+        self.dir = Some(dir);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().trees.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, summary_root: &Node) -> Option<Arc<BlockRootsTree>> {
+        {
+            let mut entries = self.entries.lock().unwrap();
+            if let Some(tree) = entries.trees.get(summary_root).cloned() {
+                entries.order.retain(|root| root != summary_root);
+                entries.order.push_back(*summary_root);
+                return Some(tree);
+            }
+        }
+
+        let tree = self.load(summary_root)?;
+        Some(self.insert_in_memory(tree))
+    }
+
+    pub fn insert(&self, tree: BlockRootsTree) -> Arc<BlockRootsTree> {
+        self.store(&tree);
+        self.insert_in_memory(tree)
+    }
+
+    fn insert_in_memory(&self, tree: BlockRootsTree) -> Arc<BlockRootsTree> {
+        let root = tree.root();
+        let tree = Arc::new(tree);
+        let mut entries = self.entries.lock().unwrap();
+
+        entries.order.retain(|r| r != &root);
+        entries.order.push_back(root);
+        entries.trees.insert(root, tree.clone());
+        while entries.trees.len() > self.capacity {
+            match entries.order.pop_front() {
+                Some(evicted) => entries.trees.remove(&evicted),
+                None => break,
+            };
+        }
+
+        tree
+    }
+
+    fn path(&self, summary_root: &Node) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.bin", hex::encode(summary_root))))
+    }
+
+    fn store(&self, tree: &BlockRootsTree) {
+        let Some(path) = self.path(&tree.root()) else {
+            return;
+        };
+        let bytes = tree
+            .leaves()
+            .iter()
+            .flat_map(|leaf| leaf.0)
+            .collect::<Vec<_>>();
+        let write = || {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, bytes)
+        };
+        // the disk cache is best effort, the tree is still cached in memory
+        if let Err(err) = write() {
+            tracing::warn!(path = %path.display(), %err, "Failed to persist period tree");
+        }
+    }
+
+    fn load(&self, summary_root: &Node) -> Option<BlockRootsTree> {
+        let bytes = fs::read(self.path(summary_root)?).ok()?;
+        // discard truncated and corrupted files
+        if bytes.len() % 32 != 0 {
+            return None;
+        }
+
+        let leaves = bytes.chunks(32).map(Node::from_slice).collect::<Vec<_>>();
+        let tree = BlockRootsTree::try_new(&leaves).ok()?;
+        (tree.root() == *summary_root).then_some(tree)
+    }
+
+    /// Writes the trees held in memory to a single file, to warm up the cache of
+    /// another environment with [`PeriodTreeCache::import`]. Returns the number of
+    /// exported trees.
+    ///
+    /// Each tree is written as its leaf count (little-endian `u32`) followed by its leaves.
+    pub fn export(&self, path: &Path) -> io::Result<usize> {
+        let trees = {
+            let entries = self.entries.lock().unwrap();
+            entries
+                .order
+                .iter()
+                .filter_map(|root| entries.trees.get(root).cloned())
+                .collect::<Vec<_>>()
+        };
+
+        let mut bytes = Vec::new();
+        for tree in &trees {
+            bytes.extend_from_slice(&(tree.leaves().len() as u32).to_le_bytes());
+            bytes.extend(tree.leaves().iter().flat_map(|leaf| leaf.0));
+        }
+        fs::write(path, bytes)?;
+
+        Ok(trees.len())
+    }
+
+    /// Inserts the trees of a file written by [`PeriodTreeCache::export`], in their
+    /// order of use, so that the most recently used ones are kept within `capacity`.
+    /// Returns the number of imported trees.
+    pub fn import(&self, path: &Path) -> io::Result<usize> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid period tree export");
+        let bytes = fs::read(path)?;
+
+        let mut trees = Vec::new();
+        let mut rest = bytes.as_slice();
+        while !rest.is_empty() {
+            let (count, tail) = rest.split_first_chunk::<4>().ok_or_else(invalid)?;
+            let count = u32::from_le_bytes(*count) as usize;
+            if !count.is_power_of_two() || tail.len() < count * 32 {
+                return Err(invalid());
+            }
+            let (leaves, tail) = tail.split_at(count * 32);
+            trees.push(BlockRootsTree::new(
+                &leaves.chunks(32).map(Node::from_slice).collect::<Vec<_>>(),
+            ));
+            rest = tail;
+        }
+
+        let imported = trees.len();
+        for tree in trees {
+            self.insert(tree);
+        }
+
+        Ok(imported)
+    }
+
+    /// Returns the tree of `period`, whose root must be `summary_root`, fetching
+    /// and merkleizing its block roots if it's not cached.
+    #[cfg(feature = "providers")]
+    pub async fn get_or_fetch<P: ProofProvider>(
+        &self,
+        provider: &P,
+        period: u64,
+        summary_root: &Node,
+    ) -> Result<Arc<BlockRootsTree>, ProofProviderError> {
+        if let Some(tree) = self.get(summary_root) {
+            return Ok(tree);
+        }
+
+        let block_roots = fetch_period_block_roots(provider, period).await?;
+        let tree = BlockRootsTree::new(&block_roots);
+        if tree.root() != *summary_root {
+            return Err(ProofProviderError::InvalidProofError());
+        }
+
+        Ok(self.insert(tree))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "providers")]
+    use crate::provider::MockProofProvider;
+
+    fn tree(seed: u8) -> BlockRootsTree {
+        BlockRootsTree::new(&vec![Node::repeat_byte(seed); 8192])
+    }
+
+    #[test]
+    fn it_should_evict_least_recently_used_trees() {
+        let cache = PeriodTreeCache::new(2);
+        let (first, second, third) = (tree(1), tree(2), tree(3));
+
+        cache.insert(first.clone());
+        cache.insert(second.clone());
+        assert!(cache.get(&first.root()).is_some());
+        cache.insert(third.clone());
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&first.root()).is_some());
+        assert!(cache.get(&second.root()).is_none());
+        assert!(cache.get(&third.root()).is_some());
+    }
+
+    #[test]
+    fn it_should_persist_trees_to_disk() {
+        let dir = std::env::temp_dir().join(format!("period_tree_cache_{}", std::process::id()));
+        let tree = tree(4);
+
+        PeriodTreeCache::new(1)
+            .with_dir(dir.clone())
+            .insert(tree.clone());
+        let cached = PeriodTreeCache::new(1)
+            .with_dir(dir.clone())
+            .get(&tree.root());
+        fs::remove_dir_all(dir).unwrap();
+
+        assert_eq!(cached.as_deref(), Some(&tree));
+    }
+
+    #[test]
+    fn it_should_discard_truncated_files() {
+        let dir =
+            std::env::temp_dir().join(format!("period_tree_truncated_{}", std::process::id()));
+        let tree = tree(10);
+        let cache = PeriodTreeCache::new(1).with_dir(dir.clone());
+        let path = cache.path(&tree.root()).unwrap();
+
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, vec![10; 8192 * 32 - 7]).unwrap();
+        let cached = cache.get(&tree.root());
+        fs::remove_dir_all(dir).unwrap();
+
+        assert!(cached.is_none());
+    }
+
+    #[test]
+    fn it_should_import_exported_trees() {
+        let path = std::env::temp_dir().join(format!("period_trees_{}.bin", std::process::id()));
+        let cache = PeriodTreeCache::new(2);
+        let (first, second) = (tree(8), tree(9));
+        cache.insert(first.clone());
+        cache.insert(second.clone());
+
+        assert_eq!(cache.export(&path).unwrap(), 2);
+        let imported = PeriodTreeCache::new(2);
+        assert_eq!(imported.import(&path).unwrap(), 2);
+        fs::write(&path, [1, 0, 0, 0, 0xff]).unwrap();
+        let truncated = imported.import(&path);
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(imported.get(&first.root()).as_deref(), Some(&first));
+        assert_eq!(imported.get(&second.root()).as_deref(), Some(&second));
+        assert_eq!(truncated.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "providers")]
+    #[tokio::test]
+    async fn it_should_fetch_block_roots_only_once() {
+        let expected = tree(5);
+        let mut provider = MockProofProvider::new();
+        provider
+            .expect_get_block_roots()
+            .times(1)
+            .returning(|_| Ok(vec![Node::repeat_byte(5); 8192]));
+        let cache = PeriodTreeCache::new(1);
+
+        for _ in 0..2 {
+            let tree = cache
+                .get_or_fetch(&provider, 959, &expected.root())
+                .await
+                .unwrap();
+            assert_eq!(tree.root(), expected.root());
+        }
+    }
+
+    #[cfg(feature = "providers")]
+    #[tokio::test]
+    async fn it_should_reject_block_roots_not_matching_the_summary_root() {
+        let mut provider = MockProofProvider::new();
+        provider
+            .expect_get_block_roots()
+            .returning(|_| Ok(vec![Node::repeat_byte(6); 8192]));
+        let cache = PeriodTreeCache::new(1);
+
+        let result = cache
+            .get_or_fetch(&provider, 959, &Node::repeat_byte(7))
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(ProofProviderError::InvalidProofError())
+        ));
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,283 @@
+//! Pipelined proving of large batches of targets, e.g. backfills, where fetching,
+//! verification and encoding run as separate stages connected by bounded channels,
+//! so that the CPU-bound stages don't hold back the requests to the provider.
+
+use crate::batch::BatchTargets;
+use crate::canonical::to_canonical_json;
+use crate::concurrency::AimdController;
+use crate::errors::{AncestryProverError, ProofProviderError};
+use crate::prover::AncestryProver;
+use crate::provider::{BlockRootsProof, ProofProvider};
+use crate::validation::parse_state_root;
+use crate::verification::verify_block_roots_proof_for_anchor;
+use ethereum_consensus::capella::presets::mainnet::SLOTS_PER_HISTORICAL_ROOT;
+use ethereum_consensus::ssz::prelude::Node;
+use futures::channel::mpsc;
+use futures::stream::FuturesUnordered;
+use futures::{SinkExt, StreamExt};
+use std::thread::available_parallelism;
+use std::time::Instant;
+
+/// Concurrency of the stages of [`AncestryProver::prove_pipelined`].
+#[derive(Debug, Clone)]
+pub struct PipelineOptions {
+    /// Requests in flight to the provider, or their maximum with adaptive concurrency.
+    pub fetch_concurrency: usize,
+    /// Tunes the requests in flight to the latency and errors of the provider, see
+    /// [`AimdController`], starting from one.
+    pub adaptive_concurrency: bool,
+    /// Proofs verified in parallel, on the blocking thread pool.
+    pub verify_concurrency: usize,
+    /// Proofs encoded in parallel, on the blocking thread pool.
+    pub encode_concurrency: usize,
+    /// Capacity of the channels between the stages.
+    pub capacity: usize,
+    /// Sends the requests one at a time, in ascending order of target slot, so that
+    /// runs (e.g. the reproduction of an incident) send the same sequence of requests.
+    /// Overrides the fetch concurrency.
+    pub deterministic: bool,
+}
+
+impl Default for PipelineOptions {
+    fn default() -> Self {
+        let cpus = available_parallelism().map_or(1, usize::from);
+        Self {
+            fetch_concurrency: 32,
+            adaptive_concurrency: false,
+            verify_concurrency: cpus,
+            encode_concurrency: cpus,
+            capacity: 64,
+            deterministic: false,
+        }
+    }
+}
+
+impl PipelineOptions {
+    pub fn with_fetch_concurrency(mut self, concurrency: usize) -> Self {
+        self.fetch_concurrency = concurrency;
+        self
+    }
+
+    pub fn with_adaptive_concurrency(mut self, enabled: bool) -> Self {
+        self.adaptive_concurrency = enabled;
+        self
+    }
+
+    pub fn with_verify_concurrency(mut self, concurrency: usize) -> Self {
+        self.verify_concurrency = concurrency;
+        self
+    }
+
+    pub fn with_encode_concurrency(mut self, concurrency: usize) -> Self {
+        self.encode_concurrency = concurrency;
+        self
+    }
+
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn with_deterministic(mut self, enabled: bool) -> Self {
+        self.deterministic = enabled;
+        self
+    }
+}
+
+/// Verified proof of a target, with its canonical JSON encoding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodedProof {
+    pub proof: BlockRootsProof,
+    /// Root of the target block, proven by `proof`.
+    pub block_root: Node,
+    /// Canonical JSON of `proof`, see [`crate::canonical`].
+    pub json: String,
+}
+
+type Fetched = (u64, Result<BlockRootsProof, AncestryProverError>);
+type Verified = (u64, Result<(BlockRootsProof, Node), AncestryProverError>);
+
+impl<P: ProofProvider> AncestryProver<P> {
+    /// Proves, verifies and encodes each of `target_slots` against one recent block,
+    /// like [`AncestryProver::prove_each`] followed by the verification and encoding
+    /// of the proofs, but with the three stages running concurrently. Repeated targets
+    /// are proven once, and the results are in ascending order of slot.
+    ///
+    /// Like [`AncestryProver::prove`], fails with [`AncestryProverError::TargetTooOld`]
+    /// for targets older than the block_roots window.
+    pub async fn prove_pipelined(
+        &self,
+        target_slots: &[u64],
+        recent_block_slot: u64,
+        recent_block_state_root: &str,
+        options: &PipelineOptions,
+    ) -> Vec<(u64, Result<EncodedProof, AncestryProverError>)> {
+        let slots = BatchTargets::dedup(target_slots).slots().to_vec();
+
+        let (mut fetched_tx, fetched_rx) = mpsc::channel::<Fetched>(options.capacity);
+        let (verified_tx, verified_rx) = mpsc::channel::<Verified>(options.capacity);
+
+        let mut controller = if options.deterministic {
+            AimdController::new(1, 1)
+        } else if options.adaptive_concurrency {
+            AimdController::new(1, options.fetch_concurrency)
+        } else {
+            AimdController::new(options.fetch_concurrency, options.fetch_concurrency)
+        };
+        let fetch = async move {
+            let mut slots = slots.into_iter();
+            let mut in_flight = FuturesUnordered::new();
+            loop {
+                while in_flight.len() < controller.limit() {
+                    let Some(slot) = slots.next() else { break };
+                    in_flight.push(async move {
+                        let start = Instant::now();
+                        let result = self
+                            .prove(slot, recent_block_slot, recent_block_state_root)
+                            .await;
+                        (slot, start.elapsed(), result)
+                    });
+                }
+
+                let Some((slot, latency, result)) = in_flight.next().await else {
+                    break;
+                };
+                match &result {
+                    Err(AncestryProverError::ProofProviderError(err)) if err.is_transient() => {
+                        controller.on_error()
+                    }
+                    _ => controller.on_success(latency),
+                }
+                if fetched_tx.send((slot, result)).await.is_err() {
+                    break;
+                }
+            }
+        };
+
+        // the fetched proofs are valid, so is the state root they were fetched for
+        let state_root = parse_state_root(recent_block_state_root).unwrap_or_default();
+        let verify = fetched_rx
+            .map(|(slot, result)| async move {
+                let proof = match result {
+                    Ok(proof) => proof,
+                    Err(err) => return (slot, Err(err)),
+                };
+                let verified = tokio::task::spawn_blocking(move || {
+                    verify_block_roots_proof_for_anchor(
+                        state_root.0,
+                        recent_block_slot,
+                        slot,
+                        SLOTS_PER_HISTORICAL_ROOT as u64,
+                        &proof,
+                    )
+                    .map(|block_root| (proof, Node::from_slice(&block_root)))
+                    .map_err(|_| ProofProviderError::InvalidProofError().into())
+                })
+                .await
+                .expect("verification doesn't panic");
+                (slot, verified)
+            })
+            .buffer_unordered(options.verify_concurrency.max(1))
+            .map(Ok)
+            .forward(verified_tx);
+
+        let encode = verified_rx
+            .map(|(slot, result)| async move {
+                let (proof, block_root) = match result {
+                    Ok(verified) => verified,
+                    Err(err) => return (slot, Err(err)),
+                };
+                let encoded = tokio::task::spawn_blocking(move || {
+                    let json = to_canonical_json(&proof).map_err(ProofProviderError::from)?;
+                    Ok(EncodedProof {
+                        proof,
+                        block_root,
+                        json,
+                    })
+                })
+                .await
+                .expect("encoding doesn't panic");
+                (slot, encoded)
+            })
+            .buffer_unordered(options.encode_concurrency.max(1))
+            .collect::<Vec<_>>();
+
+        // the channels are only closed early if a stage is dropped, i.e. never here
+        let (_, _, mut results) = futures::join!(fetch, verify, encode);
+        results.sort_unstable_by_key(|(slot, _)| *slot);
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prover::block_roots_gindex;
+    use crate::provider::MockProofProvider;
+    use crate::sim::{SimChain, SimProvider};
+    use crate::StateId;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn it_should_prove_verify_and_encode_in_stages() {
+        let chain = Arc::new(SimChain::new(9_000));
+        let prover = AncestryProver::new(SimProvider::new(chain.clone()));
+        let state_root = StateId::Root(chain.state_root(9_000)).to_string();
+        let options = PipelineOptions::default()
+            .with_fetch_concurrency(8)
+            .with_adaptive_concurrency(true)
+            .with_verify_concurrency(2)
+            .with_capacity(4);
+
+        // the anchor itself is not a valid target
+        let targets = (8_950..=9_000).rev().chain([8_990]).collect::<Vec<_>>();
+        let results = prover
+            .prove_pipelined(&targets, 9_000, &state_root, &options)
+            .await;
+
+        assert_eq!(results.len(), 51);
+        for (slot, result) in &results[..50] {
+            let encoded = result.as_ref().unwrap();
+            assert_eq!(encoded.block_root, chain.block_root(*slot));
+            let decoded: BlockRootsProof = serde_json::from_str(&encoded.json).unwrap();
+            assert_eq!(decoded, encoded.proof);
+        }
+        assert!(matches!(
+            results[50],
+            (
+                9_000,
+                Err(AncestryProverError::TargetNotBeforeAnchor { .. })
+            )
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_send_the_same_requests_in_deterministic_mode() {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let mut provider = MockProofProvider::new();
+        let recorded = requests.clone();
+        provider
+            .expect_get_state_proof()
+            .returning(move |_, gindex| {
+                recorded.lock().unwrap().push(gindex);
+                Err(ProofProviderError::NotFoundError("no state".to_string()))
+            });
+        let prover = AncestryProver::new(provider);
+        let options = PipelineOptions::default().with_deterministic(true);
+
+        let results = prover
+            .prove_pipelined(
+                &[8_990, 8_950, 8_970],
+                9_000,
+                &format!("0x{}", "11".repeat(32)),
+                &options,
+            )
+            .await;
+
+        assert!(results.iter().all(|(_, result)| result.is_err()));
+        assert_eq!(
+            *requests.lock().unwrap(),
+            [8_950, 8_970, 8_990].map(block_roots_gindex)
+        );
+    }
+}
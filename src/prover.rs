@@ -1,47 +1,778 @@
-use crate::errors::AncestryProverError;
-use crate::provider::{BlockRootsProof, ProofProvider, Verify};
+#[cfg(feature = "providers")]
+use crate::anchor::{select_anchor, Anchor, AnchorSource, Checkpoint, TaggedAnchor};
+#[cfg(feature = "providers")]
+use crate::anchor_lock::AnchorLock;
+#[cfg(feature = "providers")]
+use crate::batch::{BatchResult, BatchTargets};
+#[cfg(feature = "providers")]
+use crate::bundle::{BundledMessage, ProofBundle};
+use crate::distance::fits_in_window;
+#[cfg(feature = "providers")]
+use crate::errors::ProofProviderError;
+use crate::errors::{AncestryProverError, VerifyError};
+use crate::header::verify_block_roots_proof_at_block_root;
+#[cfg(feature = "providers")]
+use crate::header::{anchor_at_block_root, header_root};
+#[cfg(feature = "providers")]
+use crate::middleware::RequestTally;
+use crate::provider::{BlockRootsProof, ProviderInfo};
+#[cfg(feature = "providers")]
+use crate::provider::{ProofProvider, StateId, Verify};
+#[cfg(feature = "providers")]
+use crate::stats::StatsRecorder;
+#[cfg(feature = "providers")]
+use crate::validation::{
+    parse_anchor, parse_block_root, parse_state_root, validate_in_window, validate_target_slot,
+};
+use crate::verification::proven_leaf;
+#[cfg(feature = "providers")]
+use crate::verification::{proof_root, SLOT_FIELD_GINDEX};
+use crate::verification::{verify_block_roots_proof, verify_block_roots_proof_strict};
+#[cfg(feature = "providers")]
+use crate::version::VersionInfo;
 use alloy_primitives::FixedBytes;
 use ethereum_consensus::capella::presets::mainnet::{BeaconState, SLOTS_PER_HISTORICAL_ROOT};
 use ethereum_consensus::ssz::prelude::*;
+#[cfg(feature = "providers")]
+use futures::future::join_all;
+use std::ops::RangeInclusive;
 use std::str::FromStr;
+#[cfg(feature = "providers")]
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(feature = "providers")]
+use std::time::Instant;
 
+#[cfg(feature = "providers")]
 pub struct AncestryProver<P: ProofProvider> {
     proof_provider: P,
+    anchor_diagnostics: bool,
+    canonical_check: bool,
+    stats: Option<StatsRecorder>,
 }
 
+/// Per-call overrides of the configuration of a prover, see [`AncestryProver::prove_with`].
+#[cfg(feature = "providers")]
+#[derive(Clone, Default)]
+pub struct ProveOptions {
+    /// Provider used instead of the prover's, e.g. of another endpoint or network.
+    pub provider: Option<Arc<dyn ProofProvider>>,
+    /// Fails the call with a `ProofProviderError::Timeout` when it takes longer.
+    pub timeout: Option<Duration>,
+    /// Times the call is retried after transient errors, see [`ProofProviderError::is_transient`],
+    /// waiting first as advised by rate limited backends ([`ProofProviderError::retry_after`]).
+    pub retries: u32,
+    /// Fails the call with [`AncestryProverError::DeadlineExceeded`] when it isn't
+    /// done by then, retries included.
+    pub deadline: Option<Instant>,
+}
+
+#[cfg(feature = "providers")]
+impl ProveOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_provider<P: ProofProvider>(mut self, provider: P) -> Self {
+        self.provider = Some(Arc::new(provider));
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+}
+
+/// Outcome of the cross-check of a proven block root against the canonical chain.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CanonicalCheck {
+    /// The cross-check is disabled, see `AncestryProver::with_canonical_check`.
+    NotChecked,
+    Matches,
+    /// The proven block root is not the canonical one, e.g. the provider served
+    /// the state of a non-canonical chain.
+    Mismatch {
+        canonical_root: Node,
+    },
+    /// The canonical block root could not be fetched, e.g. because the target slot was skipped.
+    Unavailable(String),
+}
+
+/// Backend that generated a proof, to trace bad proofs back to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    pub provider: ProviderInfo,
+    /// Time taken by the provider to return the proof.
+    pub latency: Duration,
+    /// HTTP requests sent by the provider, e.g. several when negotiating endpoints.
+    pub requests: u32,
+    /// Attempts beyond `requests`, made by retrying layers of the provider's stack.
+    pub retries: u32,
+}
+
+/// Verified proof, with metadata about the proven block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProvenAncestry {
+    pub proof: BlockRootsProof,
+    /// Root of the target block, proven by `proof`.
+    pub block_root: Node,
+    pub canonical_check: CanonicalCheck,
+    pub provenance: Provenance,
+}
+
+#[cfg(feature = "providers")]
 impl<P: ProofProvider> AncestryProver<P> {
     pub fn new(proof_provider: P) -> Self {
-        Self { proof_provider }
+        Self {
+            proof_provider,
+            anchor_diagnostics: false,
+            canonical_check: false,
+            stats: None,
+        }
+    }
+
+    /// Records the distances from the targets to the anchors of the requested proofs
+    /// (see [`Stats::distances`](crate::stats::Stats::distances)), e.g. in the recorder
+    /// of the provider.
+    pub fn with_stats_recorder(mut self, stats: StatsRecorder) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    /// Cross-checks the block roots proven by [`AncestryProver::prove_with_metadata`]
+    /// against the canonical block root of the target slot, as reported by the
+    /// provider's beacon API. Costs an extra request per proof, disabled by default.
+    pub fn with_canonical_check(mut self, enabled: bool) -> Self {
+        self.canonical_check = enabled;
+        self
+    }
+
+    /// Verifies the proofs against the anchor state root, and when they fail (or no
+    /// state is found) asks the provider whether the anchor root is actually a block
+    /// root, returning [`AncestryProverError::BlockRootAsStateRoot`] if so.
+    ///
+    /// Costs an extra request to the provider on failures, disabled by default.
+    pub fn with_anchor_diagnostics(mut self, enabled: bool) -> Self {
+        self.anchor_diagnostics = enabled;
+        self
+    }
+
+    /// Returns whether the block of `target_slot` can be proven against the block of
+    /// `recent_slot`, i.e. whether [`AncestryProver::prove`] supports the pair.
+    pub fn can_prove(&self, target_slot: u64, recent_slot: u64) -> bool {
+        is_provable(target_slot, recent_slot)
     }
 
     // This implementation generates an ancestry proof from the target block to a recent block.
-    // Currently, the target block cannot be older than SLOTS_PER_HISTORICAL_ROOT (8192 blocks, ~27 hours).
+    // Currently, the target block cannot be older than SLOTS_PER_HISTORICAL_ROOT (8192 blocks, ~27 hours),
+    // see `can_prove`.
     pub async fn prove(
         &self,
         target_block_slot: u64,
         recent_block_slot: u64,
         recent_block_state_root: &str,
     ) -> Result<BlockRootsProof, AncestryProverError> {
-        if recent_block_slot.saturating_sub(target_block_slot) >= (SLOTS_PER_HISTORICAL_ROOT as u64)
-        {
-            // todo:  Historical root proofs
-            unimplemented!()
-        }
+        validate_target_slot(target_block_slot, recent_block_slot)?;
+        self.record_distance(target_block_slot, recent_block_slot);
+        validate_in_window(target_block_slot, recent_block_slot)?;
 
         // calculate gindex of the target block
-        let index = target_block_slot % SLOTS_PER_HISTORICAL_ROOT as u64;
-        let path = &["block_roots".into(), PathElement::Index(index as usize)];
-        let gindex = BeaconState::generalized_index(path).unwrap() as u64;
+        let gindex = block_roots_gindex(target_block_slot);
 
-        let state_root_str = &recent_block_state_root.to_string();
+        let state_root = parse_state_root(recent_block_state_root)?;
         // get proofs from provider
+        let proof = match self
+            .proof_provider
+            .get_state_proof(&StateId::Root(state_root), gindex)
+            .await
+        {
+            Ok(proof) => proof,
+            Err(err) => {
+                let err = anchor_error(err, recent_block_state_root);
+                return Err(self.diagnose_anchor(&state_root, err).await);
+            }
+        };
+
+        // make sure that the provider proved the requested block root and not another state field
+        validate_gindex(&proof, gindex)?;
+
+        if self.anchor_diagnostics && proof.verify(state_root).is_err() {
+            let err = ProofProviderError::InvalidProofError().into();
+            return Err(self.diagnose_anchor(&state_root, err).await);
+        }
+
+        Ok(proof)
+    }
+
+    /// Like [`AncestryProver::prove`], but anchored at the root of the recent block
+    /// instead of its state root, for verifiers that only know block roots (e.g. from
+    /// the EIP-4788 beacon roots contract). The proof additionally proves the state
+    /// root under the block root, see [`anchor_at_block_root`], and is verified with
+    /// [`verify_at_block_root`].
+    pub async fn prove_at_block_root(
+        &self,
+        target_block_slot: u64,
+        recent_block_slot: u64,
+        recent_block_root: &str,
+    ) -> Result<BlockRootsProof, AncestryProverError> {
+        validate_target_slot(target_block_slot, recent_block_slot)?;
+        let block_root = parse_block_root(recent_block_root)?;
+        let header = self
+            .proof_provider
+            .get_block_header(&block_root)
+            .await
+            .map_err(|err| anchor_error(err, recent_block_root))?;
+        if header_root(&header) != block_root {
+            return Err(ProofProviderError::InvalidProofError().into());
+        }
+        if header.slot != recent_block_slot {
+            return Err(AncestryProverError::InvalidAnchor(format!(
+                "block {recent_block_root} is at slot {}, not {recent_block_slot}",
+                header.slot
+            )));
+        }
+
+        let proof = self
+            .prove(
+                target_block_slot,
+                recent_block_slot,
+                &StateId::Root(header.state_root).to_string(),
+            )
+            .await?;
+        anchor_at_block_root(proof, &header)
+            .map_err(|_| ProofProviderError::InvalidProofError().into())
+    }
+
+    /// Like [`AncestryProver::prove`], with the provider, timeout, retry policy or
+    /// deadline overridden for this call only, so that one prover can serve requests
+    /// with different configurations.
+    pub async fn prove_with(
+        &self,
+        target_block_slot: u64,
+        recent_block_slot: u64,
+        recent_block_state_root: &str,
+        options: &ProveOptions,
+    ) -> Result<BlockRootsProof, AncestryProverError> {
+        let overridden = options
+            .provider
+            .clone()
+            .map(|proof_provider| AncestryProver {
+                proof_provider,
+                anchor_diagnostics: self.anchor_diagnostics,
+                canonical_check: self.canonical_check,
+                stats: self.stats.clone(),
+            });
+
+        let deadline_exceeded = || AncestryProverError::DeadlineExceeded(target_block_slot);
+        let mut attempt = 0;
+        loop {
+            let left = options
+                .deadline
+                .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            if left == Some(Duration::ZERO) {
+                return Err(deadline_exceeded());
+            }
+
+            let prove = async {
+                match &overridden {
+                    Some(prover) => {
+                        prover
+                            .prove(
+                                target_block_slot,
+                                recent_block_slot,
+                                recent_block_state_root,
+                            )
+                            .await
+                    }
+                    None => {
+                        self.prove(
+                            target_block_slot,
+                            recent_block_slot,
+                            recent_block_state_root,
+                        )
+                        .await
+                    }
+                }
+            };
+            let prove = async {
+                match left {
+                    Some(left) => tokio::time::timeout(left, prove)
+                        .await
+                        .unwrap_or_else(|_| Err(deadline_exceeded())),
+                    None => prove.await,
+                }
+            };
+            let result = match options.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, prove)
+                    .await
+                    .unwrap_or_else(|_| {
+                        let provider = match &overridden {
+                            Some(prover) => prover.proof_provider.info(),
+                            None => self.proof_provider.info(),
+                        };
+                        Err(ProofProviderError::Timeout {
+                            url: provider.url.unwrap_or(provider.backend),
+                            elapsed: timeout,
+                        }
+                        .into())
+                    }),
+                None => prove.await,
+            };
+
+            match result {
+                Err(AncestryProverError::ProofProviderError(err))
+                    if err.is_transient() && attempt < options.retries =>
+                {
+                    // back off as advised by rate limiting backends, unless it'd miss the deadline
+                    if let Some(wait) = err.retry_after() {
+                        if left.is_some_and(|left| wait >= left) {
+                            return Err(err.into());
+                        }
+                        tokio::time::sleep(wait).await;
+                    }
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Proves each of `target_slots` separately against one recent block, reporting
+    /// the targets that failed instead of failing the whole batch. Repeated targets
+    /// are proven once, see [`BatchResult::outcomes`] for the outcomes in the order
+    /// of `target_slots`.
+    ///
+    /// Like [`AncestryProver::prove`], fails with [`AncestryProverError::TargetTooOld`]
+    /// for targets older than the block_roots window.
+    pub async fn prove_each(
+        &self,
+        target_slots: &[u64],
+        recent_block_slot: u64,
+        recent_block_state_root: &str,
+    ) -> BatchResult {
+        // targets not before the anchor fail on their own, see BatchResult::outcomes
+        let targets = BatchTargets::dedup(target_slots);
+
+        let results = join_all(targets.slots().iter().map(|target_block_slot| {
+            self.prove(
+                *target_block_slot,
+                recent_block_slot,
+                recent_block_state_root,
+            )
+        }))
+        .await;
+
+        let mut batch = BatchResult::default();
+        for (slot, result) in targets.slots().iter().zip(results) {
+            match result {
+                Ok(proof) => batch.ok.push((*slot, proof)),
+                Err(err) => batch.failed.push((*slot, err)),
+            }
+        }
+        batch.targets = targets;
+        batch
+    }
+
+    /// Resolves an anchor descriptor given as user input (see [`parse_anchor`]) into
+    /// the slot and state root of the anchor, from a proof of the `slot` of its state.
+    pub async fn resolve_anchor(&self, descriptor: &str) -> Result<Anchor, AncestryProverError> {
+        self.resolve_state_anchor(&parse_anchor(descriptor)?).await
+    }
+
+    /// Like [`AncestryProver::resolve_anchor`], tagging the anchor with the checkpoint
+    /// it was resolved from, e.g. [`Checkpoint::Justified`] for `justified`.
+    pub async fn resolve_tagged_anchor(
+        &self,
+        descriptor: &str,
+    ) -> Result<TaggedAnchor, AncestryProverError> {
+        let state_id = parse_anchor(descriptor)?;
+        Ok(TaggedAnchor {
+            anchor: self.resolve_state_anchor(&state_id).await?,
+            checkpoint: Checkpoint::from_state_id(&state_id),
+        })
+    }
+
+    async fn resolve_state_anchor(
+        &self,
+        state_id: &StateId,
+    ) -> Result<Anchor, AncestryProverError> {
         let proof = self
             .proof_provider
-            .get_state_proof(state_root_str.as_str(), gindex)
+            .get_state_proof(state_id, SLOT_FIELD_GINDEX)
+            .await
+            .map_err(|err| anchor_error(err, &state_id.to_string()))?;
+
+        let invalid = |_| ProofProviderError::InvalidProofError();
+        let state_root = proof_root(&proof).map_err(invalid)?;
+        if matches!(state_id, StateId::Root(root) if *root != state_root) {
+            return Err(ProofProviderError::InvalidProofError().into());
+        }
+        let slot = proven_leaf(&proof, SLOT_FIELD_GINDEX).map_err(invalid)?;
+
+        Ok(Anchor {
+            slot: u64::from_le_bytes(slot.0[..8].try_into().unwrap()),
+            state_root,
+        })
+    }
+
+    /// Proves the block of `target_slot` against the latest anchor accepted by the
+    /// destination of the proof, as reported by `anchors`.
+    pub async fn prove_with_anchor_source<S: AnchorSource + ?Sized>(
+        &self,
+        target_block_slot: u64,
+        anchors: &S,
+    ) -> Result<(Anchor, BlockRootsProof), AncestryProverError> {
+        let accepted = anchors.accepted_anchors().await?;
+        let anchor = *select_anchor(&accepted, target_block_slot)
+            .ok_or(AncestryProverError::NoAcceptedAnchor(target_block_slot))?;
+
+        let proof = self
+            .prove(
+                target_block_slot,
+                anchor.slot,
+                &StateId::Root(anchor.state_root).to_string(),
+            )
+            .await?;
+
+        Ok((anchor, proof))
+    }
+
+    /// Proves the block of `target_slot` against the anchor pinned for it in `lock`,
+    /// failing with [`AncestryProverError::NoAcceptedAnchor`] if none is.
+    pub async fn prove_pinned(
+        &self,
+        target_block_slot: u64,
+        lock: &AnchorLock,
+    ) -> Result<(Anchor, BlockRootsProof), AncestryProverError> {
+        let anchor = lock
+            .anchor_for(target_block_slot)
+            .ok_or(AncestryProverError::NoAcceptedAnchor(target_block_slot))?
+            .anchor();
+
+        let proof = self
+            .prove(
+                target_block_slot,
+                anchor.slot,
+                &StateId::Root(anchor.state_root).to_string(),
+            )
+            .await?;
+
+        Ok((anchor, proof))
+    }
+
+    /// Like [`AncestryProver::prove_with_anchor_source`], within `deadline` for the
+    /// resolution of the anchor and the proof (retries included), failing with
+    /// [`AncestryProverError::DeadlineExceeded`] once it's exceeded.
+    pub async fn prove_with_deadline<S: AnchorSource + ?Sized>(
+        &self,
+        target_block_slot: u64,
+        anchors: &S,
+        options: &ProveOptions,
+        deadline: Instant,
+    ) -> Result<(Anchor, BlockRootsProof), AncestryProverError> {
+        let left = deadline.saturating_duration_since(Instant::now());
+        let accepted = tokio::time::timeout(left, anchors.accepted_anchors())
+            .await
+            .map_err(|_| AncestryProverError::DeadlineExceeded(target_block_slot))??;
+        let anchor = *select_anchor(&accepted, target_block_slot)
+            .ok_or(AncestryProverError::NoAcceptedAnchor(target_block_slot))?;
+
+        let proof = self
+            .prove_with(
+                target_block_slot,
+                anchor.slot,
+                &StateId::Root(anchor.state_root).to_string(),
+                &options.clone().with_deadline(deadline),
+            )
             .await?;
 
+        Ok((anchor, proof))
+    }
+
+    /// Like [`AncestryProver::prove`], but also verifies the proof and returns the
+    /// proven block root, cross-checked against the canonical chain if enabled.
+    ///
+    /// The proof and the canonical block root are fetched concurrently; failing to
+    /// fetch the latter is reported as [`CanonicalCheck::Unavailable`].
+    pub async fn prove_with_metadata(
+        &self,
+        target_block_slot: u64,
+        recent_block_slot: u64,
+        recent_block_state_root: &str,
+    ) -> Result<ProvenAncestry, AncestryProverError> {
+        let tally = Arc::new(RequestTally::default());
+        let start = Instant::now();
+        let prove = async {
+            let proof = tally
+                .scope(self.prove(
+                    target_block_slot,
+                    recent_block_slot,
+                    recent_block_state_root,
+                ))
+                .await;
+            (proof, start.elapsed())
+        };
+        let canonical_root = async {
+            if !self.canonical_check {
+                return None;
+            }
+            Some(self.proof_provider.get_block_root(target_block_slot).await)
+        };
+        let ((proof, latency), canonical_root) = futures::join!(prove, canonical_root);
+        let proof = proof?;
+        let provenance = Provenance {
+            provider: self.proof_provider.info(),
+            latency,
+            requests: tally.requests(),
+            retries: tally.retries(),
+        };
+        let block_root = verify_block_roots_proof(
+            parse_state_root(recent_block_state_root)?.0,
+            target_block_slot,
+            SLOTS_PER_HISTORICAL_ROOT as u64,
+            &proof,
+        )
+        .map(Node::from)
+        .map_err(|_| ProofProviderError::InvalidProofError())?;
+
+        let canonical_check = match canonical_root {
+            None => CanonicalCheck::NotChecked,
+            Some(Ok(canonical_root)) if canonical_root == block_root => CanonicalCheck::Matches,
+            Some(Ok(canonical_root)) => CanonicalCheck::Mismatch { canonical_root },
+            Some(Err(err)) => CanonicalCheck::Unavailable(err.to_string()),
+        };
+
+        Ok(ProvenAncestry {
+            proof,
+            block_root,
+            canonical_check,
+            provenance,
+        })
+    }
+
+    fn record_distance(&self, target_block_slot: u64, recent_block_slot: u64) {
+        if let Some(stats) = &self.stats {
+            stats.record_distance(recent_block_slot - target_block_slot);
+        }
+    }
+
+    /// Replaces `err` with a targeted error if anchor diagnostics are enabled and
+    /// the anchor state root turns out to be a block root.
+    async fn diagnose_anchor(
+        &self,
+        state_root: &Node,
+        err: AncestryProverError,
+    ) -> AncestryProverError {
+        if !self.anchor_diagnostics
+            || !matches!(
+                err,
+                AncestryProverError::AnchorStateNotFound { .. }
+                    | AncestryProverError::ProofProviderError(
+                        ProofProviderError::InvalidProofError()
+                    )
+            )
+        {
+            return err;
+        }
+
+        match self.proof_provider.is_block_root(state_root).await {
+            Ok(true) => {
+                AncestryProverError::BlockRootAsStateRoot(StateId::Root(*state_root).to_string())
+            }
+            // the original error is more useful than a failed diagnostic
+            _ => err,
+        }
+    }
+
+    /// Proves the blocks of several target slots against one recent block with a
+    /// single compact multiproof, sharing the witnesses of the targets instead of
+    /// requesting a proof per target. Repeated targets are proven once.
+    pub async fn prove_batch(
+        &self,
+        target_slots: &[u64],
+        recent_block_slot: u64,
+        recent_block_state_root: &str,
+    ) -> Result<BlockRootsProof, AncestryProverError> {
+        let targets = BatchTargets::new(target_slots, recent_block_slot)?;
+        // record every requested distance, including those past the window
+        for target_block_slot in targets.slots() {
+            self.record_distance(*target_block_slot, recent_block_slot);
+        }
+        for target_block_slot in targets.slots() {
+            validate_in_window(*target_block_slot, recent_block_slot)?;
+        }
+
+        let state_root = parse_state_root(recent_block_state_root)?;
+        // the targets are unique, but wrap around the ring buffer out of gindex order
+        let mut gindices = targets
+            .slots()
+            .iter()
+            .map(|target_block_slot| block_roots_gindex(*target_block_slot))
+            .collect::<Vec<_>>();
+        gindices.sort_unstable();
+
+        let proof = self
+            .proof_provider
+            .get_state_multiproof(&StateId::Root(state_root), &gindices)
+            .await
+            .map_err(|err| anchor_error(err, recent_block_state_root))?;
+        for gindex in &gindices {
+            validate_gindex(&proof, *gindex)?;
+        }
+
         Ok(proof)
     }
+
+    /// Proves the blocks of every slot of `start_slot..=end_slot` against one recent
+    /// block with a single multiproof, see [`AncestryProver::prove_batch`], e.g. for
+    /// indexers attesting to a contiguous span of ancestors.
+    pub async fn prove_range(
+        &self,
+        start_slot: u64,
+        end_slot: u64,
+        recent_block_slot: u64,
+        recent_block_state_root: &str,
+    ) -> Result<BlockRootsProof, AncestryProverError> {
+        if start_slot > end_slot {
+            return Err(ProofProviderError::InputError(format!(
+                "Empty range of slots {}..={}",
+                start_slot, end_slot
+            ))
+            .into());
+        }
+        // check the window before listing the targets, whose count is otherwise unbounded
+        validate_target_slot(end_slot, recent_block_slot)?;
+        validate_in_window(start_slot, recent_block_slot)?;
+
+        let target_slots = (start_slot..=end_slot).collect::<Vec<_>>();
+        self.prove_batch(&target_slots, recent_block_slot, recent_block_state_root)
+            .await
+    }
+
+    /// Proves the blocks of the target slots of several messages, given as
+    /// `(target_slot, payload_hash)` pairs, against one recent block with a
+    /// single multiproof, see [`AncestryProver::prove_batch`]. The bundled messages
+    /// are kept in the given order.
+    pub async fn prove_bundle(
+        &self,
+        messages: &[(u64, Node)],
+        recent_block_slot: u64,
+        recent_block_state_root: &str,
+    ) -> Result<ProofBundle, AncestryProverError> {
+        let target_slots = messages
+            .iter()
+            .map(|(target_block_slot, _)| *target_block_slot)
+            .collect::<Vec<_>>();
+        let proof = self
+            .prove_batch(&target_slots, recent_block_slot, recent_block_state_root)
+            .await?;
+        let state_root = parse_state_root(recent_block_state_root)?;
+
+        let messages = messages
+            .iter()
+            .map(|(target_slot, payload_hash)| {
+                let gindex = block_roots_gindex(*target_slot);
+                let block_root = proven_leaf(&proof, gindex)
+                    .map_err(|_| AncestryProverError::MissingGindex(gindex))?;
+
+                Ok(BundledMessage {
+                    target_slot: *target_slot,
+                    payload_hash: *payload_hash,
+                    block_root,
+                })
+            })
+            .collect::<Result<Vec<_>, AncestryProverError>>()?;
+
+        Ok(ProofBundle {
+            anchor_slot: recent_block_slot,
+            anchor_state_root: state_root,
+            proof,
+            messages,
+            prover: Some(VersionInfo::current()),
+        })
+    }
+}
+
+/// Reports a missing anchor state as such, as it's most often caused by passing
+/// the anchor block root instead of its state root.
+#[cfg(feature = "providers")]
+fn anchor_error(err: ProofProviderError, state_root: &str) -> AncestryProverError {
+    match err {
+        ProofProviderError::NotFoundError(url) => AncestryProverError::AnchorStateNotFound {
+            state_root: state_root.to_string(),
+            url,
+        },
+        err => err.into(),
+    }
+}
+
+/// Returns the latest slot provable against the state of `anchor_slot`, i.e. the
+/// slot before the anchor, or `None` for the genesis anchor.
+pub fn max_provable_target(anchor_slot: u64) -> Option<u64> {
+    anchor_slot.checked_sub(1)
+}
+
+/// Returns the earliest slot provable against the state of `anchor_slot`, i.e. less
+/// than `SLOTS_PER_HISTORICAL_ROOT` slots before it, or `None` for the genesis anchor.
+pub fn min_provable_target(anchor_slot: u64) -> Option<u64> {
+    max_provable_target(anchor_slot)?;
+    Some(anchor_slot.saturating_sub(SLOTS_PER_HISTORICAL_ROOT as u64 - 1))
+}
+
+/// Checks whether the block of `slot` can be proven against the state of `anchor_slot`,
+/// e.g. to filter targets before proving them in batches.
+pub fn is_provable(slot: u64, anchor_slot: u64) -> bool {
+    fits_in_window(anchor_slot, slot)
+}
+
+/// Returns the gindex of the `block_roots` entry of a slot in the beacon state.
+pub fn block_roots_gindex(slot: u64) -> u64 {
+    let index = slot % SLOTS_PER_HISTORICAL_ROOT as u64;
+    let path = &["block_roots".into(), PathElement::Index(index as usize)];
+    BeaconState::generalized_index(path).unwrap() as u64
+}
+
+/// Returns the range of gindexes of the `block_roots` vector entries in the beacon state.
+pub fn block_roots_gindex_range() -> RangeInclusive<u64> {
+    block_roots_gindex(0)..=block_roots_gindex(SLOTS_PER_HISTORICAL_ROOT as u64 - 1)
+}
+
+/// Checks that a proof returned by a provider proves the expected `block_roots` gindex.
+pub fn validate_gindex(proof: &BlockRootsProof, expected: u64) -> Result<(), AncestryProverError> {
+    match proof {
+        BlockRootsProof::SingleProof { gindex, .. } => {
+            if !block_roots_gindex_range().contains(gindex) {
+                return Err(AncestryProverError::GindexOutOfBounds(*gindex));
+            }
+            if *gindex != expected {
+                return Err(AncestryProverError::UnexpectedGindex {
+                    expected,
+                    actual: *gindex,
+                });
+            }
+        }
+        BlockRootsProof::CompactProof { .. } => {
+            let gindices = proof
+                .gindices()
+                .map_err(|_| AncestryProverError::MissingGindex(expected))?;
+            if !gindices.contains(&expected) {
+                return Err(AncestryProverError::MissingGindex(expected));
+            }
+        }
+    }
+
+    Ok(())
 }
 
 pub fn verify(
@@ -50,20 +781,110 @@ pub fn verify(
     recent_block_slot: u64,
     recent_block_state_root: &str,
 ) -> bool {
-    if recent_block_slot.saturating_sub(target_block_slot) >= (SLOTS_PER_HISTORICAL_ROOT as u64) {
-        // todo:  Historical root proofs
-        unimplemented!()
+    verify_in_window(
+        proof,
+        target_block_slot,
+        recent_block_slot,
+        recent_block_state_root,
+        verify_block_roots_proof,
+    )
+}
+
+/// Like [`verify`], but also rejects proofs of a zero leaf or with an all-zero
+/// branch, see [`verify_block_roots_proof_strict`]. The `block_roots` entries of
+/// chains younger than the window are zero until filled, and a block root is never
+/// zero, so this tells the unfilled entries of young chains from proven blocks.
+pub fn verify_strict(
+    proof: &BlockRootsProof,
+    target_block_slot: u64,
+    recent_block_slot: u64,
+    recent_block_state_root: &str,
+) -> bool {
+    verify_in_window(
+        proof,
+        target_block_slot,
+        recent_block_slot,
+        recent_block_state_root,
+        verify_block_roots_proof_strict,
+    )
+}
+
+fn verify_in_window(
+    proof: &BlockRootsProof,
+    target_block_slot: u64,
+    recent_block_slot: u64,
+    recent_block_state_root: &str,
+    verify_block_roots_proof: fn(
+        [u8; 32],
+        u64,
+        u64,
+        &BlockRootsProof,
+    ) -> Result<[u8; 32], VerifyError>,
+) -> bool {
+    // the anchor state doesn't hold the root of the target block, todo: historical root proofs
+    if !fits_in_window(recent_block_slot, target_block_slot) {
+        return false;
+    }
+
+    let recent_block_state_root = match FixedBytes::<32>::from_str(recent_block_state_root) {
+        Ok(root) => root,
+        Err(_) => return false,
+    };
+
+    verify_block_roots_proof(
+        recent_block_state_root.0,
+        target_block_slot,
+        SLOTS_PER_HISTORICAL_ROOT as u64,
+        proof,
+    )
+    .is_ok()
+}
+
+/// Like [`verify`], for proofs anchored at the root of the recent block instead of
+/// its state root, see [`AncestryProver::prove_at_block_root`].
+pub fn verify_at_block_root(
+    proof: &BlockRootsProof,
+    target_block_slot: u64,
+    recent_block_slot: u64,
+    recent_block_root: &str,
+) -> bool {
+    if !fits_in_window(recent_block_slot, target_block_slot) {
+        return false;
     }
 
-    let recent_block_state_root = match FixedBytes::from_str(recent_block_state_root) {
+    let recent_block_root = match FixedBytes::<32>::from_str(recent_block_root) {
         Ok(root) => root,
         Err(_) => return false,
     };
 
-    proof.verify(recent_block_state_root)
+    verify_block_roots_proof_at_block_root(
+        recent_block_root.0,
+        target_block_slot,
+        SLOTS_PER_HISTORICAL_ROOT as u64,
+        proof,
+    )
+    .is_ok()
+}
+
+/// Like [`verify`], but also checks that the proven block root is `expected_target_root`,
+/// for callers who already know which block they expect.
+pub fn verify_expected(
+    proof: &BlockRootsProof,
+    target_block_slot: u64,
+    recent_block_slot: u64,
+    recent_block_state_root: &str,
+    expected_target_root: &Node,
+) -> bool {
+    verify(
+        proof,
+        target_block_slot,
+        recent_block_slot,
+        recent_block_state_root,
+    ) && proven_leaf(proof, block_roots_gindex(target_block_slot)).as_ref()
+        == Ok(expected_target_root)
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "providers"))]
 mod tests {
     use std::fs::File;
 
@@ -81,22 +902,43 @@ mod tests {
         block
     }
 
+    #[test]
+    fn it_should_compute_the_provable_window() {
+        assert_eq!(max_provable_target(7_879_376), Some(7_879_375));
+        assert_eq!(min_provable_target(7_879_376), Some(7_871_185));
+        assert!(is_provable(7_871_185, 7_879_376));
+        assert!(!is_provable(7_871_184, 7_879_376));
+        assert!(!is_provable(7_879_376, 7_879_376));
+
+        assert_eq!(min_provable_target(100), Some(0));
+        assert_eq!(max_provable_target(0), None);
+        assert!(!is_provable(0, 0));
+    }
+
     #[tokio::test]
-    #[should_panic(expected = "not implemented")]
-    async fn it_should_panic_for_old_blocks() {
+    async fn it_should_reject_targets_too_old() {
         // 7879376 - 7862720 = 16656
         let target_block = get_test_block_for_slot(7_862_720);
         let recent_block = get_test_block_for_slot(7_879_376);
 
         let prover_api = StateProverProvider::new("mainnet".to_string(), "".to_string());
         let prover = AncestryProver::new(prover_api);
-        _ = prover
+        assert!(!prover.can_prove(target_block.slot, recent_block.slot));
+        assert!(prover.can_prove(recent_block.slot - 8_191, recent_block.slot));
+        let result = prover
             .prove(
                 target_block.slot,
                 recent_block.slot,
                 recent_block.state_root.to_string().as_str(),
             )
             .await;
+        assert!(matches!(
+            result,
+            Err(AncestryProverError::TargetTooOld {
+                distance: 16_656,
+                max_supported: 8_191
+            })
+        ));
     }
 
     #[tokio::test]
@@ -105,18 +947,28 @@ mod tests {
         let target_block = get_test_block_for_slot(7_879_316);
         let recent_block = get_test_block_for_slot(7_879_323);
 
+        let expected_gindex = block_roots_gindex(target_block.slot);
         let mut prover_api = provider::MockProofProvider::new();
         prover_api
             .expect_get_state_proof()
-            .returning(|_block_id, _gindex| Ok(BlockRootsProof::default()));
+            .withf(move |_block_id, gindex| *gindex == expected_gindex)
+            .times(1)
+            .returning(|_block_id, gindex| {
+                Ok(BlockRootsProof::SingleProof {
+                    gindex,
+                    witnesses: vec![],
+                    leaf: Node::default(),
+                })
+            });
         let prover = AncestryProver::new(prover_api);
-        _ = prover
+        let proof = prover
             .prove(
                 target_block.slot,
                 recent_block.slot,
                 recent_block.state_root.to_string().as_str(),
             )
             .await;
+        assert!(proof.is_ok());
     }
 
     #[tokio::test]
@@ -234,6 +1086,828 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn it_should_reject_proofs_outside_of_block_roots() {
+        let target_block = get_test_block_for_slot(7_879_316);
+        let recent_block = get_test_block_for_slot(7_879_323);
+
+        // gindex 39 is the state_roots field of the beacon state
+        let mut prover_api = provider::MockProofProvider::new();
+        prover_api.expect_get_state_proof().returning(|_, _| {
+            Ok(BlockRootsProof::SingleProof {
+                gindex: 39,
+                witnesses: vec![Node::default(); 5],
+                leaf: Node::default(),
+            })
+        });
+        let prover = AncestryProver::new(prover_api);
+
+        let result = prover
+            .prove(
+                target_block.slot,
+                recent_block.slot,
+                recent_block.state_root.to_string().as_str(),
+            )
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(AncestryProverError::GindexOutOfBounds(39))
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_reject_proofs_for_another_block_root() {
+        let target_block = get_test_block_for_slot(7_879_316);
+        let recent_block = get_test_block_for_slot(7_879_323);
+
+        let mut prover_api = provider::MockProofProvider::new();
+        prover_api.expect_get_state_proof().returning(|_, gindex| {
+            Ok(BlockRootsProof::SingleProof {
+                gindex: gindex + 1,
+                witnesses: vec![],
+                leaf: Node::default(),
+            })
+        });
+        let prover = AncestryProver::new(prover_api);
+
+        let result = prover
+            .prove(
+                target_block.slot,
+                recent_block.slot,
+                recent_block.state_root.to_string().as_str(),
+            )
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(AncestryProverError::UnexpectedGindex {
+                expected: 309_908,
+                actual: 309_909
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_reject_malformed_state_roots() {
+        let mut prover_api = provider::MockProofProvider::new();
+        prover_api.expect_get_state_proof().never();
+        let prover = AncestryProver::new(prover_api);
+
+        for state_root in [
+            "head",
+            "0x1234",
+            "0xzz16855f71e99a620029e6b7c683abab542f66ee87c3dd8c72424568348f28b",
+        ] {
+            let result = prover.prove(7_879_316, 7_879_323, state_root).await;
+
+            assert!(
+                matches!(result, Err(AncestryProverError::InvalidStateRoot(ref root)) if root == state_root)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn it_should_reject_targets_not_before_the_anchor() {
+        let mut prover_api = provider::MockProofProvider::new();
+        prover_api.expect_get_state_proof().never();
+        let prover = AncestryProver::new(prover_api);
+
+        let result = prover
+            .prove(
+                7_879_323,
+                7_879_323,
+                "0xa16855f71e99a620029e6b7c683abab542f66ee87c3dd8c72424568348f28b33",
+            )
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(AncestryProverError::TargetNotBeforeAnchor {
+                target: 7_879_323,
+                anchor: 7_879_323
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_override_the_provider_per_call() {
+        let returning = |leaf: Node| {
+            let mut prover_api = provider::MockProofProvider::new();
+            prover_api
+                .expect_get_state_proof()
+                .returning(move |_, gindex| {
+                    Ok(BlockRootsProof::SingleProof {
+                        gindex,
+                        witnesses: vec![],
+                        leaf,
+                    })
+                });
+            prover_api
+        };
+        let prover = AncestryProver::new(returning(Node::repeat_byte(1)));
+        let state_root = "0xa16855f71e99a620029e6b7c683abab542f66ee87c3dd8c72424568348f28b33";
+
+        let options = ProveOptions::new().with_provider(returning(Node::repeat_byte(2)));
+        let overridden = prover
+            .prove_with(7_879_316, 7_879_323, state_root, &options)
+            .await
+            .unwrap();
+        let default = prover
+            .prove_with(7_879_316, 7_879_323, state_root, &ProveOptions::new())
+            .await
+            .unwrap();
+
+        assert!(
+            matches!(overridden, BlockRootsProof::SingleProof { leaf, .. } if leaf == Node::repeat_byte(2))
+        );
+        assert!(
+            matches!(default, BlockRootsProof::SingleProof { leaf, .. } if leaf == Node::repeat_byte(1))
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_retry_transient_errors_per_call() {
+        let timeout = || ProofProviderError::Timeout {
+            url: "url".to_string(),
+            elapsed: Duration::from_secs(1),
+        };
+        let mut prover_api = provider::MockProofProvider::new();
+        let mut sequence = mockall::Sequence::new();
+        prover_api
+            .expect_get_state_proof()
+            .times(2)
+            .in_sequence(&mut sequence)
+            .returning(move |_, _| Err(timeout()));
+        prover_api
+            .expect_get_state_proof()
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|_, gindex| {
+                Ok(BlockRootsProof::SingleProof {
+                    gindex,
+                    witnesses: vec![],
+                    leaf: Node::default(),
+                })
+            });
+        let prover = AncestryProver::new(prover_api);
+        let state_root = "0xa16855f71e99a620029e6b7c683abab542f66ee87c3dd8c72424568348f28b33";
+
+        let result = prover
+            .prove_with(
+                7_879_316,
+                7_879_323,
+                state_root,
+                &ProveOptions::new().with_retries(1),
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(AncestryProverError::ProofProviderError(
+                ProofProviderError::Timeout { .. }
+            ))
+        ));
+
+        let result = prover
+            .prove_with(
+                7_879_316,
+                7_879_323,
+                state_root,
+                &ProveOptions::new().with_retries(1),
+            )
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn it_should_resolve_anchor_descriptors() {
+        use crate::sim::{SimChain, SimProvider};
+
+        let chain = Arc::new(SimChain::new(9_000));
+        let prover = AncestryProver::new(SimProvider::new(chain.clone()));
+        let anchor = |slot| Anchor {
+            slot,
+            state_root: chain.state_root(slot),
+        };
+
+        assert_eq!(prover.resolve_anchor("head").await.unwrap(), anchor(9_000));
+        assert_eq!(prover.resolve_anchor("8000").await.unwrap(), anchor(8_000));
+        let root = StateId::Root(chain.state_root(7_000)).to_string();
+        assert_eq!(prover.resolve_anchor(&root).await.unwrap(), anchor(7_000));
+
+        assert!(matches!(
+            prover.resolve_anchor("latest").await,
+            Err(AncestryProverError::InvalidAnchor(_))
+        ));
+        let unknown = StateId::Root(Node::repeat_byte(1)).to_string();
+        assert!(matches!(
+            prover.resolve_anchor(&unknown).await,
+            Err(AncestryProverError::AnchorStateNotFound { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_tag_anchors_resolved_from_checkpoints() {
+        use crate::sim::{SimChain, SimProvider};
+
+        let chain = Arc::new(SimChain::new(9_000));
+        let prover = AncestryProver::new(SimProvider::new(chain.clone()));
+        let tagged = |slot, checkpoint| TaggedAnchor {
+            anchor: Anchor {
+                slot,
+                state_root: chain.state_root(slot),
+            },
+            checkpoint,
+        };
+
+        let justified = chain.justified_slot();
+        assert!(justified > chain.finalized_slot());
+        assert_eq!(
+            prover.resolve_tagged_anchor("justified").await.unwrap(),
+            tagged(justified, Some(Checkpoint::Justified))
+        );
+        assert_eq!(
+            prover.resolve_tagged_anchor("finalized").await.unwrap(),
+            tagged(chain.finalized_slot(), Some(Checkpoint::Finalized))
+        );
+        assert_eq!(
+            prover.resolve_tagged_anchor("head").await.unwrap(),
+            tagged(9_000, None)
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_prove_against_block_roots() {
+        use crate::header::header_root;
+        use crate::sim::SimChain;
+
+        let chain = SimChain::new(9_000);
+        let header = BeaconBlockHeader {
+            slot: 9_000,
+            proposer_index: 42,
+            parent_root: chain.block_root(8_999),
+            state_root: chain.state_root(9_000),
+            body_root: Node::repeat_byte(7),
+        };
+        let block_root = StateId::Root(header_root(&header)).to_string();
+        let state_root = StateId::Root(header.state_root).to_string();
+
+        let mut provider = provider::MockProofProvider::new();
+        provider
+            .expect_get_block_header()
+            .returning(move |_| Ok(header.clone()));
+        let proof = chain.prove_block_root(8_990, 9_000);
+        provider
+            .expect_get_state_proof()
+            .returning(move |_, _| Ok(proof.clone()));
+        let prover = AncestryProver::new(provider);
+
+        let proof = prover
+            .prove_at_block_root(8_990, 9_000, &block_root)
+            .await
+            .unwrap();
+        assert!(verify_at_block_root(&proof, 8_990, 9_000, &block_root));
+        assert!(!verify_at_block_root(&proof, 8_990, 9_000, &state_root));
+        assert!(!verify(&proof, 8_990, 9_000, &state_root));
+
+        // the header of the recent block is not at the recent slot
+        assert!(matches!(
+            prover.prove_at_block_root(8_990, 9_001, &block_root).await,
+            Err(AncestryProverError::InvalidAnchor(_))
+        ));
+        // malformed block roots are not reported as state roots
+        assert!(matches!(
+            prover.prove_at_block_root(8_990, 9_000, "0x1234").await,
+            Err(AncestryProverError::InvalidAnchor(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_prove_batches_with_one_multiproof() {
+        use crate::sim::SimChain;
+
+        let chain = SimChain::new(9_000);
+        let multiproof = chain
+            .prove_block_roots(&[1_000, 4_321, 8_999], 9_000)
+            .unwrap();
+        let mut provider = provider::MockProofProvider::new();
+        provider
+            .expect_get_state_multiproof()
+            .withf(|_, gindices| gindices.len() == 3)
+            .times(1)
+            .returning(move |_, _| Ok(multiproof.clone()));
+        let prover = AncestryProver::new(provider);
+        let state_root = StateId::Root(chain.state_root(9_000)).to_string();
+
+        let targets = [8_999, 1_000, 8_999, 4_321];
+        let proof = prover
+            .prove_batch(&targets, 9_000, &state_root)
+            .await
+            .unwrap();
+
+        assert!(matches!(proof, BlockRootsProof::CompactProof { .. }));
+        for target_slot in targets {
+            assert!(verify(&proof, target_slot, 9_000, &state_root));
+        }
+        assert!(matches!(
+            prover.prove_batch(&[8_999, 500], 9_000, &state_root).await,
+            Err(AncestryProverError::TargetTooOld { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_prove_ranges_of_slots() {
+        use crate::sim::{SimChain, SimProvider};
+
+        let chain = Arc::new(SimChain::new(9_000));
+        let prover = AncestryProver::new(SimProvider::new(chain.clone()));
+        let state_root = StateId::Root(chain.state_root(9_000)).to_string();
+
+        let proof = prover
+            .prove_range(8_900, 8_999, 9_000, &state_root)
+            .await
+            .unwrap();
+        for target_slot in 8_900..=8_999 {
+            assert!(verify(&proof, target_slot, 9_000, &state_root));
+        }
+
+        assert!(matches!(
+            prover.prove_range(8_999, 8_900, 9_000, &state_root).await,
+            Err(AncestryProverError::ProofProviderError(
+                ProofProviderError::InputError(_)
+            ))
+        ));
+        assert!(matches!(
+            prover.prove_range(8_900, 9_000, 9_000, &state_root).await,
+            Err(AncestryProverError::TargetNotBeforeAnchor { .. })
+        ));
+        // rejected before allocating a target per slot
+        assert!(matches!(
+            prover.prove_range(0, u64::MAX, 9_000, &state_root).await,
+            Err(AncestryProverError::TargetNotBeforeAnchor { .. })
+        ));
+        assert!(matches!(
+            prover.prove_range(0, 8_999, 9_000, &state_root).await,
+            Err(AncestryProverError::TargetTooOld { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_record_the_distances_to_the_anchors() {
+        use crate::sim::{SimChain, SimProvider};
+
+        let chain = Arc::new(SimChain::new(9_000));
+        let stats = StatsRecorder::default();
+        let prover =
+            AncestryProver::new(SimProvider::new(chain.clone())).with_stats_recorder(stats.clone());
+        let state_root = StateId::Root(chain.state_root(9_000)).to_string();
+
+        for target_slot in [8_990, 1_000, 500] {
+            let _ = prover.prove(target_slot, 9_000, &state_root).await;
+        }
+        // invalid requests aren't recorded
+        let _ = prover.prove(9_000, 9_000, &state_root).await;
+
+        let distances = stats.snapshot().distances;
+        assert_eq!(distances.total(), 3);
+        assert_eq!(distances.counts[0], 1);
+        assert_eq!(distances.beyond_window(), 1);
+        assert_eq!(distances.max, 8_500);
+    }
+
+    #[tokio::test]
+    async fn it_should_record_every_distance_of_rejected_batches() {
+        use crate::sim::{SimChain, SimProvider};
+
+        let chain = Arc::new(SimChain::new(9_000));
+        let stats = StatsRecorder::default();
+        let prover =
+            AncestryProver::new(SimProvider::new(chain.clone())).with_stats_recorder(stats.clone());
+        let state_root = StateId::Root(chain.state_root(9_000)).to_string();
+
+        assert!(matches!(
+            prover
+                .prove_batch(&[500, 8_990, 1_000], 9_000, &state_root)
+                .await,
+            Err(AncestryProverError::TargetTooOld { .. })
+        ));
+
+        let distances = stats.snapshot().distances;
+        assert_eq!(distances.total(), 3);
+        assert_eq!(distances.beyond_window(), 2);
+    }
+
+    #[tokio::test]
+    async fn it_should_prove_against_pinned_anchors() {
+        use crate::sim::{SimChain, SimProvider};
+
+        let chain = Arc::new(SimChain::new(9_000));
+        let prover = AncestryProver::new(SimProvider::new(chain.clone()));
+        let anchor = Anchor {
+            slot: 5_000,
+            state_root: chain.state_root(5_000),
+        };
+        let mut lock = AnchorLock::new();
+        lock.pin(4_000, 4_999, anchor, chain.block_root(5_000))
+            .unwrap();
+
+        let (pinned, proof) = prover.prove_pinned(4_500, &lock).await.unwrap();
+        assert_eq!(pinned, anchor);
+        assert_eq!(proof, chain.prove_block_root(4_500, 5_000));
+        assert!(matches!(
+            prover.prove_pinned(5_500, &lock).await,
+            Err(AncestryProverError::NoAcceptedAnchor(5_500))
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_abort_when_the_deadline_is_exceeded() {
+        use crate::sim::{SimChain, SimProvider};
+
+        let chain = Arc::new(SimChain::new(2_000));
+        let anchor = Anchor {
+            slot: 2_000,
+            state_root: chain.state_root(2_000),
+        };
+        let prover = AncestryProver::new(SimProvider::new(chain.clone()));
+        let deadline = || Instant::now() + Duration::from_millis(50);
+
+        let source = || async { Ok(vec![anchor]) };
+        let (_, proof) = prover
+            .prove_with_deadline(1_500, &source, &ProveOptions::new(), deadline())
+            .await
+            .unwrap();
+        assert_eq!(proof, chain.prove_block_root(1_500, 2_000));
+
+        let slow_source = || async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            Ok(vec![anchor])
+        };
+        let result = prover
+            .prove_with_deadline(1_500, &slow_source, &ProveOptions::new(), deadline())
+            .await;
+        assert!(matches!(
+            result,
+            Err(AncestryProverError::DeadlineExceeded(1_500))
+        ));
+
+        // a rate limited backend advising to wait past the deadline isn't retried
+        let mut prover_api = provider::MockProofProvider::new();
+        prover_api
+            .expect_get_state_proof()
+            .times(1)
+            .returning(|_, _| {
+                Err(ProofProviderError::RateLimited {
+                    url: "url".to_string(),
+                    status: 429,
+                    retry_after: Some(Duration::from_secs(60)),
+                })
+            });
+        let prover = AncestryProver::new(prover_api);
+        let started = Instant::now();
+        let result = prover
+            .prove_with_deadline(
+                1_500,
+                &source,
+                &ProveOptions::new().with_retries(3),
+                deadline(),
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(AncestryProverError::ProofProviderError(
+                ProofProviderError::RateLimited { .. }
+            ))
+        ));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn it_should_report_failed_targets_of_batches() {
+        let failing_gindex = block_roots_gindex(7_879_300);
+        let mut prover_api = provider::MockProofProvider::new();
+        prover_api
+            .expect_get_state_proof()
+            .times(3)
+            .returning(move |_, gindex| {
+                if gindex == failing_gindex {
+                    return Err(ProofProviderError::InvalidProofError());
+                }
+                Ok(BlockRootsProof::SingleProof {
+                    gindex,
+                    witnesses: vec![],
+                    leaf: Node::default(),
+                })
+            });
+        let prover = AncestryProver::new(prover_api);
+        let state_root = "0xa16855f71e99a620029e6b7c683abab542f66ee87c3dd8c72424568348f28b33";
+
+        let batch = prover
+            .prove_each(
+                &[7_879_316, 7_879_300, 7_879_323, 7_879_310, 7_879_316],
+                7_879_323,
+                state_root,
+            )
+            .await;
+
+        assert_eq!(
+            batch.ok.iter().map(|(slot, _)| *slot).collect::<Vec<_>>(),
+            vec![7_879_310, 7_879_316]
+        );
+        assert_eq!(batch.failed_slots(), vec![7_879_300, 7_879_323]);
+        assert!(matches!(
+            batch.failed[1].1,
+            AncestryProverError::TargetNotBeforeAnchor { .. }
+        ));
+        assert!(!batch.is_complete());
+
+        // the outcomes line up with the input, repeated targets included
+        let outcomes = batch.outcomes();
+        assert_eq!(outcomes.len(), 5);
+        assert!(matches!(outcomes[0], Some(Ok(_))));
+        assert!(matches!(outcomes[1], Some(Err(_))));
+        assert!(matches!(
+            outcomes[2],
+            Some(Err(AncestryProverError::TargetNotBeforeAnchor { .. }))
+        ));
+        assert!(matches!(outcomes[3], Some(Ok(_))));
+        assert!(matches!(
+            (outcomes[0], outcomes[4]),
+            (Some(Ok(first)), Some(Ok(repeated))) if std::ptr::eq(first, repeated)
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_report_missing_anchor_states() {
+        let mut prover_api = provider::MockProofProvider::new();
+        prover_api
+            .expect_get_state_proof()
+            .returning(|_, _| Err(ProofProviderError::NotFoundError("url".to_string())));
+        let prover = AncestryProver::new(prover_api);
+        let block_root = "0x87a1d2d49e2a4a22a5a8e1c2ce27d3c3b9bd3ec6e6f5fb4bf0e07a3ae0a0c2f5";
+
+        let result = prover.prove(7_879_316, 7_879_323, block_root).await;
+
+        assert!(matches!(
+            result,
+            Err(AncestryProverError::AnchorStateNotFound { ref state_root, .. }) if state_root == block_root
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_detect_block_roots_passed_as_state_roots() {
+        use crate::sim::{SimChain, SimProvider};
+        use std::sync::Arc;
+
+        let chain = Arc::new(SimChain::new(100));
+        let block_root = format!("{:?}", chain.block_root(100));
+        let prover = AncestryProver::new(SimProvider::new(chain.clone()));
+
+        let result = prover.prove(90, 100, &block_root).await;
+        assert!(matches!(
+            result,
+            Err(AncestryProverError::AnchorStateNotFound { .. })
+        ));
+
+        let result = prover
+            .with_anchor_diagnostics(true)
+            .prove(90, 100, &block_root)
+            .await;
+        assert!(matches!(
+            result,
+            Err(AncestryProverError::BlockRootAsStateRoot(ref root)) if *root == block_root
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_prove_adjacent_and_wrapped_around_targets() {
+        use crate::distance::is_overwritten;
+        use crate::sim::{assert_ancestry, SimChain};
+        use std::sync::Arc;
+
+        let window = SLOTS_PER_HISTORICAL_ROOT as u64;
+        let anchor_slot = 2 * window + 10;
+        let chain = Arc::new(SimChain::new(anchor_slot));
+
+        // the smallest distance, and the entries after the anchor's in the ring buffer,
+        // which still hold the roots of the previous period
+        for target_slot in [anchor_slot - 1, window + 11, window + 100, 2 * window - 1] {
+            assert!(!is_overwritten(anchor_slot, target_slot));
+            assert_ancestry(&chain, target_slot, anchor_slot).await;
+        }
+        assert!(is_overwritten(anchor_slot, window + 10));
+        assert!(!is_provable(window + 10, anchor_slot));
+    }
+
+    #[tokio::test]
+    async fn it_should_prove_early_slots_of_new_chains() {
+        use crate::sim::{assert_ancestry, SimChain, SimProvider};
+        use std::sync::Arc;
+
+        let chain = Arc::new(SimChain::new(20));
+        for anchor_slot in [1, 5, 20] {
+            for target_slot in 0..anchor_slot {
+                assert_ancestry(&chain, target_slot, anchor_slot).await;
+            }
+        }
+
+        // the entries of the slots to come are still zero in the state of slot 5
+        let anchor_root = format!("{:?}", chain.state_root(5));
+        let prover = AncestryProver::new(SimProvider::new(chain.clone()));
+        assert!(matches!(
+            prover.prove(7, 5, &anchor_root).await,
+            Err(AncestryProverError::TargetNotBeforeAnchor { .. })
+        ));
+        let unfilled = chain.prove_block_roots(&[7], 5).unwrap();
+        assert!(!verify(&unfilled, 7, 5, &anchor_root));
+        assert!(!verify_strict(&unfilled, 7, 5, &anchor_root));
+        // when the anchor slot is misreported, only strict verification tells the
+        // unfilled entry from a block root
+        assert!(verify(&unfilled, 7, 8, &anchor_root));
+        assert!(!verify_strict(&unfilled, 7, 8, &anchor_root));
+    }
+
+    #[tokio::test]
+    async fn it_should_cross_check_proven_block_roots() {
+        use crate::sim::SimChain;
+        use std::sync::Arc;
+
+        let chain = Arc::new(SimChain::new(100).with_skipped_slots([80]));
+        let state_root = format!("{:?}", chain.state_root(100));
+        let mut prover_api = provider::MockProofProvider::new();
+        let proving_chain = chain.clone();
+        prover_api
+            .expect_get_state_proof()
+            .returning(move |_, gindex| Ok(proving_chain.prove_block_root(gindex % 8192, 100)));
+        let canonical_chain = chain.clone();
+        prover_api
+            .expect_get_block_root()
+            .returning(move |slot| match slot {
+                80 => Err(ProofProviderError::NotFoundError("block 80".to_string())),
+                90 => Ok(Node::repeat_byte(9)),
+                _ => Ok(canonical_chain.block_root(slot)),
+            });
+        prover_api.expect_info().returning(ProviderInfo::default);
+        let prover = AncestryProver::new(prover_api).with_canonical_check(true);
+
+        let result = prover
+            .prove_with_metadata(70, 100, &state_root)
+            .await
+            .unwrap();
+        assert_eq!(result.block_root, chain.block_root(70));
+        assert_eq!(result.canonical_check, CanonicalCheck::Matches);
+
+        let result = prover
+            .prove_with_metadata(90, 100, &state_root)
+            .await
+            .unwrap();
+        assert_eq!(
+            result.canonical_check,
+            CanonicalCheck::Mismatch {
+                canonical_root: Node::repeat_byte(9)
+            }
+        );
+
+        let result = prover
+            .prove_with_metadata(80, 100, &state_root)
+            .await
+            .unwrap();
+        assert!(matches!(
+            result.canonical_check,
+            CanonicalCheck::Unavailable(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_prove_against_an_accepted_anchor() {
+        use crate::sim::{SimChain, SimProvider};
+        use std::sync::Arc;
+
+        let chain = Arc::new(SimChain::new(10_000));
+        let prover = AncestryProver::new(SimProvider::new(chain.clone()));
+        let accepted = [5_000, 9_000, 10_000].map(|slot| Anchor {
+            slot,
+            state_root: chain.state_root(slot),
+        });
+        let source = || async { Ok(accepted.to_vec()) };
+
+        let (anchor, proof) = prover
+            .prove_with_anchor_source(1_500, &source)
+            .await
+            .unwrap();
+        assert_eq!(anchor, accepted[1]);
+        assert!(verify(
+            &proof,
+            1_500,
+            anchor.slot,
+            &StateId::Root(anchor.state_root).to_string()
+        ));
+
+        let result = prover.prove_with_anchor_source(10_000, &source).await;
+        assert!(matches!(
+            result,
+            Err(AncestryProverError::NoAcceptedAnchor(10_000))
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_record_the_provenance_of_proofs() {
+        use crate::sim::SimChain;
+        use crate::LodestarProvider;
+
+        let chain = SimChain::new(100);
+        let BlockRootsProof::CompactProof { descriptor, nodes } =
+            chain.prove_block_roots(&[90], 100).unwrap()
+        else {
+            panic!("Invalid proof type");
+        };
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::path(matches("^/eth/v1/"))).respond_with(
+                status_code(404).body(
+                    serde_json::json!({
+                        "message": "Route GET:/eth/v1/beacon/proof/state/0x... not found",
+                        "error": "Not Found",
+                        "statusCode": 404
+                    })
+                    .to_string(),
+                ),
+            ),
+        );
+        server.expect(
+            Expectation::matching(request::path(matches("^/eth/v0/"))).respond_with(json_encoded(
+                serde_json::json!({
+                    "data": { "leaves": nodes, "descriptor": hex::encode(descriptor) },
+                    "version": "capella"
+                }),
+            )),
+        );
+        let rpc = server.url_str("").trim_end_matches('/').to_string();
+        let prover = AncestryProver::new(LodestarProvider::new(rpc.clone()));
+
+        let result = prover
+            .prove_with_metadata(90, 100, &format!("{:?}", chain.state_root(100)))
+            .await
+            .unwrap();
+
+        assert_eq!(result.provenance.provider.backend, "lodestar");
+        assert_eq!(result.provenance.provider.url, Some(rpc));
+        // the v1 endpoints are tried first
+        assert_eq!(result.provenance.requests, 2);
+        assert_eq!(result.provenance.retries, 0);
+    }
+
+    #[test]
+    fn it_should_not_verify_proofs_for_another_slot() {
+        let target_block = get_test_block_for_slot(7_877_867);
+        let recent_block = get_test_block_for_slot(7_878_867);
+
+        let file = File::open("./src/testdata/state_prover/state_proof_0x044adfafd8b8a889ea689470f630e61dddba22feb705c83eec032fac075de2ec_g308459.json").unwrap();
+        let proof: BlockRootsProof = serde_json::from_reader(file).unwrap();
+
+        assert!(!verify(
+            &proof,
+            target_block.slot - 1,
+            recent_block.slot,
+            recent_block.state_root.to_string().as_str()
+        ));
+        // same block_roots entry, but after the anchor
+        assert!(!verify(
+            &proof,
+            target_block.slot + 8192,
+            recent_block.slot,
+            recent_block.state_root.to_string().as_str()
+        ));
+    }
+
+    #[test]
+    fn it_should_verify_the_expected_target_root() {
+        let target_block = get_test_block_for_slot(7_877_867);
+        let recent_block = get_test_block_for_slot(7_878_867);
+        let state_root = recent_block.state_root.to_string();
+
+        let file = File::open("./src/testdata/state_prover/state_proof_0x044adfafd8b8a889ea689470f630e61dddba22feb705c83eec032fac075de2ec_g308459.json").unwrap();
+        let proof: BlockRootsProof = serde_json::from_reader(file).unwrap();
+        let BlockRootsProof::SingleProof { leaf, .. } = proof else {
+            panic!("Invalid proof type");
+        };
+
+        assert!(verify_expected(
+            &proof,
+            target_block.slot,
+            recent_block.slot,
+            &state_root,
+            &leaf
+        ));
+        assert!(!verify_expected(
+            &proof,
+            target_block.slot,
+            recent_block.slot,
+            &state_root,
+            &Node::repeat_byte(1)
+        ));
+    }
+
     // #[tokio::test]
     // async fn it_should_work_with_state_prover() {
     //     let prover_api = StateProverProvider::new(
@@ -1,30 +1,159 @@
-use crate::errors::ProofProviderError;
+use crate::diff::nodes_by_gindex;
+use crate::errors::{ProofProviderError, VerifyError};
+use crate::merkle::hash_pair;
+use crate::verification::{proof_root, BLOCK_ROOTS_ENTRY_DEPTH, BLOCK_ROOTS_FIELD_GINDEX};
 use alloy_primitives::FixedBytes;
+#[cfg(feature = "providers")]
 use async_trait::async_trait;
+use ethereum_consensus::capella::presets::mainnet::SLOTS_PER_HISTORICAL_ROOT;
+#[cfg(feature = "providers")]
+use ethereum_consensus::capella::BeaconBlockHeader;
 use ethereum_consensus::ssz::prelude::Node;
-use mockall::automock;
-use serde::{Deserialize, Serialize};
-use ssz_rs::compact_multiproofs::verify_compact_merkle_multiproof;
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use ssz_rs::compact_multiproofs::{compute_proof_descriptor, verify_compact_merkle_multiproof};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+#[cfg(feature = "providers")]
+use std::sync::Arc;
 
 // TODO Deserialize
 pub trait Verify: std::fmt::Debug + PartialEq + Serialize + Default + Clone {
-    fn verify(&self, root: FixedBytes<32>) -> bool;
+    fn verify(&self, root: FixedBytes<32>) -> Result<(), VerifyError>;
 }
 
-#[derive(PartialEq, Deserialize, Debug, Serialize, Clone)]
+/// Most witnesses of a single proof of a `block_roots` entry, i.e. the depth of
+/// its gindex in the beacon state.
+pub const MAX_WITNESSES: usize = BLOCK_ROOTS_ENTRY_DEPTH as usize;
+
+/// Most nodes of a compact proof of `block_roots` entries: all the entries, and
+/// the siblings of the path from the `block_roots` vector to the state root.
+pub const MAX_PROOF_NODES: usize =
+    SLOTS_PER_HISTORICAL_ROOT + BLOCK_ROOTS_FIELD_GINDEX.ilog2() as usize;
+
+/// Most bytes of the descriptor of a compact proof, one bit per node of the
+/// proof (`MAX_PROOF_NODES`) and per internal node above them.
+pub const MAX_DESCRIPTOR_LEN: usize = (2 * MAX_PROOF_NODES - 1).div_ceil(8);
+
+/// Deserializes a sequence of at most `MAX` elements, failing as soon as it's
+/// exceeded, so that hostile inputs can't make the deserializer allocate more.
+///
+/// The bound only limits memory when the sequence is read straight from the
+/// input: untagged enums and flattened fields buffer the whole input first.
+pub(crate) fn deserialize_bounded<'de, D, T, const MAX: usize>(
+    deserializer: D,
+) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct BoundedVisitor<T, const MAX: usize>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>, const MAX: usize> Visitor<'de> for BoundedVisitor<T, MAX> {
+        type Value = Vec<T>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "a sequence of at most {} elements", MAX)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<T>, A::Error> {
+            let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(MAX));
+            while let Some(item) = seq.next_element()? {
+                if items.len() == MAX {
+                    return Err(de::Error::invalid_length(MAX + 1, &self));
+                }
+                items.push(item);
+            }
+            Ok(items)
+        }
+    }
+
+    deserializer.deserialize_seq(BoundedVisitor::<T, MAX>(PhantomData))
+}
+
+/// [`deserialize_bounded`] for optional fields.
+fn deserialize_bounded_option<'de, D, T, const MAX: usize>(
+    deserializer: D,
+) -> Result<Option<Vec<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    deserialize_bounded::<D, T, MAX>(deserializer).map(Some)
+}
+
+/// Proof of `block_roots` entries. The number of witnesses, nodes and descriptor
+/// bytes accepted when deserializing is capped by [`MAX_WITNESSES`],
+/// [`MAX_PROOF_NODES`] and [`MAX_DESCRIPTOR_LEN`].
+#[derive(PartialEq, Debug, Serialize, Clone)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(untagged)]
 pub enum BlockRootsProof {
     SingleProof {
         gindex: u64,
+        #[cfg_attr(feature = "schemars", schemars(with = "Vec<crate::schema::HexNode>"))]
         witnesses: Vec<Node>,
+        #[cfg_attr(feature = "schemars", schemars(with = "crate::schema::HexNode"))]
         leaf: Node,
     },
     CompactProof {
         descriptor: Vec<u8>,
+        #[cfg_attr(feature = "schemars", schemars(with = "Vec<crate::schema::HexNode>"))]
         nodes: Vec<Node>,
     },
 }
 
+/// Fields of both shapes of [`BlockRootsProof`]. Deriving `Deserialize` on the
+/// untagged enum would buffer the whole input before trying each variant, so it
+/// is read into this struct instead, whose sequences are bounded as they stream.
+#[derive(Deserialize)]
+struct RawBlockRootsProof {
+    gindex: Option<u64>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_bounded_option::<_, _, MAX_WITNESSES>"
+    )]
+    witnesses: Option<Vec<Node>>,
+    leaf: Option<Node>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_bounded_option::<_, _, MAX_DESCRIPTOR_LEN>"
+    )]
+    descriptor: Option<Vec<u8>>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_bounded_option::<_, _, MAX_PROOF_NODES>"
+    )]
+    nodes: Option<Vec<Node>>,
+}
+
+impl<'de> Deserialize<'de> for BlockRootsProof {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match RawBlockRootsProof::deserialize(deserializer)? {
+            RawBlockRootsProof {
+                gindex: Some(gindex),
+                witnesses: Some(witnesses),
+                leaf: Some(leaf),
+                ..
+            } => Ok(BlockRootsProof::SingleProof {
+                gindex,
+                witnesses,
+                leaf,
+            }),
+            RawBlockRootsProof {
+                descriptor: Some(descriptor),
+                nodes: Some(nodes),
+                ..
+            } => Ok(BlockRootsProof::CompactProof { descriptor, nodes }),
+            _ => Err(de::Error::custom(
+                "expected a single proof (gindex, witnesses, leaf) or a compact proof (descriptor, nodes)",
+            )),
+        }
+    }
+}
+
 impl Default for BlockRootsProof {
     fn default() -> Self {
         BlockRootsProof::SingleProof {
@@ -35,41 +164,636 @@ impl Default for BlockRootsProof {
     }
 }
 
+/// Description of the backend behind a provider, recorded in the provenance of proofs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderInfo {
+    /// Kind of backend, e.g. `lodestar`.
+    pub backend: String,
+    pub url: Option<String>,
+}
+
+impl Default for ProviderInfo {
+    fn default() -> Self {
+        Self {
+            backend: "custom".to_string(),
+            url: None,
+        }
+    }
+}
+
+/// Orders in which backends emit the nodes of a compact proof.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeOrdering {
+    /// Depth-first (left to right) order, as encoded by the descriptor. This is the canonical order.
+    DepthFirst,
+    /// Ascending order of the generalized indices of the nodes.
+    GindexAscending,
+}
+
+/// Returns the generalized indices of the nodes of a compact proof, in the
+/// depth-first order implied by its descriptor.
+pub fn descriptor_gindices(descriptor: &[u8]) -> Result<Vec<u64>, VerifyError> {
+    let bits: Vec<bool> = descriptor
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1))
+        .collect();
+
+    // Each 0 bit is an inner node followed by its two subtrees, each 1 bit is a proof node.
+    let mut gindices = vec![];
+    let mut pending = vec![1u64];
+    let mut position = 0;
+    while let Some(gindex) = pending.pop() {
+        let bit = *bits.get(position).ok_or(VerifyError::InvalidDescriptor)?;
+        position += 1;
+        if bit {
+            gindices.push(gindex);
+        } else {
+            let left = gindex
+                .checked_mul(2)
+                .ok_or(VerifyError::InvalidDescriptor)?;
+            pending.push(left + 1);
+            pending.push(left);
+        }
+    }
+
+    // the remaining bits can only be the zero padding of the last byte
+    let padding = &bits[position..];
+    if padding.len() >= 8 || padding.iter().any(|bit| *bit) {
+        return Err(VerifyError::InvalidDescriptor);
+    }
+
+    Ok(gindices)
+}
+
+/// Merges proofs of subsets of `gindices` against the same root, e.g. fetched in
+/// chunks, into one compact proof of all of them.
+///
+/// Fails with [`VerifyError::InvalidProof`] if the proofs have different roots or
+/// don't reveal enough nodes to prove every gindex.
+pub fn merge_proofs(
+    proofs: &[BlockRootsProof],
+    gindices: &[u64],
+) -> Result<BlockRootsProof, VerifyError> {
+    let mut known = BTreeMap::new();
+    let mut root = None;
+    for proof in proofs {
+        let proof_root = proof_root(proof)?;
+        if *root.get_or_insert(proof_root) != proof_root {
+            return Err(VerifyError::InvalidProof);
+        }
+        known.extend(nodes_by_gindex(proof)?);
+    }
+
+    let mut sorted = gindices.iter().map(|g| *g as usize).collect::<Vec<_>>();
+    sorted.sort_unstable();
+    sorted.dedup();
+    let descriptor =
+        compute_proof_descriptor(&sorted).map_err(|_| VerifyError::InvalidDescriptor)?;
+    let nodes = descriptor_gindices(&descriptor)?
+        .into_iter()
+        .map(|gindex| node_at(&known, gindex))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(VerifyError::InvalidProof)?;
+
+    Ok(BlockRootsProof::CompactProof { descriptor, nodes })
+}
+
+/// Returns the node at `gindex`, known or hashed from known descendants.
+fn node_at(known: &BTreeMap<u64, Node>, gindex: u64) -> Option<Node> {
+    if let Some(node) = known.get(&gindex) {
+        return Some(*node);
+    }
+    if gindex > *known.keys().next_back()? / 2 {
+        return None;
+    }
+    Some(hash_pair(
+        &node_at(known, 2 * gindex)?,
+        &node_at(known, 2 * gindex + 1)?,
+    ))
+}
+
+impl BlockRootsProof {
+    /// Reorders the nodes of a compact proof, emitted by a backend in the given
+    /// `ordering`, into the canonical depth-first order expected by verification.
+    pub fn normalize(&mut self, ordering: NodeOrdering) -> Result<(), VerifyError> {
+        let BlockRootsProof::CompactProof { descriptor, nodes } = self else {
+            return Ok(());
+        };
+
+        let gindices = checked_descriptor_gindices(descriptor, nodes)?;
+        if ordering == NodeOrdering::GindexAscending {
+            *nodes = from_gindex_ascending(&gindices, nodes);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the generalized indices of the nodes revealed by the proof.
+    pub fn gindices(&self) -> Result<Vec<u64>, VerifyError> {
+        match self {
+            BlockRootsProof::SingleProof { gindex, .. } => Ok(vec![*gindex]),
+            BlockRootsProof::CompactProof { descriptor, .. } => descriptor_gindices(descriptor),
+        }
+    }
+
+    /// Checks that a compact proof is laid out canonically: a well-formed descriptor
+    /// with one node per position, in the depth-first order of the descriptor's
+    /// gindices. Single proofs are always normalized.
+    ///
+    /// Nodes don't carry their gindices, so a proof listing them in another order
+    /// (e.g. [`NodeOrdering::GindexAscending`]) can only be told apart against its
+    /// root, where verification fails with [`VerifyError::NonCanonicalOrder`].
+    pub fn is_normalized(&self) -> bool {
+        match self {
+            BlockRootsProof::SingleProof { .. } => true,
+            BlockRootsProof::CompactProof { descriptor, nodes } => {
+                checked_descriptor_gindices(descriptor, nodes).is_ok()
+            }
+        }
+    }
+}
+
+/// Returns the gindices of the descriptor of a compact proof, checking that there
+/// is one node per gindex.
+fn checked_descriptor_gindices(descriptor: &[u8], nodes: &[Node]) -> Result<Vec<u64>, VerifyError> {
+    let gindices = descriptor_gindices(descriptor)?;
+    if gindices.len() != nodes.len() {
+        return Err(VerifyError::InvalidNodeCount {
+            expected: gindices.len(),
+            actual: nodes.len(),
+        });
+    }
+
+    Ok(gindices)
+}
+
+/// Reorders `nodes`, listed in ascending order of their gindices, into the
+/// depth-first order of `gindices`.
+fn from_gindex_ascending(gindices: &[u64], nodes: &[Node]) -> Vec<Node> {
+    let mut sorted = gindices.to_vec();
+    sorted.sort_unstable();
+    gindices
+        .iter()
+        .map(|gindex| nodes[sorted.binary_search(gindex).unwrap()])
+        .collect()
+}
+
 impl Verify for BlockRootsProof {
-    fn verify(&self, root: FixedBytes<32>) -> bool {
+    fn verify(&self, root: FixedBytes<32>) -> Result<(), VerifyError> {
         match self {
             BlockRootsProof::SingleProof {
                 gindex,
                 witnesses,
                 leaf,
             } => {
+                if *gindex == 0 {
+                    return Err(VerifyError::InvalidGindex(*gindex));
+                }
+
+                // the depth of a node is the number of bits after the leading 1 of its gindex
+                let depth = (63 - gindex.leading_zeros()) as usize;
+                if witnesses.len() != depth {
+                    return Err(VerifyError::InvalidBranchLength {
+                        gindex: *gindex,
+                        expected: depth,
+                        actual: witnesses.len(),
+                    });
+                }
+
                 let merkle_proof = ssz_rs::proofs::Proof {
-                    leaf: leaf.clone(),
+                    leaf: *leaf,
                     index: *gindex as usize,
                     branch: witnesses.clone(),
                 };
-                match merkle_proof.verify(root) {
-                    Ok(_) => true,
-                    Err(_) => false,
-                }
+                merkle_proof
+                    .verify(root)
+                    .map_err(|_| VerifyError::InvalidProof)
             }
             BlockRootsProof::CompactProof { descriptor, nodes } => {
-                match verify_compact_merkle_multiproof(nodes, descriptor, root) {
-                    Ok(_) => true,
-                    Err(_) => false,
+                let gindices = checked_descriptor_gindices(descriptor, nodes)?;
+                if verify_compact_merkle_multiproof(nodes, descriptor, root).is_ok() {
+                    return Ok(());
+                }
+
+                // tell proofs of the root in another order from invalid ones
+                let reordered = from_gindex_ascending(&gindices, nodes);
+                if verify_compact_merkle_multiproof(&reordered, descriptor, root).is_ok() {
+                    return Err(VerifyError::NonCanonicalOrder(
+                        NodeOrdering::GindexAscending,
+                    ));
                 }
+                Err(VerifyError::InvalidProof)
             }
         }
     }
 }
 
-#[automock]
+/// Identifier of the beacon state to fetch proofs from, as in the beacon API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StateId {
+    Root(Node),
+    Slot(u64),
+    Head,
+    Finalized,
+    Justified,
+}
+
+impl fmt::Display for StateId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateId::Root(root) => write!(f, "0x{}", hex::encode(root)),
+            StateId::Slot(slot) => write!(f, "{}", slot),
+            StateId::Head => write!(f, "head"),
+            StateId::Finalized => write!(f, "finalized"),
+            StateId::Justified => write!(f, "justified"),
+        }
+    }
+}
+
+impl FromStr for StateId {
+    type Err = ProofProviderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "head" => Ok(StateId::Head),
+            "finalized" => Ok(StateId::Finalized),
+            "justified" => Ok(StateId::Justified),
+            _ if s.starts_with("0x") => Node::from_str(s)
+                .map(StateId::Root)
+                .map_err(|_| ProofProviderError::InputError(format!("Invalid state root {}", s))),
+            _ => s
+                .parse()
+                .map(StateId::Slot)
+                .map_err(|_| ProofProviderError::InputError(format!("Invalid state id {}", s))),
+        }
+    }
+}
+
+impl From<Node> for StateId {
+    fn from(root: Node) -> Self {
+        StateId::Root(root)
+    }
+}
+
+impl From<u64> for StateId {
+    fn from(slot: u64) -> Self {
+        StateId::Slot(slot)
+    }
+}
+
+#[cfg(feature = "providers")]
+#[cfg_attr(any(test, feature = "test-utils"), mockall::automock)]
 #[async_trait]
 pub trait ProofProvider: Sync + Send + 'static {
-    /// Fetches a proof from a specific g_index or a path to the beacon state of a specific block.
+    /// Fetches a proof from a specific g_index or a path to the beacon state identified by `state_id`.
     async fn get_state_proof(
         &self,
-        state_id: &str,
+        state_id: &StateId,
         gindex: u64,
     ) -> Result<BlockRootsProof, ProofProviderError>;
+
+    /// Fetches a single (compact multi)proof of several gindices of the beacon state
+    /// identified by `state_id`, so that their witnesses are shared.
+    async fn get_state_multiproof(
+        &self,
+        state_id: &StateId,
+        gindices: &[u64],
+    ) -> Result<BlockRootsProof, ProofProviderError> {
+        match gindices {
+            [gindex] => self.get_state_proof(state_id, *gindex).await,
+            _ => Err(ProofProviderError::NotSupportedError(format!(
+                "get_state_multiproof for state {}",
+                state_id
+            ))),
+        }
+    }
+
+    /// Fetches the `block_roots` vector of the beacon state of a specific block.
+    ///
+    /// The leaves are only checked against the state when `state_id` is a
+    /// [`StateId::Root`], otherwise they are as unverified as the node serving them.
+    async fn get_block_roots(&self, state_id: &StateId) -> Result<Vec<Node>, ProofProviderError> {
+        Err(ProofProviderError::NotSupportedError(format!(
+            "get_block_roots for state {}",
+            state_id
+        )))
+    }
+
+    /// Fetches the root of the canonical block of `slot`, e.g. to cross-check the
+    /// block roots proven by another provider.
+    async fn get_block_root(&self, slot: u64) -> Result<Node, ProofProviderError> {
+        Err(ProofProviderError::NotSupportedError(format!(
+            "get_block_root for slot {}",
+            slot
+        )))
+    }
+
+    /// Describes the backend of the provider.
+    fn info(&self) -> ProviderInfo {
+        ProviderInfo::default()
+    }
+
+    /// Checks whether `root` is the root of a known block, e.g. to tell apart a
+    /// block root mistakenly passed as a state root.
+    async fn is_block_root(&self, root: &Node) -> Result<bool, ProofProviderError> {
+        Err(ProofProviderError::NotSupportedError(format!(
+            "is_block_root for root {}",
+            StateId::Root(*root)
+        )))
+    }
+
+    /// Fetches the header of the block with root `block_root`, e.g. to anchor proofs
+    /// at the block root instead of its state root.
+    async fn get_block_header(
+        &self,
+        block_root: &Node,
+    ) -> Result<BeaconBlockHeader, ProofProviderError> {
+        Err(ProofProviderError::NotSupportedError(format!(
+            "get_block_header for block {}",
+            StateId::Root(*block_root)
+        )))
+    }
+}
+
+#[cfg(feature = "providers")]
+#[async_trait]
+impl<P: ProofProvider + ?Sized> ProofProvider for Box<P> {
+    async fn get_state_proof(
+        &self,
+        state_id: &StateId,
+        gindex: u64,
+    ) -> Result<BlockRootsProof, ProofProviderError> {
+        (**self).get_state_proof(state_id, gindex).await
+    }
+
+    async fn get_state_multiproof(
+        &self,
+        state_id: &StateId,
+        gindices: &[u64],
+    ) -> Result<BlockRootsProof, ProofProviderError> {
+        (**self).get_state_multiproof(state_id, gindices).await
+    }
+
+    async fn get_block_roots(&self, state_id: &StateId) -> Result<Vec<Node>, ProofProviderError> {
+        (**self).get_block_roots(state_id).await
+    }
+
+    async fn get_block_root(&self, slot: u64) -> Result<Node, ProofProviderError> {
+        (**self).get_block_root(slot).await
+    }
+
+    fn info(&self) -> ProviderInfo {
+        (**self).info()
+    }
+
+    async fn is_block_root(&self, root: &Node) -> Result<bool, ProofProviderError> {
+        (**self).is_block_root(root).await
+    }
+
+    async fn get_block_header(
+        &self,
+        block_root: &Node,
+    ) -> Result<BeaconBlockHeader, ProofProviderError> {
+        (**self).get_block_header(block_root).await
+    }
+}
+
+#[cfg(feature = "providers")]
+#[async_trait]
+impl<P: ProofProvider + ?Sized> ProofProvider for Arc<P> {
+    async fn get_state_proof(
+        &self,
+        state_id: &StateId,
+        gindex: u64,
+    ) -> Result<BlockRootsProof, ProofProviderError> {
+        (**self).get_state_proof(state_id, gindex).await
+    }
+
+    async fn get_state_multiproof(
+        &self,
+        state_id: &StateId,
+        gindices: &[u64],
+    ) -> Result<BlockRootsProof, ProofProviderError> {
+        (**self).get_state_multiproof(state_id, gindices).await
+    }
+
+    async fn get_block_roots(&self, state_id: &StateId) -> Result<Vec<Node>, ProofProviderError> {
+        (**self).get_block_roots(state_id).await
+    }
+
+    async fn get_block_root(&self, slot: u64) -> Result<Node, ProofProviderError> {
+        (**self).get_block_root(slot).await
+    }
+
+    fn info(&self) -> ProviderInfo {
+        (**self).info()
+    }
+
+    async fn is_block_root(&self, root: &Node) -> Result<bool, ProofProviderError> {
+        (**self).is_block_root(root).await
+    }
+
+    async fn get_block_header(
+        &self,
+        block_root: &Node,
+    ) -> Result<BeaconBlockHeader, ProofProviderError> {
+        (**self).get_block_header(block_root).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ssz_rs::compact_multiproofs::compute_proof_descriptor;
+
+    #[test]
+    fn it_should_reject_single_proofs_with_wrong_branch_length() {
+        let proof = BlockRootsProof::SingleProof {
+            gindex: 309_908,
+            witnesses: vec![Node::default(); 17],
+            leaf: Node::default(),
+        };
+
+        assert_eq!(
+            proof.verify(Node::default()),
+            Err(VerifyError::InvalidBranchLength {
+                gindex: 309_908,
+                expected: 18,
+                actual: 17,
+            })
+        );
+    }
+
+    #[test]
+    fn it_should_cap_the_size_of_deserialized_proofs() {
+        let single = |witnesses: usize| {
+            serde_json::to_string(&BlockRootsProof::SingleProof {
+                gindex: 309_908,
+                witnesses: vec![Node::default(); witnesses],
+                leaf: Node::default(),
+            })
+            .unwrap()
+        };
+        let compact = |nodes: usize| {
+            serde_json::to_string(&BlockRootsProof::CompactProof {
+                descriptor: vec![0x80],
+                nodes: vec![Node::default(); nodes],
+            })
+            .unwrap()
+        };
+
+        assert_eq!(MAX_WITNESSES, 18);
+        assert!(serde_json::from_str::<BlockRootsProof>(&single(MAX_WITNESSES)).is_ok());
+        assert!(serde_json::from_str::<BlockRootsProof>(&single(MAX_WITNESSES + 1)).is_err());
+        assert!(serde_json::from_str::<BlockRootsProof>(&compact(MAX_PROOF_NODES)).is_ok());
+        assert!(serde_json::from_str::<BlockRootsProof>(&compact(MAX_PROOF_NODES + 1)).is_err());
+    }
+
+    /// Reader of a single proof whose `witnesses` never end, counting the bytes read.
+    struct EndlessWitnesses {
+        bytes: Box<dyn Iterator<Item = u8>>,
+        read: usize,
+    }
+
+    impl std::io::Read for EndlessWitnesses {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let mut read = 0;
+            for (slot, byte) in buf.iter_mut().zip(&mut self.bytes) {
+                *slot = byte;
+                read += 1;
+            }
+            self.read += read;
+            Ok(read)
+        }
+    }
+
+    #[test]
+    fn it_should_reject_oversized_proofs_without_buffering_them() {
+        let node = serde_json::to_string(&Node::default()).unwrap();
+        let head = format!(r#"{{"gindex":309908,"leaf":{},"witnesses":["#, node);
+        let witness = format!("{},", node);
+        let mut reader = EndlessWitnesses {
+            bytes: Box::new(
+                head.clone()
+                    .into_bytes()
+                    .into_iter()
+                    .chain(witness.clone().into_bytes().into_iter().cycle()),
+            ),
+            read: 0,
+        };
+
+        // buffering the input would never end
+        assert!(serde_json::from_reader::<_, BlockRootsProof>(&mut reader).is_err());
+        assert!(reader.read <= head.len() + (MAX_WITNESSES + 1) * witness.len());
+    }
+
+    #[test]
+    fn it_should_reject_proofs_missing_fields() {
+        for json in [
+            r#"{"gindex":1,"witnesses":[]}"#,
+            r#"{"descriptor":[128]}"#,
+            r#"{}"#,
+        ] {
+            assert!(serde_json::from_str::<BlockRootsProof>(json).is_err());
+        }
+    }
+
+    #[test]
+    fn it_should_reject_single_proofs_with_zero_gindex() {
+        assert_eq!(
+            BlockRootsProof::default().verify(Node::default()),
+            Err(VerifyError::InvalidGindex(0))
+        );
+    }
+
+    #[test]
+    fn it_should_compute_descriptor_gindices_in_depth_first_order() {
+        let descriptor = compute_proof_descriptor(&[309_908]).unwrap();
+        let gindices = descriptor_gindices(&descriptor).unwrap();
+
+        assert_eq!(gindices.len(), 19);
+        assert!(gindices.contains(&309_908));
+        // the leftmost node is 8, sibling of the leaf's ancestor 9
+        assert_eq!(gindices[0], 8);
+    }
+
+    #[test]
+    fn it_should_normalize_gindex_ordered_compact_proofs() {
+        use crate::merkle::BlockRootsTree;
+
+        let tree = BlockRootsTree::new(
+            &(0..16u64)
+                .map(|leaf| Node::from(alloy_primitives::U256::from(leaf)))
+                .collect::<Vec<_>>(),
+        );
+        let root = FixedBytes(tree.root().0);
+        let descriptor = compute_proof_descriptor(&[16, 18, 29]).unwrap();
+        let gindices = descriptor_gindices(&descriptor).unwrap();
+        let canonical = BlockRootsProof::CompactProof {
+            descriptor: descriptor.clone(),
+            nodes: gindices
+                .iter()
+                .map(|gindex| tree.node(*gindex).unwrap())
+                .collect(),
+        };
+
+        let mut sorted = gindices.clone();
+        sorted.sort_unstable();
+        let mut proof = BlockRootsProof::CompactProof {
+            descriptor,
+            nodes: sorted
+                .iter()
+                .map(|gindex| tree.node(*gindex).unwrap())
+                .collect(),
+        };
+        assert_ne!(proof, canonical);
+        assert_eq!(
+            proof.verify(root),
+            Err(VerifyError::NonCanonicalOrder(
+                NodeOrdering::GindexAscending
+            ))
+        );
+
+        proof.normalize(NodeOrdering::GindexAscending).unwrap();
+
+        assert_eq!(proof, canonical);
+        assert!(proof.is_normalized());
+        assert_eq!(proof.verify(root), Ok(()));
+    }
+
+    #[test]
+    fn it_should_reject_compact_proofs_with_wrong_node_count() {
+        let mut proof = BlockRootsProof::CompactProof {
+            descriptor: compute_proof_descriptor(&[309_908]).unwrap(),
+            nodes: vec![Node::default(); 3],
+        };
+
+        assert!(!proof.is_normalized());
+        assert_eq!(
+            proof.verify(Node::default()),
+            Err(VerifyError::InvalidNodeCount {
+                expected: 19,
+                actual: 3
+            })
+        );
+        assert_eq!(
+            proof.normalize(NodeOrdering::DepthFirst),
+            Err(VerifyError::InvalidNodeCount {
+                expected: 19,
+                actual: 3
+            })
+        );
+    }
+
+    #[test]
+    fn it_should_encode_state_ids_as_in_the_beacon_api() {
+        let root = "0xa16855f71e99a620029e6b7c683abab542f66ee87c3dd8c72424568348f28b33";
+
+        for state_id in [root, "7864320", "head", "finalized", "justified"] {
+            assert_eq!(StateId::from_str(state_id).unwrap().to_string(), state_id);
+        }
+        assert_eq!(StateId::from(7_864_320), StateId::Slot(7_864_320));
+        assert!(StateId::from_str("0x1234").is_err());
+        assert!(StateId::from_str("latest").is_err());
+    }
 }
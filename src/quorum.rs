@@ -0,0 +1,185 @@
+//! Provider requiring several independent backends to agree on a proof, for
+//! high-assurance deployments that don't trust a single beacon endpoint.
+
+use crate::errors::ProofProviderError;
+use crate::provider::{BlockRootsProof, ProofProvider, ProviderInfo, StateId, Verify};
+use crate::verification::proven_leaf;
+use alloy_primitives::FixedBytes;
+use async_trait::async_trait;
+use ethereum_consensus::ssz::prelude::Node;
+use futures::future::join_all;
+
+/// Provider querying all its backends, and returning a proof only when at least
+/// `threshold` of them return proofs of the same leaves.
+///
+/// Proofs of states identified by root are verified against it before being
+/// counted, so a backend serving another state can't take part in the quorum.
+pub struct QuorumProvider {
+    threshold: usize,
+    providers: Vec<Box<dyn ProofProvider>>,
+}
+
+impl QuorumProvider {
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            providers: Vec::new(),
+        }
+    }
+
+    /// Adds a backend to the quorum.
+    pub fn with_provider<P: ProofProvider>(mut self, provider: P) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+
+    async fn quorum(
+        &self,
+        state_id: &StateId,
+        gindices: &[u64],
+    ) -> Result<BlockRootsProof, ProofProviderError> {
+        let results = join_all(self.providers.iter().map(|provider| async move {
+            match gindices {
+                [gindex] => provider.get_state_proof(state_id, *gindex).await,
+                _ => provider.get_state_multiproof(state_id, gindices).await,
+            }
+        }))
+        .await;
+
+        // proofs grouped by their proven leaves, with the number of backends agreeing
+        let mut groups: Vec<(Vec<Node>, BlockRootsProof, usize)> = Vec::new();
+        for proof in results.into_iter().filter_map(Result::ok) {
+            if let StateId::Root(root) = state_id {
+                if proof.verify(FixedBytes(root.0)).is_err() {
+                    continue;
+                }
+            }
+            let Ok(leaves) = gindices
+                .iter()
+                .map(|gindex| proven_leaf(&proof, *gindex))
+                .collect::<Result<Vec<_>, _>>()
+            else {
+                continue;
+            };
+
+            match groups.iter_mut().find(|(group, ..)| *group == leaves) {
+                Some((.., count)) => *count += 1,
+                None => groups.push((leaves, proof, 1)),
+            }
+        }
+
+        let agreeing = groups.iter().map(|(.., count)| *count).max().unwrap_or(0);
+        match groups
+            .into_iter()
+            .find(|(.., count)| *count >= self.threshold)
+        {
+            Some((_, proof, _)) if self.threshold > 0 => Ok(proof),
+            _ => Err(ProofProviderError::NoQuorum {
+                required: self.threshold,
+                agreeing,
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl ProofProvider for QuorumProvider {
+    async fn get_state_proof(
+        &self,
+        state_id: &StateId,
+        gindex: u64,
+    ) -> Result<BlockRootsProof, ProofProviderError> {
+        self.quorum(state_id, &[gindex]).await
+    }
+
+    async fn get_state_multiproof(
+        &self,
+        state_id: &StateId,
+        gindices: &[u64],
+    ) -> Result<BlockRootsProof, ProofProviderError> {
+        self.quorum(state_id, gindices).await
+    }
+
+    fn info(&self) -> ProviderInfo {
+        ProviderInfo {
+            backend: format!("quorum({}/{})", self.threshold, self.providers.len()),
+            url: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockProofProvider;
+    use crate::sim::{SimChain, SimProvider};
+    use std::sync::Arc;
+
+    fn provider_returning(leaf: Node) -> MockProofProvider {
+        let mut provider = MockProofProvider::new();
+        provider
+            .expect_get_state_proof()
+            .returning(move |_, gindex| {
+                Ok(BlockRootsProof::SingleProof {
+                    gindex,
+                    witnesses: vec![],
+                    leaf,
+                })
+            });
+        provider
+    }
+
+    fn quorum(threshold: usize) -> QuorumProvider {
+        QuorumProvider::new(threshold)
+            .with_provider(provider_returning(Node::repeat_byte(1)))
+            .with_provider(provider_returning(Node::repeat_byte(2)))
+            .with_provider(provider_returning(Node::repeat_byte(1)))
+    }
+
+    #[tokio::test]
+    async fn it_should_return_proofs_agreed_on_by_the_threshold() {
+        let proof = quorum(2)
+            .get_state_proof(&StateId::Head, 309_908)
+            .await
+            .unwrap();
+        assert_eq!(proven_leaf(&proof, 309_908), Ok(Node::repeat_byte(1)));
+
+        let result = quorum(3).get_state_proof(&StateId::Head, 309_908).await;
+        assert!(matches!(
+            result,
+            Err(ProofProviderError::NoQuorum {
+                required: 3,
+                agreeing: 2
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_not_count_proofs_of_other_states() {
+        let chain = Arc::new(SimChain::new(200));
+        let forged = Arc::new(SimChain::new(200).with_seed(1));
+        let state_id = StateId::Root(chain.state_root(200));
+        let gindex = crate::prover::block_roots_gindex(150);
+
+        // a backend serving the proof of another state for the requested root
+        let forged_proof = forged.prove_block_root(150, 200);
+        let mut forging = MockProofProvider::new();
+        forging
+            .expect_get_state_proof()
+            .returning(move |_, _| Ok(forged_proof.clone()));
+
+        let provider = QuorumProvider::new(2)
+            .with_provider(SimProvider::new(chain.clone()))
+            .with_provider(forging);
+
+        let result = provider.get_state_proof(&state_id, gindex).await;
+
+        assert!(matches!(
+            result,
+            Err(ProofProviderError::NoQuorum {
+                required: 2,
+                agreeing: 1
+            })
+        ));
+    }
+}
@@ -0,0 +1,157 @@
+use crate::client::shared_client;
+use crate::errors::AncestryProverError;
+use crate::lodestar_provider::LodestarProvider;
+use crate::prover::AncestryProver;
+use crate::provider::{BlockRootsProof, ProofProvider};
+use crate::state_prover_provider::StateProverProvider;
+use reqwest::Client;
+use std::collections::BTreeMap;
+
+/// Holds provers for several networks (e.g. mainnet, gnosis, sepolia) and
+/// routes proof requests to the prover of the requested network.
+///
+/// Providers added through `add_lodestar` and `add_state_prover` share the
+/// HTTP client of the registry, which defaults to the crate-level shared client.
+pub struct ProverRegistry {
+    client: Client,
+    provers: BTreeMap<String, AncestryProver<Box<dyn ProofProvider>>>,
+}
+
+impl Default for ProverRegistry {
+    fn default() -> Self {
+        Self::with_client(shared_client())
+    }
+}
+
+impl ProverRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a registry whose HTTP providers use the given client.
+    pub fn with_client(client: Client) -> Self {
+        Self {
+            client,
+            provers: BTreeMap::new(),
+        }
+    }
+
+    /// Registers a prover for `network` backed by a custom provider, replacing any previous one.
+    pub fn register<P: ProofProvider>(&mut self, network: &str, provider: P) -> &mut Self {
+        let provider: Box<dyn ProofProvider> = Box::new(provider);
+        self.provers
+            .insert(network.to_string(), AncestryProver::new(provider));
+        self
+    }
+
+    /// Registers a `LodestarProvider` for `network`.
+    pub fn add_lodestar(&mut self, network: &str, rpc: String) -> &mut Self {
+        let provider = LodestarProvider::with_client(rpc, self.client.clone());
+        self.register(network, provider)
+    }
+
+    /// Registers a `StateProverProvider` for `network`.
+    pub fn add_state_prover(&mut self, network: &str, rpc: String) -> &mut Self {
+        let provider =
+            StateProverProvider::with_client(network.to_string(), rpc, self.client.clone());
+        self.register(network, provider)
+    }
+
+    pub fn get(&self, network: &str) -> Option<&AncestryProver<Box<dyn ProofProvider>>> {
+        self.provers.get(network)
+    }
+
+    /// Returns the registered networks, in alphabetical order.
+    pub fn networks(&self) -> impl Iterator<Item = &str> {
+        self.provers.keys().map(String::as_str)
+    }
+
+    pub async fn prove(
+        &self,
+        network: &str,
+        target_block_slot: u64,
+        recent_block_slot: u64,
+        recent_block_state_root: &str,
+    ) -> Result<BlockRootsProof, AncestryProverError> {
+        let prover = self
+            .get(network)
+            .ok_or_else(|| AncestryProverError::UnknownNetwork(network.to_string()))?;
+
+        prover
+            .prove(
+                target_block_slot,
+                recent_block_slot,
+                recent_block_state_root,
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockProofProvider;
+    use ethereum_consensus::ssz::prelude::Node;
+
+    const STATE_ROOT: &str = "0xa16855f71e99a620029e6b7c683abab542f66ee87c3dd8c72424568348f28b33";
+
+    fn provider_returning(leaf: Node) -> MockProofProvider {
+        let mut provider = MockProofProvider::new();
+        provider
+            .expect_get_state_proof()
+            .returning(move |_, gindex| {
+                Ok(BlockRootsProof::SingleProof {
+                    gindex,
+                    witnesses: vec![],
+                    leaf,
+                })
+            });
+        provider
+    }
+
+    #[tokio::test]
+    async fn it_should_route_proofs_to_the_network_prover() {
+        let mut registry = ProverRegistry::new();
+        registry
+            .register("mainnet", provider_returning(Node::repeat_byte(1)))
+            .register("sepolia", provider_returning(Node::repeat_byte(2)));
+
+        let proof = registry
+            .prove("sepolia", 7_879_316, 7_879_323, STATE_ROOT)
+            .await
+            .unwrap();
+
+        match proof {
+            BlockRootsProof::SingleProof { leaf, .. } => assert_eq!(leaf, Node::repeat_byte(2)),
+            _ => panic!("Invalid proof type"),
+        }
+    }
+
+    #[test]
+    fn it_should_list_networks_in_order() {
+        let mut registry = ProverRegistry::new();
+        registry.add_lodestar("sepolia", "http://localhost".to_string());
+        registry.add_lodestar("gnosis", "http://localhost".to_string());
+        registry.add_lodestar("mainnet", "http://localhost".to_string());
+
+        assert_eq!(
+            registry.networks().collect::<Vec<_>>(),
+            vec!["gnosis", "mainnet", "sepolia"]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_fail_for_unknown_networks() {
+        let mut registry = ProverRegistry::new();
+        registry.add_lodestar("mainnet", "http://localhost".to_string());
+
+        let result = registry
+            .prove("gnosis", 7_879_316, 7_879_323, STATE_ROOT)
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(AncestryProverError::UnknownNetwork(network)) if network == "gnosis"
+        ));
+    }
+}
@@ -0,0 +1,64 @@
+//! JSON Schema generation for the proof types, enabled with the `schemars` feature.
+
+use crate::provider::BlockRootsProof;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject, StringValidation};
+use schemars::JsonSchema;
+
+/// Schema of a 0x-prefixed, hex encoded 32 byte merkle node.
+pub(crate) struct HexNode;
+
+impl JsonSchema for HexNode {
+    fn schema_name() -> String {
+        "Node".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some("^0x[0-9a-fA-F]{64}$".to_string()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// Returns the JSON Schema of `BlockRootsProof`, covering all proof variants.
+pub fn proof_schema() -> RootSchema {
+    schemars::schema_for!(BlockRootsProof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_consensus::ssz::prelude::Node;
+
+    #[test]
+    fn it_should_generate_schema_for_all_proof_variants() {
+        let schema = serde_json::to_value(proof_schema()).unwrap();
+        let variants = schema["anyOf"].as_array().unwrap();
+
+        assert_eq!(variants.len(), 2);
+        assert!(variants[0]["required"]
+            .as_array()
+            .unwrap()
+            .contains(&"witnesses".into()));
+        assert!(variants[1]["required"]
+            .as_array()
+            .unwrap()
+            .contains(&"descriptor".into()));
+    }
+
+    #[test]
+    fn it_should_describe_nodes_as_hex_strings() {
+        let schema = serde_json::to_value(proof_schema()).unwrap();
+        let pattern = schema["definitions"]["Node"]["pattern"].as_str().unwrap();
+
+        let node = serde_json::to_value(Node::default()).unwrap();
+        assert_eq!(pattern, "^0x[0-9a-fA-F]{64}$");
+        assert_eq!(node.as_str().unwrap().len(), 66);
+    }
+}
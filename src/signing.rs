@@ -0,0 +1,176 @@
+//! Detached signatures of the artifacts produced by the prover (proofs, bundles, ...),
+//! so that the queues relaying them from the prover to the submitter can't tamper
+//! with them undetected.
+//!
+//! Signatures are over the [canonical JSON](crate::canonical) of the artifact, so
+//! they survive re-encodings of the artifact by intermediate hops.
+
+use crate::canonical::to_canonical_json;
+use crate::errors::VerifyError;
+use hmac::{Hmac, Mac};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::Sha256;
+
+/// Prefix of the signed messages, so that signatures of artifacts can't be replayed
+/// as signatures of other messages by the same key.
+const DOMAIN: &[u8] = b"ancestry-prover/envelope/v1\n";
+
+/// Signer of artifacts, e.g. backed by a KMS or an HSM.
+pub trait EnvelopeSigner {
+    /// Identifier of the signing key, recorded in the signatures.
+    fn key_id(&self) -> &str;
+
+    fn sign(&self, message: &[u8]) -> Vec<u8>;
+}
+
+/// Verifier of the signatures of artifacts.
+pub trait EnvelopeVerifier {
+    /// Returns whether `signature` is a valid signature of `message` by the key `key_id`.
+    fn verify(&self, key_id: &str, message: &[u8], signature: &[u8]) -> bool;
+}
+
+/// Detached signature of an artifact.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvelopeSignature {
+    pub key_id: String,
+    #[serde(serialize_with = "serialize_hex", deserialize_with = "deserialize_hex")]
+    pub signature: Vec<u8>,
+}
+
+/// Signs the canonical JSON of `envelope`.
+pub fn sign_envelope<T: Serialize + ?Sized>(
+    envelope: &T,
+    signer: &dyn EnvelopeSigner,
+) -> Result<EnvelopeSignature, serde_json::Error> {
+    let message = signed_message(envelope)?;
+    Ok(EnvelopeSignature {
+        key_id: signer.key_id().to_string(),
+        signature: signer.sign(&message),
+    })
+}
+
+/// Checks that `signature` is a valid signature of `envelope`, failing with
+/// [`VerifyError::InvalidSignature`] otherwise.
+pub fn verify_envelope_signature<T: Serialize + ?Sized>(
+    envelope: &T,
+    signature: &EnvelopeSignature,
+    verifier: &dyn EnvelopeVerifier,
+) -> Result<(), VerifyError> {
+    let invalid = || VerifyError::InvalidSignature(signature.key_id.clone());
+    let message = signed_message(envelope).map_err(|_| invalid())?;
+    if verifier.verify(&signature.key_id, &message, &signature.signature) {
+        Ok(())
+    } else {
+        Err(invalid())
+    }
+}
+
+fn signed_message<T: Serialize + ?Sized>(envelope: &T) -> Result<Vec<u8>, serde_json::Error> {
+    let json = to_canonical_json(envelope)?;
+    Ok([DOMAIN, json.as_bytes()].concat())
+}
+
+/// HMAC-SHA256 signer and verifier, for pipelines whose prover and submitter share
+/// a secret.
+pub struct HmacSigner {
+    key_id: String,
+    key: Vec<u8>,
+}
+
+impl HmacSigner {
+    pub fn new(key_id: impl Into<String>, key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            key_id: key_id.into(),
+            key: key.into(),
+        }
+    }
+
+    fn mac(&self, message: &[u8]) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(message);
+        mac
+    }
+}
+
+impl EnvelopeSigner for HmacSigner {
+    fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    fn sign(&self, message: &[u8]) -> Vec<u8> {
+        self.mac(message).finalize().into_bytes().to_vec()
+    }
+}
+
+impl EnvelopeVerifier for HmacSigner {
+    fn verify(&self, key_id: &str, message: &[u8], signature: &[u8]) -> bool {
+        // constant time comparison
+        key_id == self.key_id && self.mac(message).verify_slice(signature).is_ok()
+    }
+}
+
+fn serialize_hex<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("0x{}", hex::encode(bytes)))
+}
+
+fn deserialize_hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let encoded = String::deserialize(deserializer)?;
+    hex::decode(encoded.strip_prefix("0x").unwrap_or(&encoded)).map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::BlockRootsTree;
+    use crate::provider::BlockRootsProof;
+    use ethereum_consensus::ssz::prelude::Node;
+
+    fn proof() -> BlockRootsProof {
+        let leaves = (0..16u8).map(Node::repeat_byte).collect::<Vec<_>>();
+        BlockRootsTree::new(&leaves).prove_leaf(5)
+    }
+
+    #[test]
+    fn it_should_verify_signatures_of_relayed_envelopes() {
+        let signer = HmacSigner::new("prover-1", b"secret".to_vec());
+        let signature = sign_envelope(&proof(), &signer).unwrap();
+
+        // the envelope and its signature went through a queue
+        let json = serde_json::to_string(&(proof(), &signature)).unwrap();
+        let (relayed, signature): (BlockRootsProof, EnvelopeSignature) =
+            serde_json::from_str(&json).unwrap();
+
+        assert_eq!(signature.key_id, "prover-1");
+        assert_eq!(
+            verify_envelope_signature(&relayed, &signature, &signer),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn it_should_reject_tampered_envelopes() {
+        let signer = HmacSigner::new("prover-1", b"secret".to_vec());
+        let signature = sign_envelope(&proof(), &signer).unwrap();
+        let invalid = Err(VerifyError::InvalidSignature("prover-1".to_string()));
+
+        let mut tampered = proof();
+        if let BlockRootsProof::SingleProof { leaf, .. } = &mut tampered {
+            *leaf = Node::repeat_byte(42);
+        }
+        assert_eq!(
+            verify_envelope_signature(&tampered, &signature, &signer),
+            invalid
+        );
+
+        let other_key = HmacSigner::new("prover-1", b"other secret".to_vec());
+        assert_eq!(
+            verify_envelope_signature(&proof(), &signature, &other_key),
+            invalid
+        );
+
+        let other_id = HmacSigner::new("prover-2", b"secret".to_vec());
+        assert!(verify_envelope_signature(&proof(), &signature, &other_id).is_err());
+    }
+}
@@ -0,0 +1,567 @@
+//! Deterministic synthetic beacon chain, for end-to-end tests of the prover
+//! without any network.
+//!
+//! The chain has one block per slot (except the skipped ones), each linked to its
+//! parent, and one state per slot whose `block_roots` vector is consistent with
+//! the blocks. States are merkleized as a 32 field container, like the beacon
+//! state, where every field but `block_roots` is filled with deterministic
+//! placeholder roots, so `block_roots` proofs have the same gindices and shape
+//! as the mainnet ones.
+//!
+//! A Deneb fork can be scheduled mid-chain, after which states commit to a Deneb
+//! execution payload header, to exercise chains that straddle a fork.
+
+use crate::distance::fits_in_window;
+use crate::errors::ProofProviderError;
+use crate::merkle::{hash_pair, BlockRootsTree};
+use crate::provider::{descriptor_gindices, BlockRootsProof, ProofProvider, ProviderInfo, StateId};
+use crate::verification::{
+    block_roots_gindex, verify_block_roots_proof, BLOCK_ROOTS_FIELD_GINDEX, SLOT_FIELD_GINDEX,
+};
+use crate::{verify, AncestryProver};
+use ::ssz_rs::compact_multiproofs::compute_proof_descriptor;
+use async_trait::async_trait;
+use ethereum_consensus::capella::presets::mainnet::SLOTS_PER_HISTORICAL_ROOT;
+use ethereum_consensus::ssz::prelude::Node;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex, OnceLock};
+
+const STATE_FIELDS: usize = 32;
+const SLOTS_PER_EPOCH: u64 = 32;
+const SLOT_FIELD_INDEX: usize = SLOT_FIELD_GINDEX as usize - STATE_FIELDS;
+const BLOCK_ROOTS_FIELD_INDEX: usize = BLOCK_ROOTS_FIELD_GINDEX as usize - STATE_FIELDS;
+const LATEST_EXECUTION_PAYLOAD_HEADER_FIELD_INDEX: usize = 24;
+
+fn u64_node(value: u64) -> Node {
+    Node::left_padding_from(&value.to_be_bytes())
+}
+
+/// Fork of a state of the synthetic chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimFork {
+    Capella,
+    Deneb,
+}
+
+impl SimFork {
+    /// Returns the fork name, as in the `version` of beacon API responses.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SimFork::Capella => "capella",
+            SimFork::Deneb => "deneb",
+        }
+    }
+}
+
+/// Block of the synthetic chain. Its root commits to its parent root and slot
+/// (it's not the `hash_tree_root` of a real header).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimHeader {
+    pub slot: u64,
+    pub parent_root: Node,
+    pub root: Node,
+}
+
+/// Merkleized state of a slot of the synthetic chain.
+struct SimState {
+    block_roots: BlockRootsTree,
+    fields: BlockRootsTree,
+}
+
+impl SimState {
+    fn root(&self) -> Node {
+        self.fields.root()
+    }
+
+    /// Returns the node at `gindex` of the state, if it's in the `block_roots` subtree
+    /// or above the fields.
+    fn node(&self, gindex: u64) -> Option<Node> {
+        let fields_depth = STATE_FIELDS.trailing_zeros();
+        let depth = 63_u32.checked_sub(gindex.leading_zeros())?;
+        if depth <= fields_depth {
+            return self.fields.node(gindex);
+        }
+
+        let subtree_depth = depth - fields_depth;
+        if gindex >> subtree_depth != BLOCK_ROOTS_FIELD_GINDEX {
+            return None;
+        }
+        let relative = (1 << subtree_depth) | (gindex & ((1 << subtree_depth) - 1));
+        self.block_roots.node(relative)
+    }
+
+    fn prove_block_root(&self, index: usize) -> BlockRootsProof {
+        let (mut witnesses, leaf) = match self.block_roots.prove_leaf(index) {
+            BlockRootsProof::SingleProof {
+                witnesses, leaf, ..
+            } => (witnesses, leaf),
+            _ => unreachable!(),
+        };
+        if let BlockRootsProof::SingleProof {
+            witnesses: field_witnesses,
+            ..
+        } = self.fields.prove_leaf(BLOCK_ROOTS_FIELD_INDEX)
+        {
+            witnesses.extend(field_witnesses);
+        }
+
+        BlockRootsProof::SingleProof {
+            gindex: BLOCK_ROOTS_FIELD_GINDEX * SLOTS_PER_HISTORICAL_ROOT as u64 + index as u64,
+            witnesses,
+            leaf,
+        }
+    }
+
+    /// Returns the compact multiproof of `gindices`, relative to the state root.
+    fn prove_gindices(&self, gindices: &[u64]) -> Result<BlockRootsProof, ProofProviderError> {
+        let gindices = gindices.iter().map(|g| *g as usize).collect::<Vec<_>>();
+        let descriptor = compute_proof_descriptor(&gindices).map_err(|err| {
+            ProofProviderError::InputError(format!("Failed to compute proof descriptor: {}", err))
+        })?;
+        let nodes = descriptor_gindices(&descriptor)
+            .map_err(|_| ProofProviderError::InvalidProofError())?
+            .into_iter()
+            .map(|gindex| {
+                self.node(gindex).ok_or_else(|| {
+                    ProofProviderError::NotSupportedError(format!(
+                        "Proofs of gindex {} are not simulated",
+                        gindex
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(BlockRootsProof::CompactProof { descriptor, nodes })
+    }
+}
+
+/// Deterministic synthetic chain from genesis (slot 0) to `head_slot`.
+pub struct SimChain {
+    head_slot: u64,
+    seed: u64,
+    skipped: BTreeSet<u64>,
+    deneb_fork_slot: Option<u64>,
+    // root of the latest block at or before each slot
+    latest_block_roots: OnceLock<Vec<Node>>,
+    states: Mutex<HashMap<u64, Arc<SimState>>>,
+    slots_by_state_root: Mutex<HashMap<Node, u64>>,
+}
+
+impl SimChain {
+    pub fn new(head_slot: u64) -> Self {
+        Self {
+            head_slot,
+            seed: 0,
+            skipped: BTreeSet::new(),
+            deneb_fork_slot: None,
+            latest_block_roots: OnceLock::new(),
+            states: Mutex::new(HashMap::new()),
+            slots_by_state_root: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Uses another seed, to generate a different chain of the same shape.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Skips the blocks of the given slots. The genesis block is never skipped.
+    pub fn with_skipped_slots(mut self, slots: impl IntoIterator<Item = u64>) -> Self {
+        self.skipped
+            .extend(slots.into_iter().filter(|slot| *slot != 0));
+        self
+    }
+
+    /// Schedules the Deneb fork at `slot`, the states before it being Capella ones.
+    pub fn with_deneb_fork(mut self, slot: u64) -> Self {
+        self.deneb_fork_slot = Some(slot);
+        self
+    }
+
+    pub fn head_slot(&self) -> u64 {
+        self.head_slot
+    }
+
+    /// Returns the slot of the justified checkpoint, the first slot of the epoch
+    /// before the head's, as the chain justifies every epoch.
+    pub fn justified_slot(&self) -> u64 {
+        (self.head_slot / SLOTS_PER_EPOCH).saturating_sub(1) * SLOTS_PER_EPOCH
+    }
+
+    /// Returns the slot of the finalized checkpoint, the epoch before the justified one.
+    pub fn finalized_slot(&self) -> u64 {
+        (self.head_slot / SLOTS_PER_EPOCH).saturating_sub(2) * SLOTS_PER_EPOCH
+    }
+
+    /// Returns the fork of the state of `slot`.
+    pub fn fork(&self, slot: u64) -> SimFork {
+        match self.deneb_fork_slot {
+            Some(fork_slot) if slot >= fork_slot => SimFork::Deneb,
+            _ => SimFork::Capella,
+        }
+    }
+
+    fn latest_block_roots(&self) -> &[Node] {
+        self.latest_block_roots.get_or_init(|| {
+            let mut roots = Vec::with_capacity(self.head_slot as usize + 1);
+            let mut parent_root = Node::default();
+            for slot in 0..=self.head_slot {
+                if !self.skipped.contains(&slot) {
+                    let seeded_slot = hash_pair(&u64_node(self.seed), &u64_node(slot));
+                    parent_root = hash_pair(&parent_root, &seeded_slot);
+                }
+                roots.push(parent_root);
+            }
+            roots
+        })
+    }
+
+    /// Returns the block of `slot`, or `None` if the slot was skipped or is after the head.
+    pub fn header(&self, slot: u64) -> Option<SimHeader> {
+        if slot > self.head_slot || self.skipped.contains(&slot) {
+            return None;
+        }
+
+        let roots = self.latest_block_roots();
+        Some(SimHeader {
+            slot,
+            parent_root: slot
+                .checked_sub(1)
+                .map(|parent| roots[parent as usize])
+                .unwrap_or_default(),
+            root: roots[slot as usize],
+        })
+    }
+
+    /// Returns the root of the latest block at or before `slot`, i.e. the entry of
+    /// `slot` in the `block_roots` of later states.
+    pub fn block_root(&self, slot: u64) -> Node {
+        self.latest_block_roots()[slot as usize]
+    }
+
+    /// Returns the `block_roots` vector of the state of `slot`.
+    pub fn block_roots(&self, slot: u64) -> Vec<Node> {
+        self.state(slot).block_roots.leaves().to_vec()
+    }
+
+    /// Returns the state root of `slot`.
+    pub fn state_root(&self, slot: u64) -> Node {
+        self.state(slot).root()
+    }
+
+    /// Returns the single proof of the block root of `target_slot` in the state of
+    /// `anchor_slot`, without going through a provider (e.g. to generate proofs in
+    /// benchmarks).
+    ///
+    /// Panics if `target_slot` is outside of the `block_roots` window of the anchor.
+    pub fn prove_block_root(&self, target_slot: u64, anchor_slot: u64) -> BlockRootsProof {
+        assert!(
+            fits_in_window(anchor_slot, target_slot),
+            "slot {} is outside of the block_roots of slot {}",
+            target_slot,
+            anchor_slot
+        );
+
+        self.state(anchor_slot)
+            .prove_block_root((target_slot % SLOTS_PER_HISTORICAL_ROOT as u64) as usize)
+    }
+
+    /// Returns the compact multiproof of the block roots of `target_slots` in the
+    /// state of `anchor_slot`, like [`SimChain::prove_block_root`].
+    pub fn prove_block_roots(
+        &self,
+        target_slots: &[u64],
+        anchor_slot: u64,
+    ) -> Result<BlockRootsProof, ProofProviderError> {
+        let mut gindices = target_slots
+            .iter()
+            .map(|slot| {
+                block_roots_gindex(*slot, SLOTS_PER_HISTORICAL_ROOT as u64)
+                    .map_err(|_| ProofProviderError::InvalidProofError())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        gindices.sort_unstable();
+        gindices.dedup();
+
+        self.state(anchor_slot).prove_gindices(&gindices)
+    }
+
+    fn state(&self, slot: u64) -> Arc<SimState> {
+        assert!(slot <= self.head_slot, "slot {} is after the head", slot);
+        if let Some(state) = self.states.lock().unwrap().get(&slot) {
+            return state.clone();
+        }
+
+        let window = SLOTS_PER_HISTORICAL_ROOT as u64;
+        let mut block_roots = vec![Node::default(); SLOTS_PER_HISTORICAL_ROOT];
+        for past_slot in slot.saturating_sub(window)..slot {
+            block_roots[(past_slot % window) as usize] = self.block_root(past_slot);
+        }
+        let block_roots = BlockRootsTree::new(&block_roots);
+
+        // the fields of both forks are laid out identically, only the execution
+        // payload header differs (it gains the blob gas fields in Deneb)
+        let fork = self.fork(slot);
+        let fields = (0..STATE_FIELDS)
+            .map(|index| match index {
+                SLOT_FIELD_INDEX => Node::right_padding_from(&slot.to_le_bytes()),
+                BLOCK_ROOTS_FIELD_INDEX => block_roots.root(),
+                LATEST_EXECUTION_PAYLOAD_HEADER_FIELD_INDEX if fork == SimFork::Deneb => {
+                    hash_pair(&u64_node(slot), &Node::right_padding_from(b"deneb"))
+                }
+                _ => hash_pair(&u64_node(slot), &u64_node(index as u64)),
+            })
+            .collect::<Vec<_>>();
+        let state = Arc::new(SimState {
+            block_roots,
+            fields: BlockRootsTree::new(&fields),
+        });
+
+        self.slots_by_state_root
+            .lock()
+            .unwrap()
+            .insert(state.root(), slot);
+        self.states.lock().unwrap().insert(slot, state.clone());
+        state
+    }
+
+    /// Resolves a state id, the head, a slot or the root of a state built before.
+    fn resolve(&self, state_id: &StateId) -> Result<Arc<SimState>, ProofProviderError> {
+        let slot = match state_id {
+            StateId::Slot(slot) => *slot,
+            StateId::Head => self.head_slot,
+            StateId::Root(root) => *self
+                .slots_by_state_root
+                .lock()
+                .unwrap()
+                .get(root)
+                .ok_or_else(|| ProofProviderError::NotFoundError(state_id.to_string()))?,
+            StateId::Justified => self.justified_slot(),
+            StateId::Finalized => self.finalized_slot(),
+        };
+        if slot > self.head_slot {
+            return Err(ProofProviderError::NotFoundError(state_id.to_string()));
+        }
+
+        Ok(self.state(slot))
+    }
+}
+
+/// Provider serving proofs of a [`SimChain`].
+#[derive(Clone)]
+pub struct SimProvider {
+    chain: Arc<SimChain>,
+}
+
+impl SimProvider {
+    pub fn new(chain: Arc<SimChain>) -> Self {
+        Self { chain }
+    }
+}
+
+#[async_trait]
+impl ProofProvider for SimProvider {
+    async fn get_state_proof(
+        &self,
+        state_id: &StateId,
+        gindex: u64,
+    ) -> Result<BlockRootsProof, ProofProviderError> {
+        let state = self.chain.resolve(state_id)?;
+        let first = BLOCK_ROOTS_FIELD_GINDEX * SLOTS_PER_HISTORICAL_ROOT as u64;
+        match gindex
+            .checked_sub(first)
+            .filter(|index| *index < SLOTS_PER_HISTORICAL_ROOT as u64)
+        {
+            Some(index) => Ok(state.prove_block_root(index as usize)),
+            // other nodes (e.g. the state fields) are proven by compact proofs
+            None => state.prove_gindices(&[gindex]),
+        }
+    }
+
+    async fn get_state_multiproof(
+        &self,
+        state_id: &StateId,
+        gindices: &[u64],
+    ) -> Result<BlockRootsProof, ProofProviderError> {
+        self.chain.resolve(state_id)?.prove_gindices(gindices)
+    }
+
+    async fn get_block_roots(&self, state_id: &StateId) -> Result<Vec<Node>, ProofProviderError> {
+        Ok(self.chain.resolve(state_id)?.block_roots.leaves().to_vec())
+    }
+
+    async fn get_block_root(&self, slot: u64) -> Result<Node, ProofProviderError> {
+        self.chain
+            .header(slot)
+            .map(|header| header.root)
+            .ok_or_else(|| ProofProviderError::NotFoundError(format!("block {}", slot)))
+    }
+
+    fn info(&self) -> ProviderInfo {
+        ProviderInfo {
+            backend: "sim".to_string(),
+            url: None,
+        }
+    }
+
+    async fn is_block_root(&self, root: &Node) -> Result<bool, ProofProviderError> {
+        Ok((0..=self.chain.head_slot).any(|slot| {
+            self.chain
+                .header(slot)
+                .is_some_and(|header| header.root == *root)
+        }))
+    }
+}
+
+/// Asserts that the block of `target_slot` (or the latest one before it, if skipped)
+/// is proven and verified as an ancestor of the state of `anchor_slot`.
+pub async fn assert_ancestry(chain: &Arc<SimChain>, target_slot: u64, anchor_slot: u64) {
+    assert!(target_slot < anchor_slot, "target must precede the anchor");
+
+    let anchor_root = chain.state_root(anchor_slot);
+    let anchor_root_hex = format!("{:?}", anchor_root);
+    let prover = AncestryProver::new(SimProvider::new(chain.clone()));
+
+    let proof = prover
+        .prove(target_slot, anchor_slot, &anchor_root_hex)
+        .await
+        .expect("Failed to prove ancestry");
+
+    assert!(
+        verify(&proof, target_slot, anchor_slot, &anchor_root_hex),
+        "Proof of slot {} against slot {} failed verification",
+        target_slot,
+        anchor_slot
+    );
+    let block_root = verify_block_roots_proof(
+        anchor_root.0,
+        target_slot,
+        SLOTS_PER_HISTORICAL_ROOT as u64,
+        &proof,
+    )
+    .unwrap();
+    assert_eq!(
+        Node::from(block_root),
+        chain.block_root(target_slot),
+        "Proof of slot {} proved another block root",
+        target_slot
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_should_prove_ancestry_across_the_window() {
+        let chain = Arc::new(SimChain::new(20_000));
+
+        // first and last slots of the window, and a period boundary inside it
+        assert_ancestry(&chain, 19_999, 20_000).await;
+        assert_ancestry(&chain, 20_000 - 8191, 20_000).await;
+        assert_ancestry(&chain, 16_383, 20_000).await;
+        assert_ancestry(&chain, 16_384, 20_000).await;
+    }
+
+    #[tokio::test]
+    async fn it_should_prove_skipped_slots_as_their_latest_block() {
+        let chain = Arc::new(SimChain::new(100).with_skipped_slots([41, 42]));
+
+        assert!(chain.header(42).is_none());
+        assert_eq!(chain.block_root(42), chain.header(40).unwrap().root);
+        assert_eq!(
+            chain.header(43).unwrap().parent_root,
+            chain.header(40).unwrap().root
+        );
+        assert_ancestry(&chain, 42, 100).await;
+    }
+
+    #[test]
+    fn it_should_generate_deterministic_chains() {
+        let chain = SimChain::new(10);
+
+        assert_eq!(chain.state_root(10), SimChain::new(10).state_root(10));
+        assert_ne!(
+            chain.state_root(10),
+            SimChain::new(10).with_seed(1).state_root(10)
+        );
+        assert_eq!(chain.block_roots(10)[9], chain.header(9).unwrap().root);
+        assert_eq!(chain.block_roots(10)[10], Node::default());
+    }
+
+    #[tokio::test]
+    async fn it_should_resolve_states_by_slot_and_root() {
+        let chain = Arc::new(SimChain::new(10));
+        let provider = SimProvider::new(chain.clone());
+        let root = StateId::Root(chain.state_root(10));
+
+        assert_eq!(
+            provider.get_block_roots(&StateId::Slot(10)).await.unwrap(),
+            provider.get_block_roots(&root).await.unwrap()
+        );
+        assert_eq!(
+            provider.get_block_roots(&StateId::Head).await.unwrap(),
+            provider.get_block_roots(&root).await.unwrap()
+        );
+        assert!(matches!(
+            provider.get_block_roots(&StateId::Slot(11)).await,
+            Err(ProofProviderError::NotFoundError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_prove_ancestry_across_the_deneb_fork() {
+        let chain = Arc::new(SimChain::new(20_000).with_deneb_fork(16_384));
+
+        assert_eq!(chain.fork(16_383), SimFork::Capella);
+        assert_eq!(chain.fork(16_384).name(), "deneb");
+        assert_ne!(
+            chain.state_root(16_384),
+            SimChain::new(16_384).state_root(16_384)
+        );
+
+        // capella target against a deneb anchor, and both in deneb
+        assert_ancestry(&chain, 16_383, 16_400).await;
+        assert_ancestry(&chain, 12_000, 20_000).await;
+        assert_ancestry(&chain, 16_384, 20_000).await;
+    }
+
+    #[test]
+    fn it_should_generate_proofs_without_a_provider() {
+        let chain = SimChain::new(10_000).with_seed(3);
+        let anchor_root = chain.state_root(10_000);
+        let window = SLOTS_PER_HISTORICAL_ROOT as u64;
+
+        let single = chain.prove_block_root(2_000, 10_000);
+        let compact = chain
+            .prove_block_roots(&[2_000, 9_999, 5_000], 10_000)
+            .unwrap();
+
+        for slot in [2_000, 9_999, 5_000] {
+            assert_eq!(
+                verify_block_roots_proof(anchor_root.0, slot, window, &compact).unwrap(),
+                chain.block_root(slot).0
+            );
+        }
+        assert_eq!(
+            verify_block_roots_proof(anchor_root.0, 2_000, window, &single).unwrap(),
+            chain.block_root(2_000).0
+        );
+    }
+
+    #[test]
+    fn it_should_share_block_roots_gindices_between_forks() {
+        use crate::prover::block_roots_gindex;
+        use ethereum_consensus::deneb::presets::mainnet::BeaconState;
+        use ethereum_consensus::ssz::prelude::*;
+
+        for slot in [0, 6592, 8191] {
+            let path = &["block_roots".into(), PathElement::Index(slot as usize)];
+            assert_eq!(
+                BeaconState::generalized_index(path).unwrap() as u64,
+                block_roots_gindex(slot)
+            );
+        }
+    }
+}
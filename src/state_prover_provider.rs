@@ -1,55 +1,178 @@
 use crate::errors::ProofProviderError;
-use crate::provider::{BlockRootsProof, ProofProvider};
+use crate::middleware::{
+    self, ContentType, HttpService, ReqwestService, DEFAULT_MAX_RESPONSE_SIZE,
+};
+use crate::provider::{BlockRootsProof, ProofProvider, ProviderInfo, StateId};
+use crate::stats::{Stats, StatsRecorder};
 use async_trait::async_trait;
-use mockall::automock;
+use reqwest::{Client, Method, Request, Response, StatusCode, Url};
+use serde::Deserialize;
+use tower::{Layer, Service};
 
 /// Provider that uses [`state prover`](https://github.com/commonprefix/state-prover) to interact with the Lodestar API.
 #[derive(Clone)]
 pub struct StateProverProvider {
     network: String,
     rpc: String,
+    service: HttpService,
+    stats: StatsRecorder,
+    max_response_size: usize,
+    query_params: Vec<(String, String)>,
 }
 
 impl StateProverProvider {
-    #[cfg(test)]
     pub fn new(network: String, rpc: String) -> Self {
-        Self { network, rpc }
+        Self::with_service(network, rpc, middleware::boxed(ReqwestService::default()))
     }
 
-    async fn get(&self, req: &str) -> Result<BlockRootsProof, ProofProviderError> {
-        let response = reqwest::get(req)
-            .await
-            .map_err(ProofProviderError::NetworkError)?;
+    /// Creates a provider that uses the given client (and its connection pool).
+    pub fn with_client(network: String, rpc: String, client: Client) -> Self {
+        Self::with_service(network, rpc, middleware::boxed(ReqwestService::new(client)))
+    }
 
-        if response.status() == reqwest::StatusCode::NOT_FOUND {
-            return Err(ProofProviderError::NotFoundError(req.into()));
+    /// Creates a provider that sends its requests through a custom `tower::Service` stack.
+    pub fn with_service(network: String, rpc: String, service: HttpService) -> Self {
+        Self {
+            network,
+            rpc,
+            service,
+            stats: StatsRecorder::default(),
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            query_params: Vec::new(),
         }
+    }
+
+    /// Wraps the request stack of the provider with an additional layer,
+    /// e.g. for authentication, tracing, retries or rate limiting.
+    pub fn layer<L>(mut self, layer: L) -> Self
+    where
+        L: Layer<HttpService>,
+        L::Service: Service<Request, Response = Response, Error = ProofProviderError>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        <L::Service as Service<Request>>::Future: Send + 'static,
+    {
+        self.service = middleware::wrap(self.service, layer);
+        self
+    }
+
+    /// Appends a query parameter to every request, e.g. to select a node behind a
+    /// gateway multiplexing several of them. The path of `rpc` is kept as a prefix.
+    pub fn with_query_param(mut self, name: &str, value: &str) -> Self {
+        self.query_params
+            .push((name.to_string(), value.to_string()));
+        self
+    }
 
-        let bytes = response
-            .bytes()
-            .await
-            .map_err(ProofProviderError::NetworkError)?;
+    /// Limits the (decompressed) size of the responses, defaults to [`DEFAULT_MAX_RESPONSE_SIZE`].
+    pub fn with_max_response_size(mut self, bytes: usize) -> Self {
+        self.max_response_size = bytes;
+        self
+    }
+
+    /// Returns the statistics of the requests made by the provider (and its clones).
+    pub fn stats(&self) -> Stats {
+        self.stats.snapshot()
+    }
+
+    /// Returns the recorder of the provider statistics, e.g. for caches wrapping
+    /// the provider to record their hits.
+    pub fn stats_recorder(&self) -> StatsRecorder {
+        self.stats.clone()
+    }
+
+    async fn get(&self, url: Url) -> Result<BlockRootsProof, ProofProviderError> {
+        let result = self.try_get(url).await;
+        self.stats.record(&result);
+        result
+    }
+
+    async fn try_get(&self, url: Url) -> Result<BlockRootsProof, ProofProviderError> {
+        let req = url.to_string();
+        let response = middleware::send(&self.service, Request::new(Method::GET, url)).await?;
+        if let Some(err) = middleware::rate_limited(&response) {
+            return Err(err);
+        }
+
+        let status = response.status();
+        let content_type = ContentType::of(&response);
+        let bytes = middleware::read_body(response, self.max_response_size).await?;
+        self.stats.record_bytes(bytes.len());
+
+        if !status.is_success() {
+            return Err(ErrorResponse::decode(req, status, &bytes));
+        }
+
+        if content_type != ContentType::Json {
+            return Err(content_type.unexpected(&req));
+        }
 
         serde_json::from_slice(&bytes).map_err(ProofProviderError::SerializationError)
     }
 }
 
-#[automock]
+/// JSON body of the errors of the state prover, e.g.
+/// `{"code": "unknown_state", "message": "..."}`.
+#[derive(Deserialize)]
+struct ErrorResponse {
+    #[serde(default)]
+    code: Option<String>,
+    #[serde(alias = "error")]
+    message: String,
+}
+
+impl ErrorResponse {
+    /// Maps the error of a failed request to a typed error, falling back to
+    /// [`ProofProviderError::BackendError`] for unknown or undecodable errors.
+    fn decode(url: String, status: StatusCode, body: &[u8]) -> ProofProviderError {
+        let (code, message) = match serde_json::from_slice::<ErrorResponse>(body) {
+            Ok(response) => (response.code, response.message),
+            Err(_) => (None, status.to_string()),
+        };
+        match code.as_deref() {
+            Some("unknown_state") => ProofProviderError::NotFoundError(url),
+            Some("invalid_gindex") => ProofProviderError::InputError(message),
+            _ if status == StatusCode::NOT_FOUND => ProofProviderError::NotFoundError(url),
+            _ => ProofProviderError::BackendError {
+                url,
+                status: status.as_u16(),
+                message,
+            },
+        }
+    }
+}
+
+#[cfg_attr(any(test, feature = "test-utils"), mockall::automock)]
 #[async_trait]
 impl ProofProvider for StateProverProvider {
     async fn get_state_proof(
         &self,
-        state_id: &str,
+        state_id: &StateId,
         gindex: u64,
     ) -> Result<BlockRootsProof, ProofProviderError> {
-        let req = format!(
-            "{}/state_proof?state_id={}&gindex={}&network={}",
-            self.rpc, state_id, gindex, self.network
-        );
+        let url = middleware::endpoint_url(
+            &self.rpc,
+            "state_proof",
+            &[
+                ("state_id", &state_id.to_string()),
+                ("gindex", &gindex.to_string()),
+                ("network", &self.network),
+            ],
+            &self.query_params,
+        )?;
 
-        let lodestar_proof = self.get(&req).await;
+        let lodestar_proof = self.get(url).await;
         lodestar_proof
     }
+
+    fn info(&self) -> ProviderInfo {
+        ProviderInfo {
+            backend: "state_prover".to_string(),
+            url: Some(self.rpc.clone()),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -72,14 +195,14 @@ mod tests {
 
         server.expect(
             Expectation::matching(all_of![
-                request::query(url_decoded(contains(("state_id", "state_id")))),
+                request::query(url_decoded(contains(("state_id", "head")))),
                 request::query(url_decoded(contains(("gindex", "1")))),
             ])
             .respond_with(status_code(200).body(json_response)),
         );
 
-        let result = prover.get_state_proof("state_id", 1).await.unwrap();
-        assert_eq!(result, expected_response.into());
+        let result = prover.get_state_proof(&StateId::Head, 1).await.unwrap();
+        assert_eq!(result, expected_response);
     }
 
     #[tokio::test]
@@ -88,13 +211,115 @@ mod tests {
 
         server.expect(
             Expectation::matching(all_of![
-                request::query(url_decoded(contains(("state_id", "state_id")))),
+                request::query(url_decoded(contains(("state_id", "head")))),
                 request::query(url_decoded(contains(("gindex", "1")))),
             ])
             .respond_with(status_code(400).body("Error")),
         );
 
-        let result = prover.get_state_proof("state_id", 1).await;
+        let result = prover.get_state_proof(&StateId::Head, 1).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn it_should_decode_typed_errors() {
+        let (server, prover) = setup_server_and_prover();
+        let respond = |status, body: serde_json::Value| {
+            status_code(status)
+                .append_header("content-type", "application/json")
+                .body(body.to_string())
+        };
+
+        server.expect(
+            Expectation::matching(request::query(url_decoded(contains(("gindex", "1")))))
+                .respond_with(respond(
+                    400,
+                    serde_json::json!({"code": "unknown_state", "message": "Unknown state"}),
+                )),
+        );
+        server.expect(
+            Expectation::matching(request::query(url_decoded(contains(("gindex", "2")))))
+                .respond_with(respond(
+                    400,
+                    serde_json::json!({"code": "invalid_gindex", "message": "Bad gindex 2"}),
+                )),
+        );
+        server.expect(
+            Expectation::matching(request::query(url_decoded(contains(("gindex", "3")))))
+                .respond_with(respond(500, serde_json::json!({"error": "Syncing"}))),
+        );
+
+        let result = prover.get_state_proof(&StateId::Head, 1).await;
+        assert!(matches!(result, Err(ProofProviderError::NotFoundError(_))));
+
+        let result = prover.get_state_proof(&StateId::Head, 2).await;
+        assert!(
+            matches!(result, Err(ProofProviderError::InputError(message)) if message == "Bad gindex 2")
+        );
+
+        let err = prover.get_state_proof(&StateId::Head, 3).await.unwrap_err();
+        assert!(matches!(
+            err,
+            ProofProviderError::BackendError { status: 500, ref message, .. } if message == "Syncing"
+        ));
+        assert!(err.is_transient());
+    }
+
+    #[tokio::test]
+    async fn it_should_expose_the_advised_wait_of_rate_limited_requests() {
+        let (server, prover) = setup_server_and_prover();
+
+        server.expect(
+            Expectation::matching(any())
+                .respond_with(status_code(429).append_header("retry-after", "30")),
+        );
+
+        let err = prover.get_state_proof(&StateId::Head, 1).await.unwrap_err();
+
+        assert!(matches!(
+            err,
+            ProofProviderError::RateLimited { status: 429, .. }
+        ));
+        assert_eq!(err.retry_after(), Some(std::time::Duration::from_secs(30)));
+        assert_eq!(err.to_payload().details.retry_after_ms, Some(30_000));
+    }
+
+    #[tokio::test]
+    async fn it_should_record_request_stats() {
+        let (server, prover) = setup_server_and_prover();
+        let json_response = serde_json::to_string(&BlockRootsProof::default()).unwrap();
+
+        server.expect(
+            Expectation::matching(request::query(url_decoded(contains(("gindex", "1")))))
+                .respond_with(status_code(200).body(json_response.clone())),
+        );
+        server.expect(
+            Expectation::matching(request::query(url_decoded(contains(("gindex", "2")))))
+                .respond_with(status_code(404)),
+        );
+
+        prover.get_state_proof(&StateId::Head, 1).await.unwrap();
+        _ = prover.clone().get_state_proof(&StateId::Head, 2).await;
+
+        let stats = prover.stats();
+        assert_eq!(stats.requests, 2);
+        assert_eq!(stats.errors.get("not_found"), Some(&1));
+        assert_eq!(stats.bytes, json_response.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn it_should_reject_responses_over_the_size_limit() {
+        let (server, prover) = setup_server_and_prover();
+        let prover = prover.with_max_response_size(16);
+
+        server.expect(
+            Expectation::matching(any()).respond_with(status_code(200).body(vec![b' '; 17])),
+        );
+
+        let result = prover.get_state_proof(&StateId::Head, 1).await;
+        assert!(matches!(
+            result,
+            Err(ProofProviderError::ResponseTooLarge { limit: 16, .. })
+        ));
+    }
 }
@@ -0,0 +1,121 @@
+use crate::errors::ProofProviderError;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Snapshot of the requests made by a provider.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub requests: u64,
+    /// Failed requests, by error code.
+    pub errors: BTreeMap<&'static str, u64>,
+    /// Bytes of the response bodies received.
+    pub bytes: u64,
+    /// Requests served by a cache wrapping the provider, which never reached it.
+    pub cache_hits: u64,
+    /// Distances from the targets to the anchors of the proofs requested from a prover.
+    pub distances: DistanceHistogram,
+}
+
+/// Upper bounds of the buckets of [`DistanceHistogram`], in slots, finer towards the
+/// end of the `block_roots` window (`SLOTS_PER_HISTORICAL_ROOT - 1` slots).
+pub const DISTANCE_BUCKETS: [u64; 8] = [32, 256, 1_024, 4_096, 6_144, 7_168, 7_680, 8_191];
+
+/// Histogram of the distances from targets to anchors, to see how close requests
+/// get to the end of the `block_roots` window.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DistanceHistogram {
+    /// Targets per bucket of [`DISTANCE_BUCKETS`], then beyond the last bucket.
+    pub counts: [u64; DISTANCE_BUCKETS.len() + 1],
+    /// Longest distance recorded.
+    pub max: u64,
+}
+
+impl DistanceHistogram {
+    pub fn record(&mut self, distance: u64) {
+        let bucket = DISTANCE_BUCKETS.partition_point(|bound| *bound < distance);
+        self.counts[bucket] += 1;
+        self.max = self.max.max(distance);
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Returns the number of targets beyond the last bucket, i.e. too old to be proven
+    /// without historical proofs.
+    pub fn beyond_window(&self) -> u64 {
+        self.counts[DISTANCE_BUCKETS.len()]
+    }
+}
+
+/// Shared counters behind [`Stats`]. Clones of a provider share the same recorder.
+#[derive(Debug, Clone, Default)]
+pub struct StatsRecorder {
+    stats: Arc<Mutex<Stats>>,
+}
+
+impl StatsRecorder {
+    pub fn snapshot(&self) -> Stats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Records the outcome of a request.
+    pub fn record<T>(&self, result: &Result<T, ProofProviderError>) {
+        let mut stats = self.stats.lock().unwrap();
+        stats.requests += 1;
+        if let Err(err) = result {
+            *stats.errors.entry(err.code()).or_default() += 1;
+        }
+    }
+
+    pub fn record_bytes(&self, bytes: usize) {
+        self.stats.lock().unwrap().bytes += bytes as u64;
+    }
+
+    /// Records a request served from a cache, for caches wrapping a provider.
+    pub fn record_cache_hit(&self) {
+        self.stats.lock().unwrap().cache_hits += 1;
+    }
+
+    /// Records the distance from a target to its anchor, in slots.
+    pub fn record_distance(&self, distance: u64) {
+        self.stats.lock().unwrap().distances.record(distance);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_count_errors_by_code() {
+        let recorder = StatsRecorder::default();
+
+        recorder.record(&Ok(()));
+        recorder.record::<()>(&Err(ProofProviderError::NotFoundError("url".into())));
+        recorder.record::<()>(&Err(ProofProviderError::NotFoundError("url".into())));
+        recorder.record::<()>(&Err(ProofProviderError::InvalidProofError()));
+        recorder.record_bytes(64);
+        recorder.clone().record_cache_hit();
+
+        let stats = recorder.snapshot();
+        assert_eq!(stats.requests, 4);
+        assert_eq!(stats.errors["not_found"], 2);
+        assert_eq!(stats.errors["invalid_proof"], 1);
+        assert_eq!(stats.bytes, 64);
+        assert_eq!(stats.cache_hits, 1);
+    }
+
+    #[test]
+    fn it_should_bucket_distances() {
+        let mut histogram = DistanceHistogram::default();
+        for distance in [1, 32, 33, 8_000, 8_191, 8_192, 20_000] {
+            histogram.record(distance);
+        }
+
+        assert_eq!(histogram.counts, [2, 1, 0, 0, 0, 0, 0, 2, 2]);
+        assert_eq!(histogram.total(), 7);
+        assert_eq!(histogram.beyond_window(), 2);
+        assert_eq!(histogram.max, 20_000);
+    }
+}
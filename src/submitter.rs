@@ -0,0 +1,393 @@
+//! Submission of proofs to a verifier contract on an EVM chain, closing the loop
+//! from proving to on-chain verification.
+//!
+//! Transactions are sent with `eth_sendTransaction`, i.e. signed by the node behind
+//! the JSON-RPC endpoint (e.g. a node or signer proxy holding the sending account).
+
+use crate::batch::BatchResult;
+use crate::client::shared_client;
+use crate::errors::SubmitError;
+use crate::provider::BlockRootsProof;
+use alloy_primitives::{Address, Bytes, FixedBytes, B256};
+use alloy_sol_types::{decode_revert_reason, sol, SolCall};
+use ethereum_consensus::ssz::prelude::Node;
+use futures::lock::Mutex;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+sol! {
+    /// Interface of the verifier contracts receiving the proofs, verifying them
+    /// against the anchor block root they store for `anchorSlot`.
+    interface IAncestryVerifier {
+        function verifyAncestry(
+            uint64 targetSlot,
+            uint64 anchorSlot,
+            uint64 gindex,
+            bytes32 leaf,
+            bytes32[] witnesses
+        );
+        function verifyCompactAncestry(
+            uint64 targetSlot,
+            uint64 anchorSlot,
+            bytes descriptor,
+            bytes32[] nodes
+        );
+    }
+}
+
+/// Margin added to the gas estimates of the submissions, in percent.
+pub const DEFAULT_GAS_MARGIN_PERCENT: u64 = 20;
+
+/// ABI-encodes the call of [`IAncestryVerifier`] submitting the proof of the block
+/// of `target_slot` against the state of `anchor_slot`.
+pub fn encode_submission(target_slot: u64, anchor_slot: u64, proof: &BlockRootsProof) -> Vec<u8> {
+    let nodes = |nodes: &[Node]| nodes.iter().map(|node| FixedBytes(node.0)).collect();
+
+    match proof {
+        BlockRootsProof::SingleProof {
+            gindex,
+            witnesses,
+            leaf,
+        } => IAncestryVerifier::verifyAncestryCall {
+            targetSlot: target_slot,
+            anchorSlot: anchor_slot,
+            gindex: *gindex,
+            leaf: FixedBytes(leaf.0),
+            witnesses: nodes(witnesses),
+        }
+        .abi_encode(),
+        BlockRootsProof::CompactProof {
+            descriptor,
+            nodes: proof_nodes,
+        } => IAncestryVerifier::verifyCompactAncestryCall {
+            targetSlot: target_slot,
+            anchorSlot: anchor_slot,
+            descriptor: Bytes::copy_from_slice(descriptor),
+            nodes: nodes(proof_nodes),
+        }
+        .abi_encode(),
+    }
+}
+
+/// Sends proofs to a verifier contract, managing the nonce of the sending account
+/// and the gas limit of the transactions.
+///
+/// Submissions are serialized, so that concurrent callers get consecutive nonces.
+pub struct ProofSubmitter {
+    client: Client,
+    rpc: String,
+    from: Address,
+    verifier: Address,
+    gas_margin_percent: u64,
+    // next nonce of `from`, fetched from the node when unknown
+    nonce: Mutex<Option<u64>>,
+}
+
+impl ProofSubmitter {
+    pub fn new(rpc: String, from: Address, verifier: Address) -> Self {
+        Self::with_client(rpc, from, verifier, shared_client())
+    }
+
+    /// Creates a submitter that uses the given client (and its connection pool).
+    pub fn with_client(rpc: String, from: Address, verifier: Address, client: Client) -> Self {
+        Self {
+            client,
+            rpc,
+            from,
+            verifier,
+            gas_margin_percent: DEFAULT_GAS_MARGIN_PERCENT,
+            nonce: Mutex::new(None),
+        }
+    }
+
+    /// Sets the margin added to the gas estimates, defaults to [`DEFAULT_GAS_MARGIN_PERCENT`].
+    pub fn with_gas_margin(mut self, percent: u64) -> Self {
+        self.gas_margin_percent = percent;
+        self
+    }
+
+    /// Submits the proof of the block of `target_slot` against the state of
+    /// `anchor_slot`, returning the hash of the transaction.
+    pub async fn submit(
+        &self,
+        target_slot: u64,
+        anchor_slot: u64,
+        proof: &BlockRootsProof,
+    ) -> Result<B256, SubmitError> {
+        let mut nonce = self.nonce.lock().await;
+        let next_nonce = match *nonce {
+            Some(next_nonce) => next_nonce,
+            None => {
+                let pending = json!([self.from.to_string(), "pending"]);
+                self.call_quantity("eth_getTransactionCount", pending)
+                    .await?
+            }
+        };
+
+        let data = Bytes::from(encode_submission(target_slot, anchor_slot, proof));
+        let mut tx = json!({
+            "from": self.from.to_string(),
+            "to": self.verifier.to_string(),
+            "data": data.to_string(),
+            "nonce": format!("{next_nonce:#x}"),
+        });
+        let gas = self.call_quantity("eth_estimateGas", json!([tx])).await?;
+        let gas = gas.saturating_mul(100 + self.gas_margin_percent) / 100;
+        tx["gas"] = json!(format!("{gas:#x}"));
+
+        match self.call("eth_sendTransaction", json!([tx])).await {
+            Ok(result) => {
+                *nonce = Some(next_nonce + 1);
+                let hash = result.as_str().and_then(|hash| B256::from_str(hash).ok());
+                hash.ok_or_else(|| SubmitError::UnexpectedResult {
+                    method: "eth_sendTransaction".to_string(),
+                    result: result.to_string(),
+                })
+            }
+            Err(err) => {
+                // the transaction may or may not be pending, refetch the nonce next time
+                *nonce = None;
+                Err(err)
+            }
+        }
+    }
+
+    /// Calls the verifier contract with the proof (`eth_call`) without submitting it,
+    /// failing with [`SubmitError::Reverted`] and the revert reason if the contract
+    /// rejects it, e.g. to catch malformed encodings off-chain.
+    pub async fn dry_run(
+        &self,
+        target_slot: u64,
+        anchor_slot: u64,
+        proof: &BlockRootsProof,
+    ) -> Result<(), SubmitError> {
+        let data = Bytes::from(encode_submission(target_slot, anchor_slot, proof));
+        let tx = json!({
+            "from": self.from.to_string(),
+            "to": self.verifier.to_string(),
+            "data": data.to_string(),
+        });
+
+        match self.call("eth_call", json!([tx, "latest"])).await {
+            Ok(_) => Ok(()),
+            Err(SubmitError::RpcError {
+                message,
+                data: Some(data),
+                ..
+            }) => {
+                let output = Bytes::from_str(&data).unwrap_or_default();
+                Err(SubmitError::Reverted {
+                    reason: decode_revert_reason(&output).unwrap_or(message),
+                })
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Submits the proven targets of a batch against the state of `anchor_slot`,
+    /// one transaction each, returning the outcome of each submission by target slot.
+    pub async fn submit_batch(
+        &self,
+        anchor_slot: u64,
+        batch: &BatchResult,
+    ) -> Vec<(u64, Result<B256, SubmitError>)> {
+        let mut submissions = Vec::with_capacity(batch.ok.len());
+        for (target_slot, proof) in &batch.ok {
+            let result = self.submit(*target_slot, anchor_slot, proof).await;
+            submissions.push((*target_slot, result));
+        }
+        submissions
+    }
+
+    async fn call(&self, method: &str, params: Value) -> Result<Value, SubmitError> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        });
+        let response = self
+            .client
+            .post(&self.rpc)
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&request)?)
+            .send()
+            .await?
+            .bytes()
+            .await?;
+        let mut response: Value = serde_json::from_slice(&response)?;
+
+        if let Some(error) = response.get("error") {
+            return Err(SubmitError::RpcError {
+                method: method.to_string(),
+                code: error["code"].as_i64().unwrap_or_default(),
+                message: error["message"].as_str().unwrap_or_default().to_string(),
+                data: error["data"].as_str().map(str::to_string),
+            });
+        }
+        Ok(response["result"].take())
+    }
+
+    async fn call_quantity(&self, method: &str, params: Value) -> Result<u64, SubmitError> {
+        let result = self.call(method, params).await?;
+        result
+            .as_str()
+            .and_then(|quantity| quantity.strip_prefix("0x"))
+            .and_then(|quantity| u64::from_str_radix(quantity, 16).ok())
+            .ok_or_else(|| SubmitError::UnexpectedResult {
+                method: method.to_string(),
+                result: result.to_string(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::SimChain;
+    use alloy_sol_types::{Revert, SolError};
+    use httptest::matchers::{json_decoded, request};
+    use httptest::responders::json_encoded;
+    use httptest::{Expectation, Server};
+
+    fn method(name: &'static str) -> impl Fn(&Value) -> bool + Send {
+        move |request: &Value| request["method"] == name
+    }
+
+    #[test]
+    fn it_should_encode_single_and_compact_proofs() {
+        let chain = SimChain::new(200);
+        let single = chain.prove_block_root(150, 200);
+        let compact = chain.prove_block_roots(&[150], 200).unwrap();
+
+        let encoded = encode_submission(150, 200, &single);
+        assert_eq!(
+            encoded[..4],
+            IAncestryVerifier::verifyAncestryCall::SELECTOR
+        );
+        let call = IAncestryVerifier::verifyAncestryCall::abi_decode(&encoded, true).unwrap();
+        assert_eq!((call.targetSlot, call.anchorSlot), (150, 200));
+        assert_eq!(call.leaf, FixedBytes(chain.block_root(150).0));
+
+        let encoded = encode_submission(150, 200, &compact);
+        assert_eq!(
+            encoded[..4],
+            IAncestryVerifier::verifyCompactAncestryCall::SELECTOR
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_send_proofs_with_consecutive_nonces() {
+        let server = Server::run();
+        let chain = SimChain::new(200);
+        let proof = chain.prove_block_root(150, 200);
+        let submitter = ProofSubmitter::new(
+            server.url_str("/"),
+            Address::repeat_byte(1),
+            Address::repeat_byte(2),
+        )
+        .with_gas_margin(50);
+
+        server.expect(
+            Expectation::matching(request::body(json_decoded(method(
+                "eth_getTransactionCount",
+            ))))
+            .times(1)
+            .respond_with(json_encoded(
+                json!({"jsonrpc": "2.0", "id": 1, "result": "0x7"}),
+            )),
+        );
+        server.expect(
+            Expectation::matching(request::body(json_decoded(method("eth_estimateGas"))))
+                .times(2)
+                .respond_with(json_encoded(
+                    json!({"jsonrpc": "2.0", "id": 1, "result": "0x186a0"}),
+                )),
+        );
+        for nonce in ["0x7", "0x8"] {
+            server.expect(
+                Expectation::matching(request::body(json_decoded(move |request: &Value| {
+                    request["method"] == "eth_sendTransaction"
+                        && request["params"][0]["nonce"] == nonce
+                        && request["params"][0]["gas"] == "0x249f0"
+                })))
+                .respond_with(json_encoded(json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "result": B256::repeat_byte(3).to_string()
+                }))),
+            );
+        }
+
+        for _ in 0..2 {
+            let hash = submitter.submit(150, 200, &proof).await.unwrap();
+            assert_eq!(hash, B256::repeat_byte(3));
+        }
+    }
+
+    #[tokio::test]
+    async fn it_should_surface_the_revert_reason_of_dry_runs() {
+        let server = Server::run();
+        let proof = SimChain::new(200).prove_block_root(150, 200);
+        let submitter = ProofSubmitter::new(
+            server.url_str("/"),
+            Address::repeat_byte(1),
+            Address::repeat_byte(2),
+        );
+        let revert = Bytes::from(Revert::from("unknown anchor").abi_encode());
+
+        server.expect(
+            Expectation::matching(request::body(json_decoded(method("eth_call")))).respond_with(
+                json_encoded(json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "error": {"code": 3, "message": "execution reverted", "data": revert.to_string()}
+                })),
+            ),
+        );
+
+        let err = submitter.dry_run(150, 200, &proof).await.unwrap_err();
+
+        assert!(
+            matches!(err, SubmitError::Reverted { ref reason } if reason.contains("unknown anchor"))
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_surface_rpc_errors() {
+        let server = Server::run();
+        let proof = SimChain::new(200).prove_block_root(150, 200);
+        let submitter = ProofSubmitter::new(
+            server.url_str("/"),
+            Address::repeat_byte(1),
+            Address::repeat_byte(2),
+        );
+
+        server.expect(
+            Expectation::matching(request::body(json_decoded(method(
+                "eth_getTransactionCount",
+            ))))
+            .respond_with(json_encoded(
+                json!({"jsonrpc": "2.0", "id": 1, "result": "0x0"}),
+            )),
+        );
+        server.expect(
+            Expectation::matching(request::body(json_decoded(method("eth_estimateGas"))))
+                .respond_with(json_encoded(json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "error": {"code": 3, "message": "execution reverted"}
+                }))),
+        );
+
+        let err = submitter.submit(150, 200, &proof).await.unwrap_err();
+
+        assert!(matches!(
+            err,
+            SubmitError::RpcError { ref method, code: 3, .. } if method == "eth_estimateGas"
+        ));
+        assert_eq!(err.code(), "rpc_error");
+    }
+}
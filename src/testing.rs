@@ -0,0 +1,146 @@
+//! Utilities for testing code built on top of the prover.
+
+use crate::errors::ProofProviderError;
+use crate::provider::{BlockRootsProof, ProofProvider, StateId};
+use async_trait::async_trait;
+use ethereum_consensus::ssz::prelude::Node;
+
+/// The ways in which a [`MisbehavingProvider`] tampers with proofs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Misbehavior {
+    /// Flips a byte of the first witness (or node, for compact proofs).
+    FlippedWitnessByte,
+    /// Flips a byte of the leaf of single proofs.
+    FlippedLeaf,
+    /// Returns the (otherwise valid) proof of the next gindex instead of the requested one.
+    OffByOneGindex,
+}
+
+/// Provider that returns subtly wrong proofs, so that downstream users can
+/// assert that their pipelines reject invalid proofs end-to-end.
+pub struct MisbehavingProvider<P: ProofProvider> {
+    inner: P,
+    misbehavior: Misbehavior,
+}
+
+impl<P: ProofProvider> MisbehavingProvider<P> {
+    pub fn new(inner: P, misbehavior: Misbehavior) -> Self {
+        Self { inner, misbehavior }
+    }
+}
+
+fn flip_first_byte(node: &mut Node) {
+    node.0[0] ^= 0xff;
+}
+
+#[async_trait]
+impl<P: ProofProvider> ProofProvider for MisbehavingProvider<P> {
+    async fn get_state_proof(
+        &self,
+        state_id: &StateId,
+        gindex: u64,
+    ) -> Result<BlockRootsProof, ProofProviderError> {
+        if self.misbehavior == Misbehavior::OffByOneGindex {
+            return self.inner.get_state_proof(state_id, gindex + 1).await;
+        }
+
+        let mut proof = self.inner.get_state_proof(state_id, gindex).await?;
+        match (&mut proof, self.misbehavior) {
+            (BlockRootsProof::SingleProof { witnesses, .. }, Misbehavior::FlippedWitnessByte) => {
+                if let Some(witness) = witnesses.first_mut() {
+                    flip_first_byte(witness);
+                }
+            }
+            (BlockRootsProof::CompactProof { nodes, .. }, Misbehavior::FlippedWitnessByte) => {
+                if let Some(node) = nodes.first_mut() {
+                    flip_first_byte(node);
+                }
+            }
+            (BlockRootsProof::SingleProof { leaf, .. }, Misbehavior::FlippedLeaf) => {
+                flip_first_byte(leaf);
+            }
+            _ => {}
+        }
+
+        Ok(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockProofProvider;
+    use crate::{verify, AncestryProver};
+    use std::fs::File;
+
+    const RECENT_BLOCK_SLOT: u64 = 7_878_867;
+    const TARGET_BLOCK_SLOT: u64 = 7_877_867;
+    const RECENT_BLOCK_STATE_ROOT: &str =
+        "0x044adfafd8b8a889ea689470f630e61dddba22feb705c83eec032fac075de2ec";
+
+    fn file_provider() -> MockProofProvider {
+        let mut provider = MockProofProvider::new();
+        provider
+            .expect_get_state_proof()
+            .returning(|state_id, gindex| {
+                let filename = format!(
+                    "./src/testdata/state_prover/state_proof_{}_g{}.json",
+                    state_id, gindex
+                );
+                let file = File::open(filename)
+                    .map_err(|_| ProofProviderError::NotFoundError(gindex.to_string()))?;
+                Ok(serde_json::from_reader(file).unwrap())
+            });
+        provider
+    }
+
+    async fn prove_and_verify(misbehavior: Misbehavior) -> bool {
+        let prover = AncestryProver::new(MisbehavingProvider::new(file_provider(), misbehavior));
+        let proof = prover
+            .prove(
+                TARGET_BLOCK_SLOT,
+                RECENT_BLOCK_SLOT,
+                RECENT_BLOCK_STATE_ROOT,
+            )
+            .await
+            .unwrap();
+
+        verify(
+            &proof,
+            TARGET_BLOCK_SLOT,
+            RECENT_BLOCK_SLOT,
+            RECENT_BLOCK_STATE_ROOT,
+        )
+    }
+
+    #[tokio::test]
+    async fn it_should_return_proofs_that_fail_verification() {
+        assert!(!prove_and_verify(Misbehavior::FlippedWitnessByte).await);
+        assert!(!prove_and_verify(Misbehavior::FlippedLeaf).await);
+    }
+
+    #[tokio::test]
+    async fn it_should_request_the_next_gindex() {
+        let mut inner = MockProofProvider::new();
+        inner
+            .expect_get_state_proof()
+            .withf(|_, gindex| *gindex == 308_460)
+            .returning(|_, gindex| {
+                Ok(BlockRootsProof::SingleProof {
+                    gindex,
+                    witnesses: vec![],
+                    leaf: Default::default(),
+                })
+            });
+        let provider = MisbehavingProvider::new(inner, Misbehavior::OffByOneGindex);
+
+        match provider
+            .get_state_proof(&StateId::Head, 308_459)
+            .await
+            .unwrap()
+        {
+            BlockRootsProof::SingleProof { gindex, .. } => assert_eq!(gindex, 308_460),
+            _ => panic!("Invalid proof type"),
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Post-processing of proofs into the wire format of their consumers (compact
+//! proofs, canonical JSON, envelopes, ...), chained on the prover so that services
+//! get their final format directly from it.
+
+use crate::canonical::to_canonical_json;
+use crate::diff::nodes_by_gindex;
+use crate::errors::{AncestryProverError, ProofProviderError};
+#[cfg(feature = "providers")]
+use crate::prover::AncestryProver;
+#[cfg(feature = "providers")]
+use crate::provider::ProofProvider;
+use crate::provider::{descriptor_gindices, BlockRootsProof, NodeOrdering};
+use ::ssz_rs::compact_multiproofs::compute_proof_descriptor;
+use serde::Serialize;
+
+/// Step turning a proof (or the output of a previous step) into another representation.
+///
+/// Implemented for closures, e.g. `|proof: BlockRootsProof| Ok(abi_encode(&proof))`.
+pub trait ProofTransform<In = BlockRootsProof>: Send + Sync {
+    type Output;
+
+    fn transform(&self, input: In) -> Result<Self::Output, AncestryProverError>;
+
+    /// Chains `next` after this transform.
+    fn then<T: ProofTransform<Self::Output>>(self, next: T) -> Chain<Self, T>
+    where
+        Self: Sized,
+    {
+        Chain(self, next)
+    }
+}
+
+impl<In, Out, F> ProofTransform<In> for F
+where
+    F: Fn(In) -> Result<Out, AncestryProverError> + Send + Sync,
+{
+    type Output = Out;
+
+    fn transform(&self, input: In) -> Result<Out, AncestryProverError> {
+        self(input)
+    }
+}
+
+/// Two transforms applied one after the other, see [`ProofTransform::then`].
+#[derive(Debug, Clone, Copy)]
+pub struct Chain<A, B>(A, B);
+
+impl<In, A, B> ProofTransform<In> for Chain<A, B>
+where
+    A: ProofTransform<In>,
+    B: ProofTransform<A::Output>,
+{
+    type Output = B::Output;
+
+    fn transform(&self, input: In) -> Result<B::Output, AncestryProverError> {
+        self.1.transform(self.0.transform(input)?)
+    }
+}
+
+/// Converts single proofs into compact proofs of the same nodes, for consumers
+/// that only verify compact proofs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ToCompact;
+
+impl ProofTransform for ToCompact {
+    type Output = BlockRootsProof;
+
+    fn transform(&self, proof: BlockRootsProof) -> Result<BlockRootsProof, AncestryProverError> {
+        let BlockRootsProof::SingleProof { gindex, .. } = proof else {
+            return Ok(proof);
+        };
+
+        let invalid = |_| ProofProviderError::InvalidProofError();
+        let nodes = nodes_by_gindex(&proof).map_err(invalid)?;
+        let descriptor = compute_proof_descriptor(&[gindex as usize])
+            .map_err(|_| AncestryProverError::GindexOutOfBounds(gindex))?;
+        let nodes = descriptor_gindices(&descriptor)
+            .map_err(invalid)?
+            .iter()
+            .map(|gindex| nodes.get(gindex).copied())
+            .collect::<Option<Vec<_>>>()
+            .ok_or(ProofProviderError::InvalidProofError())?;
+
+        Ok(BlockRootsProof::CompactProof { descriptor, nodes })
+    }
+}
+
+/// Reorders the nodes of compact proofs emitted in another order, see [`BlockRootsProof::normalize`].
+#[derive(Debug, Clone, Copy)]
+pub struct Normalize(pub NodeOrdering);
+
+impl ProofTransform for Normalize {
+    type Output = BlockRootsProof;
+
+    fn transform(
+        &self,
+        mut proof: BlockRootsProof,
+    ) -> Result<BlockRootsProof, AncestryProverError> {
+        proof
+            .normalize(self.0)
+            .map_err(|_| ProofProviderError::InvalidProofError())?;
+        Ok(proof)
+    }
+}
+
+/// Serializes the output of the previous steps into canonical JSON, see [`to_canonical_json`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CanonicalJson;
+
+impl<In: Serialize> ProofTransform<In> for CanonicalJson {
+    type Output = String;
+
+    fn transform(&self, input: In) -> Result<String, AncestryProverError> {
+        to_canonical_json(&input).map_err(|err| ProofProviderError::SerializationError(err).into())
+    }
+}
+
+/// Prover whose proofs go through a transform, see [`AncestryProver::with_transform`].
+#[cfg(feature = "providers")]
+pub struct TransformedProver<P: ProofProvider, T> {
+    prover: AncestryProver<P>,
+    transform: T,
+}
+
+#[cfg(feature = "providers")]
+impl<P: ProofProvider> AncestryProver<P> {
+    /// Applies `transform` to the proofs of the prover, e.g.
+    /// `prover.with_transform(ToCompact.then(CanonicalJson))` to get canonical
+    /// JSON compact proofs.
+    pub fn with_transform<T: ProofTransform>(self, transform: T) -> TransformedProver<P, T> {
+        TransformedProver {
+            prover: self,
+            transform,
+        }
+    }
+}
+
+#[cfg(feature = "providers")]
+impl<P: ProofProvider, T: ProofTransform> TransformedProver<P, T> {
+    /// Like [`AncestryProver::prove`], returning the transformed proof.
+    pub async fn prove(
+        &self,
+        target_block_slot: u64,
+        recent_block_slot: u64,
+        recent_block_state_root: &str,
+    ) -> Result<T::Output, AncestryProverError> {
+        let proof = self
+            .prover
+            .prove(
+                target_block_slot,
+                recent_block_slot,
+                recent_block_state_root,
+            )
+            .await?;
+
+        self.transform.transform(proof)
+    }
+
+    /// Returns the underlying prover, e.g. to get untransformed proofs.
+    pub fn prover(&self) -> &AncestryProver<P> {
+        &self.prover
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::BlockRootsTree;
+    use crate::provider::Verify;
+    use alloy_primitives::FixedBytes;
+    use ethereum_consensus::ssz::prelude::Node;
+
+    #[test]
+    fn it_should_chain_transforms() {
+        let leaves = (0..16u8).map(Node::repeat_byte).collect::<Vec<_>>();
+        let tree = BlockRootsTree::new(&leaves);
+        let single = tree.prove_leaf(5);
+
+        let compact = ToCompact.transform(single.clone()).unwrap();
+        assert!(matches!(compact, BlockRootsProof::CompactProof { .. }));
+        assert_eq!(compact.verify(FixedBytes(tree.root().0)), Ok(()));
+
+        let transform = ToCompact
+            .then(CanonicalJson)
+            .then(|json: String| Ok(json.len()));
+        assert_eq!(
+            transform.transform(single).unwrap(),
+            to_canonical_json(&compact).unwrap().len()
+        );
+    }
+
+    #[cfg(feature = "providers")]
+    #[tokio::test]
+    async fn it_should_transform_the_proofs_of_the_prover() {
+        use crate::sim::{SimChain, SimProvider};
+        use std::sync::Arc;
+
+        let chain = Arc::new(SimChain::new(100));
+        let anchor_root = format!("{:?}", chain.state_root(100));
+        let prover = AncestryProver::new(SimProvider::new(chain.clone()))
+            .with_transform(Normalize(NodeOrdering::DepthFirst).then(CanonicalJson));
+
+        let json = prover.prove(90, 100, &anchor_root).await.unwrap();
+        let proof = prover.prover().prove(90, 100, &anchor_root).await.unwrap();
+
+        assert_eq!(json, to_canonical_json(&proof).unwrap());
+    }
+}
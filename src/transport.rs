@@ -0,0 +1,264 @@
+//! Transports of the HTTP providers other than TCP, e.g. for beacon nodes that expose
+//! their API on a Unix domain socket in sidecar deployments.
+//!
+//! Any other transport, e.g. an in-process test harness, can be plugged in as a
+//! `tower::Service` with the `with_service` constructors of the providers.
+
+use crate::errors::ProofProviderError;
+use crate::middleware::DEFAULT_MAX_RESPONSE_SIZE;
+use futures::future::BoxFuture;
+use hyper::body::HttpBody;
+use reqwest::header::HOST;
+use reqwest::{Request, Response, ResponseBuilderExt};
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::task::{Context, Poll};
+use tokio::net::UnixStream;
+use tower::Service;
+
+/// Service sending the requests of a provider to the HTTP server listening on a
+/// Unix domain socket. Only the path and query of the request urls are used, e.g.
+///
+/// ```no_run
+/// use ancestry_prover::middleware;
+/// use ancestry_prover::transport::UnixSocketService;
+/// use ancestry_prover::LodestarProvider;
+///
+/// let service = middleware::boxed(UnixSocketService::new("/run/lodestar/api.sock"));
+/// let provider = LodestarProvider::with_service("http://localhost".to_string(), service);
+/// ```
+#[derive(Clone, Debug)]
+pub struct UnixSocketService {
+    path: PathBuf,
+    max_response_size: usize,
+}
+
+impl UnixSocketService {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+        }
+    }
+
+    /// Limits the size of the response bodies read from the socket, defaults to
+    /// [`DEFAULT_MAX_RESPONSE_SIZE`].
+    pub fn with_max_response_size(mut self, bytes: usize) -> Self {
+        self.max_response_size = bytes;
+        self
+    }
+}
+
+impl Service<Request> for UnixSocketService {
+    type Response = Response;
+    type Error = ProofProviderError;
+    type Future = BoxFuture<'static, Result<Response, ProofProviderError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        Box::pin(send(self.path.clone(), req, self.max_response_size))
+    }
+}
+
+/// Sends a request over a new HTTP/1 connection on the socket at `path`, reading
+/// at most `limit` bytes of the response body.
+async fn send(path: PathBuf, req: Request, limit: usize) -> Result<Response, ProofProviderError> {
+    let url = req.url().to_string();
+    let error = |err: &dyn Display| ProofProviderError::TransportError {
+        url: url.clone(),
+        message: err.to_string(),
+    };
+
+    let stream = UnixStream::connect(&path)
+        .await
+        .map_err(|err| error(&err))?;
+    let (mut sender, connection) = hyper::client::conn::handshake(stream)
+        .await
+        .map_err(|err| error(&err))?;
+    // failures of the connection are reported by the request
+    tokio::spawn(async move {
+        let _ = connection.await;
+    });
+
+    let path_and_query = match req.url().query() {
+        Some(query) => format!("{}?{}", req.url().path(), query),
+        None => req.url().path().to_string(),
+    };
+    let mut builder = hyper::Request::builder()
+        .method(req.method().clone())
+        .uri(path_and_query);
+    for (name, value) in req.headers() {
+        builder = builder.header(name, value);
+    }
+    if !req.headers().contains_key(HOST) {
+        builder = builder.header(HOST, req.url().host_str().unwrap_or("localhost"));
+    }
+    let body = req
+        .body()
+        .and_then(|body| body.as_bytes())
+        .map(<[u8]>::to_vec)
+        .unwrap_or_default();
+    let request = builder
+        .body(hyper::Body::from(body))
+        .map_err(|err| error(&err))?;
+
+    let (parts, mut body) = sender
+        .send_request(request)
+        .await
+        .map_err(|err| error(&err))?
+        .into_parts();
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|err| error(&err))?;
+        if bytes.len() + chunk.len() > limit {
+            return Err(ProofProviderError::ResponseTooLarge { url, limit });
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    // reqwest only knows the url of the responses it sent the request of
+    let mut builder = hyper::Response::builder()
+        .status(parts.status)
+        .version(parts.version)
+        .url(req.url().clone());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = parts.headers;
+    }
+    let response = builder.body(bytes).map_err(|err| error(&err))?;
+
+    Ok(Response::from(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware;
+    use crate::provider::ProofProvider;
+    use crate::LodestarProvider;
+    use ethereum_consensus::ssz::prelude::Node;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::UnixListener;
+
+    #[tokio::test]
+    async fn it_should_send_requests_over_unix_sockets() {
+        let path = std::env::temp_dir().join(format!("beacon_api_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let root = Node::repeat_byte(3);
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                let mut buf = [0; 1024];
+                let read = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..read]);
+            }
+            let body = format!(r#"{{"data":{{"root":"{}"}}}}"#, root);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let service = middleware::boxed(UnixSocketService::new(&path));
+        let provider = LodestarProvider::with_service("http://localhost".to_string(), service);
+        let block_root = provider.get_block_root(100).await;
+        let request = server.await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(block_root.unwrap(), root);
+        assert!(request.starts_with("GET /eth/v1/beacon/blocks/100/root HTTP/1.1\r\n"));
+    }
+
+    /// Serves a single request on a new socket with the raw `response`.
+    fn serve_once(name: &str, response: String) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("{}_{}.sock", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                let mut buf = [0; 1024];
+                let read = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..read]);
+            }
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+        path
+    }
+
+    #[tokio::test]
+    async fn it_should_bound_the_responses_read_from_sockets() {
+        let body = "0".repeat(1024);
+        let path = serve_once(
+            "beacon_api_large",
+            format!(
+                "HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n400\r\n{body}\r\n0\r\n\r\n"
+            ),
+        );
+        let service = UnixSocketService::new(&path).with_max_response_size(512);
+        let request = Request::new(
+            reqwest::Method::GET,
+            "http://localhost/eth/v1/node/version".parse().unwrap(),
+        );
+
+        let result = middleware::send(&middleware::boxed(service), request).await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            result,
+            Err(ProofProviderError::ResponseTooLarge { ref url, limit: 512 }) if url == "http://localhost/eth/v1/node/version"
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_keep_the_request_url_on_responses() {
+        let path = serve_once(
+            "beacon_api_url",
+            "HTTP/1.1 429 Too Many Requests\r\nretry-after: 3\r\ncontent-length: 0\r\n\r\n"
+                .to_string(),
+        );
+        let service = UnixSocketService::new(&path);
+        let request = Request::new(
+            reqwest::Method::GET,
+            "http://localhost/eth/v1/node/version".parse().unwrap(),
+        );
+
+        let response = middleware::send(&middleware::boxed(service), request)
+            .await
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            response.url().as_str(),
+            "http://localhost/eth/v1/node/version"
+        );
+        assert!(matches!(
+            middleware::rate_limited(&response),
+            Some(ProofProviderError::RateLimited { ref url, status: 429, .. }) if url == "http://localhost/eth/v1/node/version"
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_report_unreachable_sockets() {
+        let service = UnixSocketService::new("/nonexistent/beacon_api.sock");
+        let request = Request::new(
+            reqwest::Method::GET,
+            "http://localhost/eth/v1/node/version".parse().unwrap(),
+        );
+
+        let result = middleware::send(&middleware::boxed(service), request).await;
+
+        assert!(matches!(
+            result,
+            Err(ProofProviderError::TransportError { ref url, .. }) if url == "http://localhost/eth/v1/node/version"
+        ));
+    }
+}
@@ -0,0 +1,124 @@
+//! Validation of proof requests, turning common integration mistakes into specific
+//! errors (with a remediation hint, see [`AncestryProverError::hint`]) before they
+//! reach a provider.
+
+use crate::distance::{distance, MAX_DISTANCE_IN_WINDOW};
+use crate::errors::AncestryProverError;
+use crate::provider::StateId;
+use ethereum_consensus::ssz::prelude::Node;
+use std::str::FromStr;
+
+/// Checks that the target block strictly precedes the anchor block, whose state
+/// only holds the roots of earlier blocks.
+pub fn validate_target_slot(target_slot: u64, anchor_slot: u64) -> Result<(), AncestryProverError> {
+    if target_slot >= anchor_slot {
+        return Err(AncestryProverError::TargetNotBeforeAnchor {
+            target: target_slot,
+            anchor: anchor_slot,
+        });
+    }
+
+    Ok(())
+}
+
+/// Checks that the target block is in the `block_roots` window of the anchor block,
+/// the only targets that can be proven for now.
+pub fn validate_in_window(target_slot: u64, anchor_slot: u64) -> Result<(), AncestryProverError> {
+    let Some(distance) = distance(anchor_slot, target_slot) else {
+        return validate_target_slot(target_slot, anchor_slot);
+    };
+    if !distance.fits_in_window() {
+        return Err(AncestryProverError::TargetTooOld {
+            distance: distance.slots(),
+            max_supported: MAX_DISTANCE_IN_WINDOW,
+        });
+    }
+
+    Ok(())
+}
+
+/// Parses a state root, rejecting malformed roots before they reach the backend.
+pub fn parse_state_root(state_root: &str) -> Result<Node, AncestryProverError> {
+    Node::from_str(state_root)
+        .map_err(|_| AncestryProverError::InvalidStateRoot(state_root.to_string()))
+}
+
+/// Parses the root of an anchor block, for proofs anchored at block roots.
+pub fn parse_block_root(block_root: &str) -> Result<Node, AncestryProverError> {
+    Node::from_str(block_root).map_err(|_| {
+        AncestryProverError::InvalidAnchor(format!(
+            "{block_root} is not a block root, expected a 0x-prefixed hex string of 32 bytes"
+        ))
+    })
+}
+
+/// Parses an anchor descriptor: `head`, `finalized`, `justified`, a slot or a
+/// 0x-prefixed state root, the grammar of anchors given as user input.
+pub fn parse_anchor(descriptor: &str) -> Result<StateId, AncestryProverError> {
+    let descriptor = descriptor.trim();
+    StateId::from_str(&descriptor.to_ascii_lowercase())
+        .map_err(|_| AncestryProverError::InvalidAnchor(descriptor.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_reject_targets_not_before_the_anchor() {
+        assert!(validate_target_slot(7_879_316, 7_879_323).is_ok());
+
+        for target_slot in [7_879_323, 7_879_324] {
+            let err = validate_target_slot(target_slot, 7_879_323).unwrap_err();
+            assert!(matches!(
+                err,
+                AncestryProverError::TargetNotBeforeAnchor { target, anchor: 7_879_323 } if target == target_slot
+            ));
+            assert!(err.hint().is_some());
+        }
+    }
+
+    #[test]
+    fn it_should_reject_targets_out_of_the_window() {
+        assert!(validate_in_window(7_871_132, 7_879_323).is_ok());
+        assert!(matches!(
+            validate_in_window(7_871_131, 7_879_323),
+            Err(AncestryProverError::TargetTooOld {
+                distance: 8_192,
+                max_supported: 8_191
+            })
+        ));
+        assert!(matches!(
+            validate_in_window(7_879_323, 7_879_323),
+            Err(AncestryProverError::TargetNotBeforeAnchor { .. })
+        ));
+    }
+
+    #[test]
+    fn it_should_reject_malformed_block_roots() {
+        assert!(parse_block_root(
+            "0xa16855f71e99a620029e6b7c683abab542f66ee87c3dd8c72424568348f28b33"
+        )
+        .is_ok());
+        assert!(matches!(
+            parse_block_root("0xa168"),
+            Err(AncestryProverError::InvalidAnchor(message)) if message.contains("block root")
+        ));
+    }
+
+    #[test]
+    fn it_should_parse_anchor_descriptors() {
+        let root = "0xa16855f71e99a620029e6b7c683abab542f66ee87c3dd8c72424568348f28b33";
+
+        assert_eq!(parse_anchor("head").unwrap(), StateId::Head);
+        assert_eq!(parse_anchor(" Finalized ").unwrap(), StateId::Finalized);
+        assert_eq!(parse_anchor("7879323").unwrap(), StateId::Slot(7_879_323));
+        assert_eq!(parse_anchor(root).unwrap().to_string(), root);
+
+        for descriptor in ["latest", "-1", "0x1234", ""] {
+            let err = parse_anchor(descriptor).unwrap_err();
+            assert!(matches!(err, AncestryProverError::InvalidAnchor(_)));
+            assert!(err.hint().is_some());
+        }
+    }
+}
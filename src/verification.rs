@@ -0,0 +1,364 @@
+//! Dependency-light verification of block roots proofs, operating on raw roots
+//! only, so that it can be bound from FFI layers or mirrored in zk circuits.
+
+use crate::diff::nodes_by_gindex;
+use crate::distance::distance;
+use crate::errors::VerifyError;
+use crate::merkle::hash_pair;
+use crate::provider::{BlockRootsProof, Verify};
+use alloy_primitives::FixedBytes;
+use ethereum_consensus::capella::presets::mainnet::SLOTS_PER_HISTORICAL_ROOT;
+use ethereum_consensus::ssz::prelude::Node;
+
+/// Gindex of the `block_roots` field in the (32 leaf) beacon state container.
+pub const BLOCK_ROOTS_FIELD_GINDEX: u64 = 37;
+
+/// Gindex of the `slot` field in the (32 leaf) beacon state container.
+pub const SLOT_FIELD_GINDEX: u64 = 34;
+
+/// Depth of the `block_roots` vector of the mainnet preset, below the `block_roots` field.
+pub const BLOCK_ROOTS_DEPTH: u32 = SLOTS_PER_HISTORICAL_ROOT.ilog2();
+
+/// Depth of the `block_roots` entries in the beacon state of the mainnet preset,
+/// i.e. the number of witnesses of their single proofs.
+pub const BLOCK_ROOTS_ENTRY_DEPTH: u32 = BLOCK_ROOTS_FIELD_GINDEX.ilog2() + BLOCK_ROOTS_DEPTH;
+
+/// Gindex of the first `block_roots` entry in the beacon state of the mainnet preset.
+pub const BLOCK_ROOTS_BASE_GINDEX: u64 = BLOCK_ROOTS_FIELD_GINDEX << BLOCK_ROOTS_DEPTH;
+
+/// Returns the gindex of the `block_roots` entry of `slot` in the beacon state of
+/// the mainnet preset, like [`block_roots_gindex`] with its window.
+pub const fn mainnet_block_roots_gindex(slot: u64) -> u64 {
+    BLOCK_ROOTS_BASE_GINDEX + slot % SLOTS_PER_HISTORICAL_ROOT as u64
+}
+
+/// Returns the gindex of the `block_roots` entry of `slot`, for a `block_roots`
+/// vector of `window` (`SLOTS_PER_HISTORICAL_ROOT`) entries.
+pub const fn block_roots_gindex(slot: u64, window: u64) -> Result<u64, VerifyError> {
+    if !window.is_power_of_two() {
+        return Err(VerifyError::InvalidWindow(window));
+    }
+
+    Ok(BLOCK_ROOTS_FIELD_GINDEX * window + slot % window)
+}
+
+/// Verifies that `proof` proves the block root of `target_slot` in the
+/// `block_roots` vector of the beacon state with root `anchor_root`, and
+/// returns the proven block root.
+///
+/// `window` is the length of the `block_roots` vector (`SLOTS_PER_HISTORICAL_ROOT`).
+pub fn verify_block_roots_proof(
+    anchor_root: [u8; 32],
+    target_slot: u64,
+    window: u64,
+    proof: &BlockRootsProof,
+) -> Result<[u8; 32], VerifyError> {
+    let expected = block_roots_gindex(target_slot, window)?;
+    let leaf = proven_leaf(proof, expected)?;
+
+    proof.verify(FixedBytes(anchor_root))?;
+
+    Ok(leaf.0)
+}
+
+/// Like [`verify_block_roots_proof`], but also checks that the `block_roots` entry
+/// of `target_slot` holds its block root in the state of `anchor_slot`, i.e. that
+/// the target is less than `window` slots before the anchor.
+///
+/// Gindices only identify positions in the `block_roots` ring buffer, so a proof
+/// also verifies for the slots whole windows away from its target, before the entry
+/// was filled or after it was overwritten, without proving their block roots.
+pub fn verify_block_roots_proof_for_anchor(
+    anchor_root: [u8; 32],
+    anchor_slot: u64,
+    target_slot: u64,
+    window: u64,
+    proof: &BlockRootsProof,
+) -> Result<[u8; 32], VerifyError> {
+    if !distance(anchor_slot, target_slot).is_some_and(|distance| distance.fits_in(window)) {
+        return Err(VerifyError::AmbiguousTarget {
+            target: target_slot,
+            anchor: anchor_slot,
+        });
+    }
+
+    verify_block_roots_proof(anchor_root, target_slot, window, proof)
+}
+
+/// Like [`verify_block_roots_proof`], but also rejects proofs of a zero leaf, or
+/// whose other nodes are all zero. Such proofs can verify structurally against
+/// degenerate roots, but never prove an actual block.
+///
+/// Single zero witnesses are accepted, as they are legitimate next to genesis,
+/// where the `block_roots` entries of the slots to come are still zero.
+pub fn verify_block_roots_proof_strict(
+    anchor_root: [u8; 32],
+    target_slot: u64,
+    window: u64,
+    proof: &BlockRootsProof,
+) -> Result<[u8; 32], VerifyError> {
+    let expected = block_roots_gindex(target_slot, window)?;
+    let leaf = proven_leaf(proof, expected)?;
+    if leaf == Node::default() {
+        return Err(VerifyError::ZeroLeaf(expected));
+    }
+
+    let mut branch = match proof {
+        BlockRootsProof::SingleProof { witnesses, .. } => witnesses.clone(),
+        BlockRootsProof::CompactProof { nodes, .. } => {
+            let gindices = proof.gindices()?;
+            nodes
+                .iter()
+                .zip(gindices)
+                .filter(|(_, gindex)| *gindex != expected)
+                .map(|(node, _)| *node)
+                .collect()
+        }
+    };
+    branch.retain(|node| *node != Node::default());
+    if branch.is_empty() {
+        return Err(VerifyError::ZeroBranch(expected));
+    }
+
+    verify_block_roots_proof(anchor_root, target_slot, window, proof)
+}
+
+/// Computes the root of the tree that `proof` reveals nodes of, e.g. the state root
+/// of a proof fetched for a state identified by slot.
+pub fn proof_root(proof: &BlockRootsProof) -> Result<Node, VerifyError> {
+    let mut nodes = nodes_by_gindex(proof)?;
+
+    // the deepest node left always has its sibling revealed or computed already
+    while let Some((gindex, node)) = nodes.pop_last() {
+        match gindex {
+            0 => break,
+            1 => {
+                // e.g. rejects single proofs with more witnesses than their depth
+                proof.verify(FixedBytes(node.0))?;
+                return Ok(node);
+            }
+            _ => {}
+        }
+        let sibling = nodes
+            .remove(&(gindex ^ 1))
+            .ok_or(VerifyError::InvalidProof)?;
+        let parent = match gindex % 2 {
+            0 => hash_pair(&node, &sibling),
+            _ => hash_pair(&sibling, &node),
+        };
+        if nodes
+            .insert(gindex / 2, parent)
+            .is_some_and(|revealed| revealed != parent)
+        {
+            return Err(VerifyError::InvalidProof);
+        }
+    }
+
+    Err(VerifyError::InvalidProof)
+}
+
+/// Returns the leaf of `proof` at `expected` gindex, without verifying the proof.
+pub(crate) fn proven_leaf(proof: &BlockRootsProof, expected: u64) -> Result<Node, VerifyError> {
+    let leaf = match proof {
+        BlockRootsProof::SingleProof { gindex, leaf, .. } => {
+            if *gindex != expected {
+                return Err(VerifyError::UnexpectedGindex {
+                    expected,
+                    actual: *gindex,
+                });
+            }
+            *leaf
+        }
+        BlockRootsProof::CompactProof { nodes, .. } => {
+            let position = proof
+                .gindices()?
+                .iter()
+                .position(|gindex| *gindex == expected)
+                .ok_or(VerifyError::MissingGindex(expected))?;
+            *nodes
+                .get(position)
+                .ok_or(VerifyError::MissingGindex(expected))?
+        }
+    };
+
+    Ok(leaf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    const ANCHOR_ROOT: &str = "0x044adfafd8b8a889ea689470f630e61dddba22feb705c83eec032fac075de2ec";
+
+    fn test_proof() -> BlockRootsProof {
+        let file = File::open(format!(
+            "./src/testdata/state_prover/state_proof_{}_g308459.json",
+            ANCHOR_ROOT
+        ))
+        .unwrap();
+        serde_json::from_reader(file).unwrap()
+    }
+
+    fn anchor_root() -> [u8; 32] {
+        hex::decode(&ANCHOR_ROOT[2..]).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn it_should_match_the_beacon_state_gindex() {
+        for slot in [0, 1, 7_877_867, 8191, 8192] {
+            assert_eq!(
+                block_roots_gindex(slot, 8192).unwrap(),
+                crate::prover::block_roots_gindex(slot)
+            );
+            assert_eq!(
+                mainnet_block_roots_gindex(slot),
+                crate::prover::block_roots_gindex(slot)
+            );
+        }
+
+        const GINDEX: u64 = mainnet_block_roots_gindex(8191);
+        assert_eq!(GINDEX, BLOCK_ROOTS_BASE_GINDEX + 8191);
+        assert_eq!(BLOCK_ROOTS_ENTRY_DEPTH, 18);
+    }
+
+    #[test]
+    fn it_should_compute_the_root_of_proofs() {
+        assert_eq!(proof_root(&test_proof()), Ok(Node::from(anchor_root())));
+
+        let mut proof = test_proof();
+        if let BlockRootsProof::SingleProof { gindex, .. } = &mut proof {
+            *gindex = 0;
+        }
+        assert!(proof_root(&proof).is_err());
+    }
+
+    #[test]
+    fn it_should_return_the_proven_block_root() {
+        let proof = test_proof();
+        let leaf = match &proof {
+            BlockRootsProof::SingleProof { leaf, .. } => leaf.0,
+            _ => panic!("Invalid proof type"),
+        };
+
+        assert_eq!(
+            verify_block_roots_proof(anchor_root(), 7_877_867, 8192, &proof),
+            Ok(leaf)
+        );
+    }
+
+    #[test]
+    fn it_should_reject_proofs_for_another_slot() {
+        assert_eq!(
+            verify_block_roots_proof(anchor_root(), 7_877_868, 8192, &test_proof()),
+            Err(VerifyError::UnexpectedGindex {
+                expected: 308_460,
+                actual: 308_459
+            })
+        );
+    }
+
+    #[test]
+    fn it_should_reject_targets_outside_of_the_anchor_window() {
+        let proof = test_proof();
+        let verify = |target_slot| {
+            verify_block_roots_proof_for_anchor(anchor_root(), 7_878_867, target_slot, 8192, &proof)
+        };
+
+        assert!(verify(7_877_867).is_ok());
+        // same gindex, overwritten by the block of 7_877_867
+        assert_eq!(
+            verify(7_877_867 - 8192),
+            Err(VerifyError::AmbiguousTarget {
+                target: 7_877_867 - 8192,
+                anchor: 7_878_867
+            })
+        );
+        // same gindex, not filled yet
+        assert!(matches!(
+            verify(7_877_867 + 8192),
+            Err(VerifyError::AmbiguousTarget { .. })
+        ));
+    }
+
+    #[test]
+    fn it_should_reject_proofs_for_another_anchor() {
+        assert_eq!(
+            verify_block_roots_proof([0; 32], 7_877_867, 8192, &test_proof()),
+            Err(VerifyError::InvalidProof)
+        );
+    }
+
+    #[test]
+    fn it_should_reject_zero_leaves_in_strict_mode() {
+        // all-zero proof, which verifies against the root of a tree of zero leaves
+        let (gindex, zero) = (block_roots_gindex(100, 8192).unwrap(), Node::default());
+        let root = (0..18).fold(zero, |node, level| match (gindex >> level) & 1 {
+            1 => crate::merkle::hash_pair(&zero, &node),
+            _ => crate::merkle::hash_pair(&node, &zero),
+        });
+        let proof = BlockRootsProof::SingleProof {
+            gindex,
+            witnesses: vec![zero; 18],
+            leaf: zero,
+        };
+
+        assert_eq!(
+            verify_block_roots_proof(root.0, 100, 8192, &proof),
+            Ok([0; 32])
+        );
+        assert_eq!(
+            verify_block_roots_proof_strict(root.0, 100, 8192, &proof),
+            Err(VerifyError::ZeroLeaf(303_204))
+        );
+    }
+
+    #[test]
+    fn it_should_reject_zero_branches_in_strict_mode() {
+        let proof = BlockRootsProof::SingleProof {
+            gindex: 308_459,
+            witnesses: vec![Node::default(); 18],
+            leaf: Node::repeat_byte(1),
+        };
+
+        assert_eq!(
+            verify_block_roots_proof_strict(anchor_root(), 7_877_867, 8192, &proof),
+            Err(VerifyError::ZeroBranch(308_459))
+        );
+        assert_eq!(
+            verify_block_roots_proof_strict(anchor_root(), 7_877_867, 8192, &test_proof()),
+            verify_block_roots_proof(anchor_root(), 7_877_867, 8192, &test_proof())
+        );
+    }
+
+    #[cfg(feature = "providers")]
+    #[test]
+    fn it_should_accept_zero_witnesses_next_to_genesis_in_strict_mode() {
+        use crate::sim::SimChain;
+
+        // the block_roots entry of slot 99 is still zero in the state of slot 99
+        let chain = SimChain::new(99);
+        let anchor_root = chain.state_root(99).0;
+        for proof in [
+            chain.prove_block_root(98, 99),
+            chain.prove_block_roots(&[98], 99).unwrap(),
+        ] {
+            assert_eq!(
+                verify_block_roots_proof_strict(anchor_root, 98, 8192, &proof),
+                Ok(chain.block_root(98).0)
+            );
+        }
+        assert_eq!(
+            verify_block_roots_proof_strict(anchor_root, 0, 8192, &chain.prove_block_root(0, 99)),
+            Ok(chain.block_root(0).0)
+        );
+    }
+
+    #[test]
+    fn it_should_reject_invalid_windows() {
+        assert_eq!(
+            verify_block_roots_proof(anchor_root(), 7_877_867, 8000, &test_proof()),
+            Err(VerifyError::InvalidWindow(8000))
+        );
+    }
+}
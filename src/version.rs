@@ -0,0 +1,72 @@
+//! Version metadata of the crate, embedded in the artifacts it produces so that
+//! consumers can require a minimum prover version, e.g. after security fixes.
+
+use serde::{Deserialize, Serialize};
+
+/// Version of the crate.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Forks whose beacon state layout matches the `block_roots` gindices computed by the crate.
+pub const SUPPORTED_FORKS: &[&str] = &["phase0", "altair", "bellatrix", "capella", "deneb"];
+
+/// Version of the prover that generated an artifact, and the forks it supports.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VersionInfo {
+    pub version: String,
+    pub forks: Vec<String>,
+}
+
+impl VersionInfo {
+    /// Returns the version metadata of this build of the crate.
+    pub fn current() -> Self {
+        Self {
+            version: VERSION.to_string(),
+            forks: SUPPORTED_FORKS
+                .iter()
+                .map(|fork| fork.to_string())
+                .collect(),
+        }
+    }
+
+    /// Returns whether the version is at least `minimum` (`major.minor.patch`),
+    /// ignoring pre-release and build metadata. Unparseable versions are rejected.
+    pub fn is_at_least(&self, minimum: &str) -> bool {
+        match (parse_version(&self.version), parse_version(minimum)) {
+            (Some(version), Some(minimum)) => version >= minimum,
+            _ => false,
+        }
+    }
+
+    pub fn supports_fork(&self, fork: &str) -> bool {
+        self.forks.iter().any(|supported| supported == fork)
+    }
+}
+
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let core = version.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+    let version = (parts.next()??, parts.next()??, parts.next()??);
+
+    parts.next().is_none().then_some(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_compare_versions() {
+        let info = |version: &str| VersionInfo {
+            version: version.to_string(),
+            forks: vec![],
+        };
+
+        assert!(info("0.2.0").is_at_least("0.1.9"));
+        assert!(info("0.10.0").is_at_least("0.9.0"));
+        assert!(info("1.0.0-rc.1").is_at_least("1.0.0"));
+        assert!(!info("0.1.0").is_at_least("0.1.1"));
+        assert!(!info("0.1").is_at_least("0.1.0"));
+        assert!(VersionInfo::current().is_at_least(VERSION));
+        assert!(VersionInfo::current().supports_fork("deneb"));
+    }
+}